@@ -0,0 +1,221 @@
+use crate::{
+    graph::NodeId,
+    hash::{BuildHasher, HashMap},
+    sync::RwLock,
+};
+use std::{
+    collections::{hash_map::Entry, HashSet},
+    convert::TryFrom,
+    fmt,
+    sync::Arc,
+};
+use tracing::field::{Field, Visit};
+
+/// How the values of a span field are combined onto the edge into the span,
+/// see [`Builder::aggregate_edges`].
+///
+/// Every value recorded for the field, when the span is created or later
+/// with `Span::record`, is combined into the [`EdgeInfo::value`] of the edge
+/// from its caller. Counts and durations are recorded as before.
+///
+/// [`Builder::aggregate_edges`]: crate::Builder::aggregate_edges
+/// [`EdgeInfo::value`]: crate::EdgeInfo::value
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum Aggregation {
+    /// The sum of the numeric values of the field.
+    Sum(&'static str),
+    /// The largest numeric value of the field, such as the peak number of
+    /// requests in flight.
+    Max(&'static str),
+    /// The smallest numeric value of the field.
+    Min(&'static str),
+    /// The number of distinct values of the field, such as the number of
+    /// users calling an endpoint.
+    Distinct(&'static str),
+    /// The numeric values of the field folded by a function of the current
+    /// value and the next one, see [`Aggregation::custom`].
+    Custom(&'static str, Arc<dyn Fn(u64, u64) -> u64 + Send + Sync>),
+}
+
+impl Aggregation {
+    /// Returns an aggregation folding the numeric values of `field` by
+    /// calling `fold` with the current value and the next one. The first
+    /// value of an edge is taken as is.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{Aggregation, GraphLayer};
+    ///
+    /// #[tracing::instrument]
+    /// fn handler() {
+    ///     for flags in [0b001, 0b100] {
+    ///         query(flags);
+    ///     }
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn query(flags: u64) {}
+    ///
+    /// let snapshot = GraphLayer::builder()
+    ///     .aggregate_edges(Aggregation::custom("flags", |a, b| a | b))
+    ///     .record(handler);
+    /// assert_eq!(snapshot.edge("handler", "query").unwrap().value, Some(0b101));
+    /// ```
+    pub fn custom<F>(field: &'static str, fold: F) -> Self
+    where
+        F: Fn(u64, u64) -> u64 + Send + Sync + 'static,
+    {
+        Self::Custom(field, Arc::new(fold))
+    }
+
+    /// The name of the aggregated field.
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::Sum(field)
+            | Self::Max(field)
+            | Self::Min(field)
+            | Self::Distinct(field)
+            | Self::Custom(field, _) => field,
+        }
+    }
+
+    /// Combines the `next` numeric value into the `current` value of an edge.
+    fn fold(&self, current: u64, next: u64) -> u64 {
+        match self {
+            Self::Sum(_) => current.saturating_add(next),
+            Self::Max(_) => current.max(next),
+            Self::Min(_) => current.min(next),
+            Self::Distinct(_) => current,
+            Self::Custom(_, fold) => fold(current, next),
+        }
+    }
+}
+
+impl fmt::Debug for Aggregation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sum(field) => f.debug_tuple("Sum").field(field).finish(),
+            Self::Max(field) => f.debug_tuple("Max").field(field).finish(),
+            Self::Min(field) => f.debug_tuple("Min").field(field).finish(),
+            Self::Distinct(field) => f.debug_tuple("Distinct").field(field).finish(),
+            Self::Custom(field, _) => f.debug_tuple("Custom").field(field).finish(),
+        }
+    }
+}
+
+/// The aggregated values of every edge, see [`Aggregation`].
+#[derive(Debug)]
+pub(crate) struct Aggregates {
+    aggregation: Aggregation,
+    hasher: BuildHasher,
+    edges: RwLock<HashMap<(NodeId, NodeId), Aggregate>>,
+}
+
+/// The aggregated value of an edge.
+#[derive(Debug)]
+enum Aggregate {
+    Value(u64),
+    Distinct(HashSet<String, BuildHasher>),
+}
+
+impl Aggregates {
+    pub(crate) fn new(aggregation: Aggregation, hasher: BuildHasher) -> Self {
+        Self {
+            aggregation,
+            edges: RwLock::new(HashMap::with_hasher(hasher.clone())),
+            hasher,
+        }
+    }
+
+    pub(crate) fn field(&self) -> &'static str {
+        self.aggregation.field()
+    }
+
+    /// Combines `value` into the value of the edge from `caller` to `callee`.
+    pub(crate) fn record(&self, caller: NodeId, callee: NodeId, value: FieldValue) {
+        let mut edges = self.edges.write();
+        match (&self.aggregation, value) {
+            (Aggregation::Distinct(_), value) => {
+                let value = match value {
+                    FieldValue::Number(number) => number.to_string(),
+                    FieldValue::Text(text) => text,
+                };
+                let aggregate = edges.entry((caller, callee)).or_insert_with(|| {
+                    Aggregate::Distinct(HashSet::with_hasher(self.hasher.clone()))
+                });
+                if let Aggregate::Distinct(values) = aggregate {
+                    values.insert(value);
+                }
+            }
+            (aggregation, FieldValue::Number(next)) => match edges.entry((caller, callee)) {
+                Entry::Occupied(mut entry) => {
+                    if let Aggregate::Value(current) = entry.get_mut() {
+                        *current = aggregation.fold(*current, next);
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(Aggregate::Value(next));
+                }
+            },
+            (_, FieldValue::Text(_)) => {}
+        }
+    }
+
+    /// Returns the value of the edge from `caller` to `callee`, if any of its
+    /// values were recorded.
+    pub(crate) fn value(&self, caller: NodeId, callee: NodeId) -> Option<u64> {
+        match self.edges.read().get(&(caller, callee))? {
+            Aggregate::Value(value) => Some(*value),
+            Aggregate::Distinct(values) => Some(values.len() as u64),
+        }
+    }
+}
+
+/// A value of the aggregated field.
+#[derive(Debug)]
+pub(crate) enum FieldValue {
+    Number(u64),
+    Text(String),
+}
+
+/// Finds the value of the aggregated field among the fields of a span.
+#[derive(Debug)]
+pub(crate) struct ValueVisitor {
+    field: &'static str,
+    pub(crate) value: Option<FieldValue>,
+}
+
+impl ValueVisitor {
+    pub(crate) fn new(field: &'static str) -> Self {
+        Self { field, value: None }
+    }
+}
+
+impl Visit for ValueVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == self.field {
+            self.value = Some(FieldValue::Number(value));
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if field.name() == self.field {
+            self.value = Some(match u64::try_from(value) {
+                Ok(value) => FieldValue::Number(value),
+                Err(_) => FieldValue::Text(value.to_string()),
+            });
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == self.field {
+            self.value = Some(FieldValue::Text(value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == self.field {
+            self.value = Some(FieldValue::Text(format!("{:?}", value)));
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::CallGraphSnapshot;
+use std::collections::HashMap;
+
+/// An edge considered by [`heaviest_edges`]: its caller, callee, weight and
+/// index into the edges of the snapshot.
+#[derive(Clone, Copy, Debug)]
+struct Candidate {
+    caller: usize,
+    callee: usize,
+    weight: i128,
+    index: usize,
+}
+
+impl CallGraphSnapshot {
+    /// Returns the heaviest call tree from `root` as a snapshot, for export:
+    /// the dominant skeleton of the calls made from `root`.
+    ///
+    /// The tree is the maximum-weight spanning arborescence of the nodes
+    /// reachable from `root`, weighted by call counts, found with the
+    /// Chu–Liu/Edmonds algorithm. Every node but `root` keeps a single edge
+    /// from one of its callers, with its counts and durations, chosen so that
+    /// the edges kept are called as often as possible in total. Returns
+    /// `None` if there is no node named `root`.
+    ///
+    /// ```rust
+    /// #[tracing::instrument]
+    /// fn handler() {
+    ///     for _ in 0..3 {
+    ///         service()
+    ///     }
+    ///     database()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn service() {
+    ///     database()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn database() {}
+    ///
+    /// let snapshot = tracing_callgraph::with_graph_recording(handler);
+    /// let tree = snapshot.heaviest_call_tree("handler").unwrap();
+    /// assert_eq!(tree.edges().len(), 2);
+    /// assert_eq!(tree.edge("service", "database").unwrap().count, 3);
+    /// assert!(tree.edge("handler", "database").is_none());
+    /// ```
+    pub fn heaviest_call_tree(&self, root: &str) -> Option<CallGraphSnapshot> {
+        let root = self.node_index(root)?;
+        let mut reached = self.reachable(root, false);
+        reached[root] = true;
+        let candidates: Vec<_> = self
+            .edges()
+            .iter()
+            .enumerate()
+            .filter(|(_, edge)| reached[edge.caller] && reached[edge.callee])
+            .map(|(index, edge)| Candidate {
+                caller: edge.caller,
+                callee: edge.callee,
+                weight: edge.count as i128,
+                index,
+            })
+            .collect();
+
+        let mut kept = heaviest_edges(self.nodes().len(), root, candidates);
+        kept.sort_unstable();
+        let edges = kept
+            .into_iter()
+            .map(|index| self.edges()[index].clone())
+            .collect();
+        let mut tree = CallGraphSnapshot::from_parts(self.nodes().to_vec(), edges);
+        tree.prune(0);
+        Some(tree)
+    }
+}
+
+/// Returns the indices of the edges of the maximum-weight arborescence from
+/// `root` over `nodes` nodes, every one of them reachable from `root` by the
+/// `candidates` or without any edge.
+///
+/// Every node picks its heaviest incoming edge. Cycles among the picked
+/// edges are contracted into single nodes, with the weights of edges into
+/// them lowered by the weight of the edge they replace, and the contracted
+/// graph solved in turn.
+fn heaviest_edges(nodes: usize, root: usize, candidates: Vec<Candidate>) -> Vec<usize> {
+    let mut best: Vec<Option<Candidate>> = vec![None; nodes];
+    for candidate in &candidates {
+        if candidate.caller == candidate.callee || candidate.callee == root {
+            continue;
+        }
+        match best[candidate.callee] {
+            Some(current) if current.weight >= candidate.weight => {}
+            _ => best[candidate.callee] = Some(*candidate),
+        }
+    }
+
+    // Find the cycles among the picked edges, numbering every node of a cycle
+    // after it and every other node on its own.
+    let mut component = vec![None; nodes];
+    let mut visited = vec![None; nodes];
+    let mut components = 0;
+    let mut cycles = Vec::new();
+    for start in 0..nodes {
+        let mut node = Some(start);
+        while let Some(current) = node.filter(|&node| visited[node].is_none() && node != root) {
+            visited[current] = Some(start);
+            node = best[current].map(|edge| edge.caller);
+        }
+        let node = match node {
+            Some(node) if visited[node] == Some(start) && component[node].is_none() => node,
+            _ => continue,
+        };
+        let caller = |node: usize| {
+            best[node]
+                .expect("expected: nodes on a cycle are called")
+                .caller
+        };
+        let mut cycle = vec![node];
+        let mut member = caller(node);
+        while member != node {
+            cycle.push(member);
+            member = caller(member);
+        }
+        for &member in &cycle {
+            component[member] = Some(components);
+        }
+        components += 1;
+        cycles.push(cycle);
+    }
+    if cycles.is_empty() {
+        return best.into_iter().flatten().map(|edge| edge.index).collect();
+    }
+    let component: Vec<_> = component
+        .into_iter()
+        .map(|component| {
+            component.unwrap_or_else(|| {
+                components += 1;
+                components - 1
+            })
+        })
+        .collect();
+
+    // The node within a cycle each remaining edge enters, by edge index.
+    let mut enters = HashMap::new();
+    let contracted = candidates
+        .iter()
+        .filter(|edge| component[edge.caller] != component[edge.callee])
+        .map(|edge| {
+            enters.insert(edge.index, edge.callee);
+            let replaced = best[edge.callee].map_or(0, |best| best.weight);
+            Candidate {
+                caller: component[edge.caller],
+                callee: component[edge.callee],
+                weight: edge.weight - replaced,
+                index: edge.index,
+            }
+        })
+        .collect();
+
+    let mut kept = heaviest_edges(components, component[root], contracted);
+    let entered: Vec<_> = kept.iter().map(|index| enters[index]).collect();
+    for cycle in cycles {
+        for member in cycle {
+            if !entered.contains(&member) {
+                kept.extend(best[member].map(|edge| edge.index));
+            }
+        }
+    }
+    kept
+}
@@ -0,0 +1,102 @@
+use crate::gzip;
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// A writer replacing a file atomically, by writing to a temporary file next
+/// to it and renaming that over the file on every flush.
+///
+/// Readers of the file, such as `xdot` or CI artifact collectors, only ever
+/// see complete output, and a crash while flushing leaves the previous
+/// output in place. Each flush replaces the file with what was written since
+/// the previous one, and is not retried if it fails. Data still buffered
+/// when the writer is dropped is flushed, ignoring errors.
+///
+/// With the `flate2` feature, output to paths ending in `.gz`, such as
+/// `output.dot.gz`, is gzip-compressed.
+///
+/// ```rust
+/// use std::{fs, io::Write};
+/// use tracing_callgraph::AtomicFile;
+///
+/// let path = std::env::temp_dir().join("atomic-file-example.dot");
+/// let _ = fs::remove_file(&path);
+/// let mut file = AtomicFile::create(&path).unwrap();
+/// file.write_all(b"digraph {\n").unwrap();
+/// assert!(!path.exists());
+/// file.write_all(b"}\n").unwrap();
+/// file.flush().unwrap();
+/// assert_eq!(fs::read_to_string(&path).unwrap(), "digraph {\n}\n");
+/// ```
+#[derive(Debug)]
+pub struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    temp: Option<File>,
+    buffer: Vec<u8>,
+    compress: bool,
+}
+
+impl AtomicFile {
+    /// Returns a writer replacing the file at `path`.
+    ///
+    /// The temporary file is created right away, so that an unwritable
+    /// location is reported here rather than on the first flush.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp");
+        let temp_path = path.with_file_name(name);
+        let temp = File::create(&temp_path)?;
+        Ok(Self {
+            temp_path,
+            temp: Some(temp),
+            buffer: Vec::new(),
+            compress: gzip::is_gzip(&path),
+            path,
+        })
+    }
+
+    /// Returns the path of the file being replaced.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        // Output is replaced as a whole, so a failed flush is not retried.
+        let mut buffer = std::mem::take(&mut self.buffer);
+        if self.compress {
+            buffer = gzip::compress(&buffer)?;
+        }
+        {
+            let mut temp = match self.temp.take() {
+                Some(temp) => temp,
+                None => File::create(&self.temp_path)?,
+            };
+            temp.write_all(&buffer)?;
+            temp.sync_all()?;
+        }
+        fs::rename(&self.temp_path, &self.path)
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        let _ = self.flush();
+        if self.temp.take().is_some() {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{
+    error::{Error, Kind},
+    AtomicFile, Builder, CallGraphSnapshot,
+};
+use std::{fs, io::Write, path::PathBuf};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// Records the call graphs of benchmarks, writing one `dot` file per
+/// benchmark so timings can be correlated with the calls they exercised.
+///
+/// Each benchmark is recorded once, separately from any timed iterations, so
+/// recording doesn't skew the measurements.
+///
+/// ```rust,no_run
+/// use tracing_callgraph::BenchRecorder;
+///
+/// #[tracing::instrument]
+/// fn parse() {}
+///
+/// let recorder = BenchRecorder::new("target/callgraphs");
+/// // Writes `target/callgraphs/parse_small.dot`.
+/// recorder.record("parse/small", parse).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct BenchRecorder {
+    dir: PathBuf,
+    builder: Builder,
+}
+
+impl BenchRecorder {
+    /// Returns a recorder writing graphs into the directory `dir`, which is
+    /// created if it doesn't exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            builder: Builder::default(),
+        }
+    }
+
+    /// Record with layers configured by `builder`.
+    pub fn builder(mut self, builder: Builder) -> Self {
+        self.builder = builder;
+        self
+    }
+
+    /// Returns the path of the graph file for the benchmark `name`.
+    ///
+    /// Characters other than ASCII letters, digits, `-` and `.` are replaced
+    /// by `_`, so benchmark ids like `group/input` map to a single file.
+    pub fn path(&self, name: &str) -> PathBuf {
+        let file: String = name
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '.' => c,
+                _ => '_',
+            })
+            .collect();
+        self.dir.join(file + ".dot")
+    }
+
+    /// Records the call graph of one run of `f`, writes it to the file for
+    /// `name`, and returns it.
+    pub fn record<F>(&self, name: &str, f: F) -> Result<CallGraphSnapshot, Error>
+    where
+        F: FnOnce(),
+    {
+        let path = self.path(name);
+        let create_file = |source| {
+            Error(Kind::CreateFile {
+                path: path.clone(),
+                source,
+            })
+        };
+        fs::create_dir_all(&self.dir).map_err(create_file)?;
+        let mut writer = AtomicFile::create(&path).map_err(create_file)?;
+
+        let layer = self.builder.clone().build();
+        let subscriber = Registry::default().with(layer.clone());
+        tracing::subscriber::with_default(subscriber, f);
+        writer
+            .write_all(layer.to_dot().as_bytes())
+            .and_then(|()| writer.flush())
+            .map_err(Kind::FlushFile)
+            .map_err(Error)?;
+        Ok(layer.snapshot())
+    }
+
+    /// Benchmarks `f` as `name` with Criterion, then records its call graph
+    /// with [`record`].
+    ///
+    /// Requires the `criterion` feature.
+    ///
+    /// [`record`]: BenchRecorder::record
+    #[cfg(feature = "criterion")]
+    pub fn bench_function<M, F>(
+        &self,
+        criterion: &mut criterion::Criterion<M>,
+        name: &str,
+        mut f: F,
+    ) -> Result<CallGraphSnapshot, Error>
+    where
+        M: criterion::measurement::Measurement + 'static,
+        F: FnMut(),
+    {
+        criterion.bench_function(name, |bencher| bencher.iter(&mut f));
+        self.record(name, f)
+    }
+}
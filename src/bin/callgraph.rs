@@ -0,0 +1,452 @@
+//! Post-processing for graphs written by `tracing-callgraph`.
+//!
+//! Snapshots are read from and written to either `dot` files, as produced by
+//! a `FlushGuard`, or JSON files holding a serialized `CallGraphSnapshot`, and
+//! can also be written in the canonical text format. The format is chosen by
+//! file extension, or by `--format` when writing to stdout. Files ending in
+//! `.gz`, such as `app.dot.gz`, are gzip-compressed.
+use flate2::read::GzDecoder;
+use std::{
+    env,
+    error::Error,
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+    process,
+};
+use tracing_callgraph::{
+    AtomicFile, CallGraphSnapshot, Centrality, DotOptions, EdgeRules, LabelOverflow, MatrixFormat,
+    NodeLabel, Theme,
+};
+
+const USAGE: &str = "\
+usage: callgraph <command> [options] <input>...
+
+commands:
+    merge <input>...             sum the counts of several graphs
+    merge-services <input>...    link the graphs of several services by their external calls
+    diff <baseline> <input>      list edges whose counts differ, or color them with -f dot
+    filter <input>               keep or drop nodes by name
+    prune <input>                drop rarely called edges
+    convert <input>              convert between formats
+    dominators <input>           write the dominator tree of a node
+    tree <input>                 write the heaviest call tree from a node
+    condense <input>             collapse mutually recursive nodes
+    reduce <input>               drop calls implied by longer chains of calls
+    paths <input>                list call paths by how often they were taken
+    check <input>                fail on edges violating the rules of --rules
+    coverage <static> <input>    list the edges of a static call graph in dot, such as from
+                                 `cargo call-stack`, that were exercised or missed
+    degrees <input>              list the nodes with the highest fan-in and fan-out
+    matrix <input>               write the call counts as a CSV adjacency matrix
+
+options:
+    -o, --output <path>          write to <path> instead of stdout, compressed if it ends in .gz
+    -f, --format <dot|json|text|svg|drawio|callgrind|pprof|parquet>
+                                 output format, defaults to the output extension or dot,
+                                 callgrind for files named callgrind.out.*,
+                                 and pprof for .pb.gz
+    --keep <pattern>             filter: keep nodes whose name contains <pattern>
+    --drop <pattern>             filter: drop nodes whose name contains <pattern>
+    --min-count <n>              prune: drop edges called fewer than <n> times
+    --threshold <ratio>          diff: color edges whose count changed by at least
+                                 <ratio> of the baseline, defaults to 0.5
+    --root <name>                dominators, tree: the node to compute the tree from
+    --top <n>                    degrees: list <n> nodes per ranking, defaults to 10
+    --node-sizing <pagerank|betweenness>
+                                 dot: size nodes by their centrality
+    --rules <path>               check, dot: the allowed and denied edges, one
+                                 `allow|deny <caller> -> <callee>` rule per line
+    --sparse                     matrix: list one `caller,callee,count` line per edge
+    --index <path>               matrix: write the name of every row and column to <path>
+    --max-length <n>             paths: list paths of at most <n> nodes, defaults to 8
+    --limit <n>                  paths: list the <n> most frequent paths, defaults to 100
+    --stats                      dot: append summary statistics
+    --critical-path              dot: highlight the critical path
+    --edge-order                 dot: prefix edge labels with the order they were first followed in
+    --node-tables                dot: draw nodes as tables of their calls, times and errors
+    --strip-prefix               dot: strip the module prefix shared by all labels
+    --theme <light|dark|print|high-contrast>
+                                 dot: style the graph with a preset
+    --labels <name|target|location>
+                                 dot: label nodes by name, by target and span name,
+                                 or also by file and line
+    --max-label-width <n>        dot: wrap labels longer than <n> characters
+    --truncate-labels            dot: truncate long labels instead of wrapping them";
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Dot,
+    Json,
+    Text,
+    Svg,
+    Drawio,
+    Callgrind,
+    Pprof,
+    Parquet,
+}
+
+impl Format {
+    fn parse(name: &str) -> Result<Self> {
+        match name {
+            "dot" => Ok(Self::Dot),
+            "json" => Ok(Self::Json),
+            "text" => Ok(Self::Text),
+            "svg" => Ok(Self::Svg),
+            "drawio" => Ok(Self::Drawio),
+            "callgrind" => Ok(Self::Callgrind),
+            "pprof" => Ok(Self::Pprof),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(format!("unknown format `{}`", name).into()),
+        }
+    }
+
+    fn from_path(path: &Path) -> Self {
+        let callgrind = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("callgrind.out"));
+        if callgrind {
+            return Self::Callgrind;
+        }
+        if path.to_string_lossy().ends_with(".pb.gz") {
+            return Self::Pprof;
+        }
+        let path = path.to_string_lossy();
+        let path = Path::new(path.strip_suffix(".gz").unwrap_or(&path));
+        match path.extension() {
+            Some(ext) if ext == "json" => Self::Json,
+            Some(ext) if ext == "txt" => Self::Text,
+            Some(ext) if ext == "svg" => Self::Svg,
+            Some(ext) if ext == "drawio" => Self::Drawio,
+            Some(ext) if ext == "parquet" => Self::Parquet,
+            _ => Self::Dot,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Args {
+    command: String,
+    inputs: Vec<String>,
+    output: Option<String>,
+    format: Option<Format>,
+    keep: Vec<String>,
+    drop: Vec<String>,
+    min_count: Option<u64>,
+    threshold: Option<f64>,
+    root: Option<String>,
+    max_length: Option<usize>,
+    limit: Option<usize>,
+    top: Option<usize>,
+    matrix_format: MatrixFormat,
+    index: Option<String>,
+    rules: Option<EdgeRules>,
+    dot_options: DotOptions,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut parsed = Args {
+            command: args.next().ok_or(USAGE)?,
+            ..Default::default()
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("`{}` expects a value", arg))
+            };
+            match arg.as_str() {
+                "-o" | "--output" => parsed.output = Some(value()?),
+                "-f" | "--format" => parsed.format = Some(Format::parse(&value()?)?),
+                "--keep" => parsed.keep.push(value()?),
+                "--drop" => parsed.drop.push(value()?),
+                "--min-count" => parsed.min_count = Some(value()?.parse()?),
+                "--threshold" => parsed.threshold = Some(value()?.parse()?),
+                "--root" => parsed.root = Some(value()?),
+                "--max-length" => parsed.max_length = Some(value()?.parse()?),
+                "--limit" => parsed.limit = Some(value()?.parse()?),
+                "--top" => parsed.top = Some(value()?.parse()?),
+                "--sparse" => parsed.matrix_format = MatrixFormat::Sparse,
+                "--index" => parsed.index = Some(value()?),
+                "--rules" => {
+                    let rules = EdgeRules::from_file(value()?)?;
+                    parsed.rules = Some(rules.clone());
+                    parsed.dot_options = parsed.dot_options.with_rules(rules)
+                }
+                "--stats" => parsed.dot_options = parsed.dot_options.with_stats(true),
+                "--critical-path" => {
+                    parsed.dot_options = parsed.dot_options.with_critical_path(true)
+                }
+                "--edge-order" => parsed.dot_options = parsed.dot_options.with_edge_order(true),
+                "--node-tables" => parsed.dot_options = parsed.dot_options.with_node_tables(true),
+                "--strip-prefix" => {
+                    parsed.dot_options = parsed.dot_options.with_common_prefix_stripped(true)
+                }
+                "--node-sizing" => {
+                    let measure = match value()?.as_str() {
+                        "pagerank" => Centrality::PageRank,
+                        "betweenness" => Centrality::Betweenness,
+                        measure => return Err(format!("unknown measure `{}`", measure).into()),
+                    };
+                    parsed.dot_options = parsed.dot_options.with_node_sizing(measure)
+                }
+                "--theme" => {
+                    let theme = match value()?.as_str() {
+                        "light" => Theme::Light,
+                        "dark" => Theme::Dark,
+                        "print" => Theme::Print,
+                        "high-contrast" => Theme::HighContrast,
+                        theme => return Err(format!("unknown theme `{}`", theme).into()),
+                    };
+                    parsed.dot_options = parsed.dot_options.with_theme(theme)
+                }
+                "--labels" => {
+                    let labels = match value()?.as_str() {
+                        "name" => NodeLabel::Name,
+                        "target" => NodeLabel::Target,
+                        "location" => NodeLabel::Location,
+                        labels => return Err(format!("unknown labels `{}`", labels).into()),
+                    };
+                    parsed.dot_options = parsed.dot_options.with_labels(labels)
+                }
+                "--max-label-width" => {
+                    let width = value()?.parse()?;
+                    parsed.dot_options = parsed.dot_options.with_max_label_width(width)
+                }
+                "--truncate-labels" => {
+                    parsed.dot_options = parsed
+                        .dot_options
+                        .with_label_overflow(LabelOverflow::Truncate)
+                }
+                "-h" | "--help" => return Err(USAGE.into()),
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg).into()),
+                _ => parsed.inputs.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn single_input(&self) -> Result<CallGraphSnapshot> {
+        match self.inputs.as_slice() {
+            [input] => read(input),
+            _ => Err(format!("`{}` expects exactly one input", self.command).into()),
+        }
+    }
+
+    fn output_format(&self) -> Format {
+        self.format.unwrap_or_else(|| match &self.output {
+            Some(path) => Format::from_path(path.as_ref()),
+            None => Format::Dot,
+        })
+    }
+
+    fn output(&self) -> Result<Box<dyn Write>> {
+        Ok(match &self.output {
+            // Profiles are compressed by the pprof format itself.
+            Some(path) if self.output_format() == Format::Pprof => {
+                Box::new(io::BufWriter::new(fs::File::create(path)?))
+            }
+            Some(path) => Box::new(AtomicFile::create(path)?),
+            None => Box::new(io::stdout()),
+        })
+    }
+}
+
+fn read(path: &str) -> Result<CallGraphSnapshot> {
+    let input = read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let snapshot = match Format::from_path(path.as_ref()) {
+        Format::Json => {
+            serde_json::from_str(&input).map_err(|e| format!("cannot read {}: {}", path, e))?
+        }
+        Format::Dot => CallGraphSnapshot::from_dot(&input)?,
+        Format::Text
+        | Format::Svg
+        | Format::Drawio
+        | Format::Callgrind
+        | Format::Pprof
+        | Format::Parquet => {
+            return Err(format!("cannot read {}: format is output only", path).into())
+        }
+    };
+    Ok(snapshot)
+}
+
+fn read_to_string(path: &str) -> io::Result<String> {
+    if !path.ends_with(".gz") {
+        return fs::read_to_string(path);
+    }
+    let mut input = String::new();
+    GzDecoder::new(fs::File::open(path)?).read_to_string(&mut input)?;
+    Ok(input)
+}
+
+fn write(args: &Args, snapshot: &CallGraphSnapshot) -> Result<()> {
+    let mut output = args.output()?;
+    match args.output_format() {
+        Format::Dot => snapshot.write_dot_with(&mut output, &args.dot_options)?,
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut output, snapshot)?;
+            writeln!(output)?;
+        }
+        Format::Text => snapshot.write_text(&mut output)?,
+        Format::Svg => snapshot.write_svg(&mut output)?,
+        Format::Drawio => snapshot.write_drawio(&mut output)?,
+        Format::Callgrind => snapshot.write_callgrind(&mut output)?,
+        Format::Pprof => snapshot.write_pprof(&mut output)?,
+        Format::Parquet => snapshot.write_parquet(&mut output)?,
+    }
+    output.flush()?;
+    Ok(())
+}
+
+fn run(args: Args) -> Result<()> {
+    match args.command.as_str() {
+        "merge" => {
+            let mut inputs = args.inputs.iter();
+            let mut merged = read(inputs.next().ok_or("`merge` expects an input")?)?;
+            for input in inputs {
+                merged.merge(&read(input)?);
+            }
+            write(&args, &merged)
+        }
+        "merge-services" => {
+            if args.inputs.is_empty() {
+                return Err("`merge-services` expects an input".into());
+            }
+            let snapshots = args
+                .inputs
+                .iter()
+                .map(|input| read(input))
+                .collect::<Result<Vec<_>>>()?;
+            write(&args, &CallGraphSnapshot::merge_services(&snapshots))
+        }
+        "diff" => {
+            let (baseline, snapshot) = match args.inputs.as_slice() {
+                [baseline, input] => (read(baseline)?, read(input)?),
+                _ => return Err("`diff` expects a baseline and an input".into()),
+            };
+            let mut output = args.output()?;
+            // Without an explicit output format, changes are listed as text.
+            let explicit = args.format.is_some() || args.output.is_some();
+            if explicit && args.output_format() == Format::Dot {
+                let threshold = args.threshold.unwrap_or(0.5);
+                snapshot.write_diff_dot(&mut output, &baseline, threshold)?;
+                output.flush()?;
+                return Ok(());
+            }
+            for change in snapshot.diff(&baseline) {
+                let edge = format!("{} -> {}", change.caller, change.callee);
+                match (change.before, change.after) {
+                    (0, after) => writeln!(output, "+ {}: {}", edge, after)?,
+                    (before, 0) => writeln!(output, "- {}: {}", edge, before)?,
+                    (before, after) => writeln!(output, "~ {}: {} -> {}", edge, before, after)?,
+                }
+            }
+            output.flush()?;
+            Ok(())
+        }
+        "coverage" => {
+            let (static_graph, snapshot) = match args.inputs.as_slice() {
+                [static_graph, input] => (
+                    CallGraphSnapshot::from_static_dot(&fs::read_to_string(static_graph)?)?,
+                    read(input)?,
+                ),
+                _ => return Err("`coverage` expects a static graph and an input".into()),
+            };
+            let mut output = args.output()?;
+            snapshot.write_coverage_report(&mut output, &static_graph)?;
+            output.flush()?;
+            Ok(())
+        }
+        "filter" => {
+            let mut snapshot = args.single_input()?;
+            snapshot.retain_nodes(|node| {
+                let matches = |pattern: &String| node.name.contains(pattern.as_str());
+                (args.keep.is_empty() || args.keep.iter().any(matches))
+                    && !args.drop.iter().any(matches)
+            });
+            write(&args, &snapshot)
+        }
+        "prune" => {
+            let min_count = args.min_count.ok_or("`prune` expects --min-count")?;
+            let mut snapshot = args.single_input()?;
+            snapshot.prune(min_count);
+            write(&args, &snapshot)
+        }
+        "convert" => write(&args, &args.single_input()?),
+        "condense" => write(&args, &args.single_input()?.condensed()),
+        "reduce" => write(&args, &args.single_input()?.transitive_reduction()),
+        "paths" => {
+            let snapshot = args.single_input()?;
+            let mut output = args.output()?;
+            snapshot.write_paths_report(
+                &mut output,
+                args.max_length.unwrap_or(8),
+                args.limit.unwrap_or(100),
+            )?;
+            output.flush()?;
+            Ok(())
+        }
+        "degrees" => {
+            let snapshot = args.single_input()?;
+            let mut output = args.output()?;
+            snapshot.write_degree_report(&mut output, args.top.unwrap_or(10))?;
+            output.flush()?;
+            Ok(())
+        }
+        "matrix" => {
+            let snapshot = args.single_input()?;
+            if let Some(path) = &args.index {
+                let mut index = AtomicFile::create(path)?;
+                snapshot.write_node_index(&mut index)?;
+                index.flush()?;
+            }
+            let mut output = args.output()?;
+            snapshot.write_adjacency_matrix(&mut output, args.matrix_format)?;
+            output.flush()?;
+            Ok(())
+        }
+        "check" => {
+            let rules = args.rules.as_ref().ok_or("`check` expects --rules")?;
+            let violations = args.single_input()?.violations(rules);
+            let mut output = args.output()?;
+            for violation in &violations {
+                writeln!(output, "{}", violation)?;
+            }
+            output.flush()?;
+            if violations.is_empty() {
+                Ok(())
+            } else {
+                Err(format!("{} edges violate the rules", violations.len()).into())
+            }
+        }
+        "dominators" => {
+            let root = args.root.as_deref().ok_or("`dominators` expects --root")?;
+            let tree = args
+                .single_input()?
+                .dominator_tree(root)
+                .ok_or_else(|| format!("no node named `{}`", root))?;
+            write(&args, &tree)
+        }
+        "tree" => {
+            let root = args.root.as_deref().ok_or("`tree` expects --root")?;
+            let tree = args
+                .single_input()?
+                .heaviest_call_tree(root)
+                .ok_or_else(|| format!("no node named `{}`", root))?;
+            write(&args, &tree)
+        }
+        command => Err(format!("unknown command `{}`\n\n{}", command, USAGE).into()),
+    }
+}
+
+fn main() {
+    let result = Args::parse(env::args().skip(1)).and_then(run);
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,156 @@
+//! `cargo callgraph`: runs a binary or tests with `tracing-callgraph` output
+//! enabled and renders the resulting graph.
+//!
+//! The target picks up the output path through `GraphLayer::from_env`, which
+//! reads the `TRACING_CALLGRAPH_OUTPUT` environment variable set here. Every
+//! layer writes its own file, as tests run in several binaries and threads,
+//! and the files are merged into one graph before rendering.
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+use tracing_callgraph::{CallGraphSnapshot, OUTPUT_ENV};
+
+const USAGE: &str = "\
+usage: cargo callgraph [options] <run|test> [cargo args]...
+
+Runs `cargo run` or `cargo test` with TRACING_CALLGRAPH_OUTPUT set, merges
+the graphs written by every layer, then renders the graph with Graphviz `dot`
+when it is installed, or as SVG without it. The target must install its
+layers with `GraphLayer::from_env`.
+
+options:
+    -o, --output <path>      where to write the graph [default: target/callgraph/callgraph.dot]
+    -T, --render <format>    Graphviz output format [default: svg]
+    --no-render              skip rendering";
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Debug)]
+struct Args {
+    output: PathBuf,
+    render: Option<String>,
+    cargo: Vec<String>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut parsed = Args {
+            output: PathBuf::from("target/callgraph/callgraph.dot"),
+            render: Some("svg".to_string()),
+            cargo: Vec::new(),
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("`{}` expects a value", arg))
+            };
+            match arg.as_str() {
+                "-o" | "--output" => parsed.output = value()?.into(),
+                "-T" | "--render" => parsed.render = Some(value()?),
+                "--no-render" => parsed.render = None,
+                "-h" | "--help" => return Err(USAGE.into()),
+                "run" | "test" => {
+                    parsed.cargo.push(arg);
+                    parsed.cargo.extend(args);
+                    return Ok(parsed);
+                }
+                _ => return Err(format!("unexpected argument `{}`\n\n{}", arg, USAGE).into()),
+            }
+        }
+        Err(USAGE.into())
+    }
+}
+
+fn run(args: Args) -> Result<i32> {
+    if let Some(parent) = args.output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let output = env::current_dir()?.join(&args.output);
+    let parts = output.with_extension("parts");
+    // Remove stale output so a target that never flushes is detected.
+    let _ = fs::remove_file(&output);
+    let _ = fs::remove_dir_all(&parts);
+    fs::create_dir_all(&parts)?;
+
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let status = Command::new(cargo)
+        .args(&args.cargo)
+        .env(OUTPUT_ENV, parts.join("{pid}-{seq}.dot"))
+        .status()?;
+
+    let merged = merge(&parts)?;
+    fs::remove_dir_all(&parts)?;
+    let snapshot = match merged {
+        Some(snapshot) => snapshot,
+        None => {
+            eprintln!(
+                "no graph was written to {}, does the target use `GraphLayer::from_env`?",
+                parts.display()
+            );
+            return Ok(status.code().unwrap_or(1));
+        }
+    };
+    fs::write(&output, snapshot.to_dot())?;
+    eprintln!("wrote {}", output.display());
+
+    if let Some(format) = &args.render {
+        let rendered = output.with_extension(format);
+        let result = Command::new("dot")
+            .arg(format!("-T{}", format))
+            .arg(&output)
+            .arg("-o")
+            .arg(&rendered)
+            .status();
+        match result {
+            Ok(render) if render.success() => eprintln!("rendered {}", rendered.display()),
+            Ok(render) => eprintln!("`dot` failed: {}", render),
+            // Without Graphviz, SVG can still be rendered by the crate itself.
+            Err(_) if format == "svg" => {
+                fs::write(&rendered, snapshot.to_svg())?;
+                eprintln!("rendered {} without Graphviz", rendered.display());
+            }
+            Err(e) => eprintln!("cannot run Graphviz `dot`, skipping render: {}", e),
+        }
+    }
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Merges the graphs written by every layer into `parts`, if any.
+fn merge(parts: &Path) -> Result<Option<CallGraphSnapshot>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(parts)? {
+        paths.push(entry?.path());
+    }
+    // Sorted, so that nodes are merged in the same order on every run.
+    paths.sort();
+    let mut merged: Option<CallGraphSnapshot> = None;
+    for path in paths {
+        if path.extension().is_some_and(|extension| extension == "dot") {
+            let snapshot = CallGraphSnapshot::from_dot(&fs::read_to_string(&path)?)?;
+            match &mut merged {
+                Some(merged) => merged.merge(&snapshot),
+                None => merged = Some(snapshot),
+            }
+        }
+    }
+    Ok(merged)
+}
+
+fn main() {
+    // Cargo passes the subcommand name as the first argument.
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("callgraph") {
+        args.next();
+    }
+    match Args::parse(args).and_then(run) {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
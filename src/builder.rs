@@ -0,0 +1,1444 @@
+#[cfg(feature = "http")]
+use crate::HttpPush;
+use crate::{
+    error::{Error, Kind},
+    failure::Failure,
+    filter::{FieldGate, Filter, NodeKeys, TopNodes},
+    gzip, noise,
+    observer::Observer,
+    partition::PartitionKey,
+    slices::Slices,
+    storage::{Config, Graphs},
+    stream::EdgeStream,
+    template, Aggregation, AtomicFile, Backend, CallGraphSnapshot, Counting, Disambiguation,
+    DotOptions, Fallback, FlushGuard, GraphLayer, HashFunction, NodeKey, Noise, RotatingFile,
+    Rotation, Simplification, Slicing, SpanFields,
+};
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tracing::Metadata;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// A builder for configuring a [`GraphLayer`].
+///
+/// ```rust
+/// use tracing_callgraph::GraphLayer;
+///
+/// let (layer, _guard) = GraphLayer::builder()
+///     .top_node("main")
+///     .with_stdout();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+    top_node: Option<&'static str>,
+    top_nodes: Option<TopNodes>,
+    dot_options: DotOptions,
+    folded_file: Option<PathBuf>,
+    speedscope_file: Option<PathBuf>,
+    time_slices: Option<(PathBuf, Slicing)>,
+    partition_file: Option<PathBuf>,
+    external_calls: bool,
+    log_events: bool,
+    counting: Counting,
+    skip_unrecorded_parents: bool,
+    filter: Option<Filter>,
+    gate: Option<FieldGate>,
+    min_duration: Option<Duration>,
+    accumulate: bool,
+    fallback: Option<Fallback>,
+    stream: Option<Arc<EdgeStream>>,
+    failure: Option<Arc<Failure>>,
+    observer: Option<Arc<Observer>>,
+    noise: Option<Noise>,
+    delta_flushes: Option<usize>,
+    config: Config,
+}
+
+impl Builder {
+    /// Returns a new `Builder` with the default configuration.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a top node to the graph, see [`GraphLayer::enable_top_node`].
+    pub fn top_node(mut self, name: &'static str) -> Self {
+        self.top_node = Some(name);
+        self
+    }
+
+    /// Add several top nodes to the graph, choosing the one of each span or
+    /// event without a recorded parent by calling `select` with its metadata.
+    ///
+    /// This attaches the roots of different subsystems to meaningful nodes,
+    /// such as one per target prefix, or per thread name by looking at
+    /// [`std::thread::current`]. The choice is made once per root span, when
+    /// it is first recorded. Where `select` returns `None`, the node set with
+    /// [`top_node`] is used, if any.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// let snapshot = GraphLayer::builder()
+    ///     .top_nodes(|metadata| match metadata.name() {
+    ///         "query" => Some("db"),
+    ///         _ => None,
+    ///     })
+    ///     .top_node("main")
+    ///     .record(|| {
+    ///         drop(tracing::info_span!("query").entered());
+    ///         drop(tracing::info_span!("render").entered());
+    ///     });
+    /// assert!(snapshot.edge("db", "query").is_some());
+    /// assert!(snapshot.edge("main", "render").is_some());
+    /// ```
+    ///
+    /// [`top_node`]: Builder::top_node
+    pub fn top_nodes<F>(mut self, select: F) -> Self
+    where
+        F: Fn(&Metadata<'_>) -> Option<&'static str> + Send + Sync + 'static,
+    {
+        self.top_nodes = Some(TopNodes::new(select));
+        self
+    }
+
+    /// Configure the `dot` output written by [`FlushGuard`]s of the layer.
+    pub fn dot_options(mut self, options: DotOptions) -> Self {
+        self.dot_options = options;
+        self
+    }
+
+    /// Choose what the calls of nodes and edges count, see [`Counting`].
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{Counting, GraphLayer};
+    ///
+    /// let polled = || {
+    ///     // A future instrumented with `tracing` enters its span on every poll.
+    ///     let span = tracing::info_span!("fetch");
+    ///     for _ in 0..3 {
+    ///         let _poll = span.enter();
+    ///     }
+    /// };
+    ///
+    /// let enters = GraphLayer::builder().record(polled);
+    /// assert_eq!(enters.nodes()[0].calls, 3);
+    /// let spans = GraphLayer::builder().counting(Counting::Spans).record(polled);
+    /// assert_eq!(spans.nodes()[0].calls, 1);
+    /// ```
+    pub fn counting(mut self, counting: Counting) -> Self {
+        self.counting = counting;
+        self
+    }
+
+    /// Only record spans for which `filter` returns `true`, such as spans of
+    /// the application rather than of noisy dependencies.
+    ///
+    /// The filter is applied once per callsite, when it is registered, so
+    /// excluded spans cost nothing when entered. Excluded spans are skipped
+    /// over, connecting the spans in them to the nearest recorded ancestor.
+    ///
+    /// As layers of `tracing-subscriber` filter for the whole subscriber,
+    /// excluded spans are disabled for any other layers of it too. Events are
+    /// never excluded.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     let _poll = tracing::info_span!("poll").entered();
+    ///     inner();
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let snapshot = GraphLayer::builder()
+    ///     .filter(|metadata| metadata.name() != "poll")
+    ///     .record(outer);
+    /// assert_eq!(snapshot.node_index("poll"), None);
+    /// assert!(snapshot.edge("outer", "inner").is_some());
+    /// ```
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Metadata<'_>) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Filter::new(filter));
+        self
+    }
+
+    /// Only record spans whose fields pass `predicate`, along with all the
+    /// spans in them, such as the requests of one tenant or the sampled
+    /// ones, to narrow down capture in production to specific traffic.
+    ///
+    /// The predicate is called with the fields of every span created
+    /// outside of a recorded span, so it can match on a field of the root
+    /// span of a request or on one set further down. Only the values a span
+    /// is created with are seen, not those recorded later. Spans left out
+    /// are skipped over like with [`filter`], and so are the events in them,
+    /// but stay enabled for other layers of the subscriber.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn handle(tenant: &str) {
+    ///     query()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {}
+    ///
+    /// let snapshot = GraphLayer::builder()
+    ///     .record_when(|fields| fields.get("tenant") == Some("canary"))
+    ///     .record(|| {
+    ///         handle("canary");
+    ///         handle("stable");
+    ///         handle("stable");
+    ///     });
+    /// assert_eq!(snapshot.edge("handle", "query").unwrap().count, 1);
+    /// ```
+    ///
+    /// [`filter`]: Builder::filter
+    pub fn record_when<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&SpanFields) -> bool + Send + Sync + 'static,
+    {
+        self.gate = Some(FieldGate::new(predicate));
+        self
+    }
+
+    /// Only record the spans that ran for at least `threshold`, for a graph
+    /// of the slow paths only when investigating latency.
+    ///
+    /// The time a span ran for is the total time it was entered, and its
+    /// calls from its caller are held back until it closes, to be recorded
+    /// then if it ran for long enough, so spans are only recorded once
+    /// closed. Time spent in spans that are too fast still counts towards
+    /// their callers.
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn handler(delay: u64) {
+    ///     query(delay)
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn query(delay: u64) {
+    ///     std::thread::sleep(Duration::from_millis(delay));
+    /// }
+    ///
+    /// let snapshot = GraphLayer::builder()
+    ///     .min_duration(Duration::from_millis(20))
+    ///     .record(|| {
+    ///         handler(0);
+    ///         handler(30);
+    ///     });
+    /// assert_eq!(snapshot.edge("handler", "query").unwrap().count, 1);
+    /// ```
+    pub fn min_duration(mut self, threshold: Duration) -> Self {
+        self.min_duration = Some(threshold);
+        self
+    }
+
+    /// Flag the spans called `name` that run for longer than `threshold`,
+    /// such as the latency budget of a request handler. Can be called once
+    /// for every span name.
+    ///
+    /// Every time a span runs for longer than its threshold counts as a
+    /// violation on its node and on the edge from its caller, which are
+    /// colored in `dot` output and labeled with the number of violations.
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn handler(delay: u64) {
+    ///     query(delay)
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn query(delay: u64) {
+    ///     std::thread::sleep(Duration::from_millis(delay));
+    /// }
+    ///
+    /// let snapshot = GraphLayer::builder()
+    ///     .sla("query", Duration::from_millis(20))
+    ///     .record(|| {
+    ///         handler(0);
+    ///         handler(30);
+    ///     });
+    /// assert_eq!(snapshot.edge("handler", "query").unwrap().sla_violations, 1);
+    /// assert!(snapshot.to_dot().contains("2 (1 over SLA)"));
+    /// ```
+    pub fn sla(mut self, name: impl Into<String>, threshold: Duration) -> Self {
+        self.config.slas.push((name.into(), threshold));
+        self
+    }
+
+    /// Halve the weight of every call each `half_life`, so that the graph
+    /// of a long-lived service reflects its recent behavior rather than its
+    /// startup and historical traffic.
+    ///
+    /// Counts, durations, errors and threshold violations of nodes and
+    /// edges decay by the time elapsed since each call, and are rounded to
+    /// whole counts in snapshots. Nodes and edges weighing less than one
+    /// call are dropped. Taking snapshots changes nothing. Whatever the
+    /// [`Backend`], the weights are recorded into one table shared by all
+    /// threads. This replaces any [`window`] set before.
+    ///
+    /// ```rust
+    /// use std::{thread, time::Duration};
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// #[tracing::instrument]
+    /// fn handle() {
+    ///     query()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {}
+    ///
+    /// let layer = GraphLayer::builder()
+    ///     .half_life(Duration::from_millis(50))
+    ///     .build();
+    /// let subscriber = Registry::default().with(layer.clone());
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     (0..8).for_each(|_| handle());
+    ///     assert_eq!(layer.snapshot().edge("handle", "query").unwrap().count, 8);
+    ///
+    ///     // Three half-lives later, the 8 earlier calls weigh 1 at most.
+    ///     thread::sleep(Duration::from_millis(150));
+    ///     handle();
+    ///     assert!(layer.snapshot().edge("handle", "query").unwrap().count <= 2);
+    ///
+    ///     // Weighing less than one call, the edge is dropped.
+    ///     thread::sleep(Duration::from_millis(200));
+    ///     assert!(layer.snapshot().edge("handle", "query").is_none());
+    /// });
+    /// ```
+    ///
+    /// [`window`]: Builder::window
+    pub fn half_life(mut self, half_life: Duration) -> Self {
+        self.config.half_life = Some(half_life);
+        self.config.window = None;
+        self
+    }
+
+    /// Only keep the calls made within the last `length` in snapshots, such
+    /// as the last 5 minutes, for a live view of a long-lived service that
+    /// is flushed periodically or shown with `GraphLayer::run_tui`.
+    ///
+    /// Nodes and edges not called within the window are dropped. Calls are
+    /// recorded into buckets by the time they are made, each spanning a
+    /// sixtieth of the window, so the calls of a bucket are dropped once it
+    /// starts before the window, and snapshots may hold calls made up to a
+    /// sixtieth of the window before it. Taking snapshots changes nothing,
+    /// and calls expire whether or not snapshots are taken. Whatever the
+    /// [`Backend`], the calls of a bucket are recorded into one graph shared
+    /// by all threads. This replaces any [`half_life`] set before.
+    ///
+    /// ```rust
+    /// use std::{thread, time::Duration};
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// #[tracing::instrument]
+    /// fn handle(cached: bool) {
+    ///     if cached {
+    ///         cache()
+    ///     } else {
+    ///         query()
+    ///     }
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn cache() {}
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {}
+    ///
+    /// let layer = GraphLayer::builder()
+    ///     .window(Duration::from_millis(100))
+    ///     .build();
+    /// let subscriber = Registry::default().with(layer.clone());
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     handle(false);
+    ///     assert!(layer.snapshot().edge("handle", "query").is_some());
+    ///
+    ///     thread::sleep(Duration::from_millis(150));
+    ///     handle(true);
+    ///     let snapshot = layer.snapshot();
+    ///     assert!(snapshot.edge("handle", "query").is_none());
+    ///     assert_eq!(snapshot.edge("handle", "cache").unwrap().count, 1);
+    /// });
+    /// ```
+    ///
+    /// [`half_life`]: Builder::half_life
+    pub fn window(mut self, length: Duration) -> Self {
+        self.config.window = Some(length);
+        self.config.half_life = None;
+        self
+    }
+
+    /// Suppress the spans of the internals of async runtimes and networking
+    /// libraries, such as tokio, hyper and tower, whose targets are listed in
+    /// [`NOISY_TARGETS`], so that graphs of async services aren't dominated
+    /// by plumbing.
+    ///
+    /// With [`Noise::Drop`], the spans are excluded like with [`filter`], and
+    /// so disabled for any other layers of the subscriber too, such as
+    /// `console-subscriber`. [`Noise::Collapse`] keeps them for other layers.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{GraphLayer, Noise};
+    ///
+    /// #[tracing::instrument]
+    /// fn handler() {
+    ///     let _task = tracing::info_span!(target: "tokio::task", "runtime.spawn").entered();
+    ///     let _poll = tracing::info_span!(target: "tokio::runtime::blocking", "poll").entered();
+    ///     work();
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn work() {}
+    ///
+    /// let dropped = GraphLayer::builder()
+    ///     .suppress_noise(Noise::Drop)
+    ///     .record(handler);
+    /// assert!(dropped.edge("handler", "work").is_some());
+    ///
+    /// let collapsed = GraphLayer::builder()
+    ///     .suppress_noise(Noise::Collapse)
+    ///     .record(handler);
+    /// assert!(collapsed.edge("handler", "tokio internals").is_some());
+    /// assert!(collapsed.edge("tokio internals", "work").is_some());
+    /// ```
+    ///
+    /// [`NOISY_TARGETS`]: crate::NOISY_TARGETS
+    /// [`filter`]: Builder::filter
+    pub fn suppress_noise(mut self, noise: Noise) -> Self {
+        self.noise = Some(noise);
+        self
+    }
+
+    /// Connect spans to their nearest ancestor recorded by this layer, rather
+    /// than to their direct parent.
+    ///
+    /// A span's parent may be unknown to the layer, when the layer did not see
+    /// it being created. By default such a parent is still
+    /// recorded as the caller, named after its callsite. With this enabled,
+    /// it is skipped over, so edges connect the closest ancestor the layer
+    /// did record instead.
+    pub fn skip_unrecorded_parents(mut self, enabled: bool) -> Self {
+        self.skip_unrecorded_parents = enabled;
+        self
+    }
+
+    /// Choose how the graph is stored while recording, see [`Backend`].
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.config.backend = backend;
+        self
+    }
+
+    /// Limit the graph to `max` distinct nodes, bounding the memory used by
+    /// long-lived processes with many dynamically named spans.
+    ///
+    /// Once the limit is reached, spans with new names are recorded as the
+    /// single [`OTHER_NODE`]. As every edge joins two nodes, this also bounds
+    /// the number of edges.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{GraphLayer, OTHER_NODE};
+    ///
+    /// let snapshot = GraphLayer::builder().max_nodes(2).record(|| {
+    ///     let _a = tracing::info_span!("a").entered();
+    ///     let _b = tracing::info_span!("b").entered();
+    ///     let _c = tracing::info_span!("c").entered();
+    ///     let _d = tracing::info_span!("d").entered();
+    /// });
+    /// let names: Vec<_> = snapshot.nodes().iter().map(|node| &node.name).collect();
+    /// assert_eq!(names, [OTHER_NODE, "a", "b"]);
+    /// ```
+    ///
+    /// [`OTHER_NODE`]: crate::OTHER_NODE
+    pub fn max_nodes(mut self, max: usize) -> Self {
+        self.config.max_nodes = Some(max);
+        self
+    }
+
+    /// Choose the hash function of the maps the graph is recorded into, see
+    /// [`HashFunction`].
+    pub fn hash_function(mut self, function: HashFunction) -> Self {
+        self.config.hash_function = function;
+        self
+    }
+
+    /// Measure the time the layer spends recording, including waiting for
+    /// locks, and report it with the graph, see [`Overhead`]. The overhead is
+    /// also written as a comment after the `dot` output.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn work() {}
+    ///
+    /// let snapshot = GraphLayer::builder().measure_overhead(true).record(work);
+    /// // One enter and one exit.
+    /// assert_eq!(snapshot.overhead().unwrap().events, 2);
+    /// ```
+    ///
+    /// [`Overhead`]: crate::Overhead
+    pub fn measure_overhead(mut self, enabled: bool) -> Self {
+        self.config.measure_overhead = enabled;
+        self
+    }
+
+    /// Also record the time spent in every stack of spans, and have the
+    /// layer's [`FlushGuard`]s write it to `path` in the folded format of
+    /// [`tracing-flame`], next to the call graph.
+    ///
+    /// This avoids running a second layer to produce flame graphs. The
+    /// stacks can also be written manually with [`GraphLayer::write_folded`].
+    ///
+    /// [`tracing-flame`]: https://docs.rs/tracing-flame
+    pub fn folded_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.folded_file = Some(path.into());
+        self.config.folded_stacks = true;
+        self
+    }
+
+    /// Also record the time every span is entered and exited, and have the
+    /// layer's [`FlushGuard`]s write it to `path` as a [Speedscope] profile
+    /// with one timeline per thread, next to the call graph.
+    ///
+    /// Unlike the aggregate graph, this keeps every event, so memory grows
+    /// with the number of spans entered. The timeline can also be written
+    /// manually with [`GraphLayer::write_speedscope`].
+    ///
+    /// [Speedscope]: https://www.speedscope.app
+    pub fn speedscope_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.speedscope_file = Some(path.into());
+        self.config.timeline = true;
+        self
+    }
+
+    /// Also write a numbered series of graphs to files named after `path`,
+    /// such as `phases.1.dot` and `phases.2.dot`, each holding only the
+    /// calls recorded during one time slice, as configured by `slicing`.
+    ///
+    /// This shows how the call structure changes across the phases of a
+    /// long run, while the graph written by [`FlushGuard`]s still covers all
+    /// of it. Dropping a [`FlushGuard`] ends the current slice early, so
+    /// that its calls aren't lost. Slices are written with the options of
+    /// [`dot_options`], and placeholders in `path` are expanded as for
+    /// [`with_file`].
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{CallGraphSnapshot, GraphLayer, Slicing};
+    ///
+    /// #[tracing::instrument]
+    /// fn request(warm: bool) {
+    ///     if warm {
+    ///         cache()
+    ///     } else {
+    ///         query()
+    ///     }
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn cache() {}
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {}
+    ///
+    /// let dir = std::env::temp_dir();
+    /// GraphLayer::builder()
+    ///     .time_slices(dir.join("slices-example.dot"), Slicing::RootSpans(2))
+    ///     .record(|| {
+    ///         for warm in [false, false, true, true] {
+    ///             request(warm)
+    ///         }
+    ///     });
+    ///
+    /// let read = |path| {
+    ///     let dot = std::fs::read_to_string(dir.join(path)).unwrap();
+    ///     CallGraphSnapshot::from_dot(&dot).unwrap()
+    /// };
+    /// assert_eq!(read("slices-example.1.dot").edge("request", "query").unwrap().count, 2);
+    /// assert!(read("slices-example.2.dot").edge("request", "query").is_none());
+    /// assert_eq!(read("slices-example.2.dot").edge("request", "cache").unwrap().count, 2);
+    /// ```
+    ///
+    /// [`dot_options`]: Builder::dot_options
+    /// [`with_file`]: Builder::with_file
+    pub fn time_slices(mut self, path: impl Into<PathBuf>, slicing: Slicing) -> Self {
+        self.time_slices = Some((path.into(), slicing));
+        self
+    }
+
+    /// Also record a separate graph for every value of `field` on root
+    /// spans, such as `request_id` or `endpoint`, so that one noisy
+    /// partition doesn't hide the structure of the others.
+    ///
+    /// Spans without the field are recorded into the partition of their
+    /// parent, so the field can also be set on a span further down than the
+    /// root. Once there are `max` partitions, all further values share one
+    /// named [`OTHER_NODE`]. The graphs can be retrieved with
+    /// [`GraphLayer::partitions`], or written with [`partition_file`].
+    ///
+    /// [`OTHER_NODE`]: crate::OTHER_NODE
+    /// [`partition_file`]: Builder::partition_file
+    pub fn partition_by(mut self, field: &'static str, max: usize) -> Self {
+        self.config.partition = Some((PartitionKey::Field(field), max));
+        self
+    }
+
+    /// Also record a separate graph for every root span, keyed by its name,
+    /// such as one graph for every route handler of a server or every
+    /// subcommand of a command line tool, which a single graph of the whole
+    /// binary would mix up.
+    ///
+    /// Spans are recorded into the partition of their root. Once there are
+    /// `max` partitions, all further roots share one named [`OTHER_NODE`].
+    /// The graphs can be retrieved with [`GraphLayer::partitions`], or
+    /// written with [`partition_file`], one file per root.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// #[tracing::instrument]
+    /// fn build() {
+    ///     compile()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn test() {
+    ///     compile();
+    ///     run()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn compile() {}
+    ///
+    /// #[tracing::instrument]
+    /// fn run() {}
+    ///
+    /// let layer = GraphLayer::builder().partition_by_root(10).build();
+    /// let subscriber = Registry::default().with(layer.clone());
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     build();
+    ///     test();
+    /// });
+    ///
+    /// let partitions = layer.partitions();
+    /// assert_eq!(partitions[0].0, "build");
+    /// assert!(partitions[0].1.edge("compile", "run").is_none());
+    /// assert_eq!(partitions[0].1.nodes().len(), 2);
+    /// assert_eq!(partitions[1].0, "test");
+    /// assert!(partitions[1].1.edge("test", "run").is_some());
+    /// ```
+    ///
+    /// [`OTHER_NODE`]: crate::OTHER_NODE
+    /// [`partition_file`]: Builder::partition_file
+    pub fn partition_by_root(mut self, max: usize) -> Self {
+        self.config.partition = Some((PartitionKey::Root, max));
+        self
+    }
+
+    /// Have the layer's [`FlushGuard`]s write the graph of every partition
+    /// of [`partition_by`] or [`partition_by_root`] to a file named after
+    /// `path`, with the key of the partition inserted before the extension,
+    /// such as `requests.GET__users.dot` for `GET /users`. Characters other
+    /// than ASCII letters, digits, `-` and `_` in the key are replaced by
+    /// `_`.
+    ///
+    /// [`partition_by`]: Builder::partition_by
+    /// [`partition_by_root`]: Builder::partition_by_root
+    pub fn partition_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.partition_file = Some(path.into());
+        self
+    }
+
+    /// Record the order in which edges are first followed within every root
+    /// span, as [`EdgeInfo::order`], which [`DotOptions::with_edge_order`]
+    /// shows in labels.
+    ///
+    /// Edges are numbered in the order their callees are entered. An edge
+    /// followed in several root spans keeps its lowest number, so the
+    /// numbers convey rough execution order rather than an exact trace.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn handler() {
+    ///     parse();
+    ///     respond();
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn parse() {}
+    ///
+    /// #[tracing::instrument]
+    /// fn respond() {}
+    ///
+    /// let snapshot = GraphLayer::builder().edge_order(true).record(handler);
+    /// assert_eq!(snapshot.edge("handler", "parse").unwrap().order, Some(1));
+    /// assert_eq!(snapshot.edge("handler", "respond").unwrap().order, Some(2));
+    /// ```
+    ///
+    /// [`EdgeInfo::order`]: crate::EdgeInfo::order
+    pub fn edge_order(mut self, enabled: bool) -> Self {
+        self.config.edge_order = enabled;
+        self
+    }
+
+    /// Add a node for every external dependency named by the conventional
+    /// fields in [`EXTERNAL_FIELDS`], with an edge from each span naming it.
+    ///
+    /// This shows outbound calls to databases and downstream services in the
+    /// graph, not just internal functions. External nodes are named
+    /// `external: ` followed by the field value, or the host of URL fields.
+    /// A span naming several dependencies calls the most specific one.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument(fields(db.system = "postgresql"))]
+    /// fn load_user() {}
+    ///
+    /// #[tracing::instrument(fields(http.url = "https://api.example.com/v1/orders"))]
+    /// fn fetch_orders() {}
+    ///
+    /// let snapshot = GraphLayer::builder().external_calls(true).record(|| {
+    ///     load_user();
+    ///     fetch_orders();
+    /// });
+    /// assert!(snapshot.edge("load_user", "external: postgresql").is_some());
+    /// assert!(snapshot.edge("fetch_orders", "external: api.example.com").is_some());
+    /// ```
+    ///
+    /// [`EXTERNAL_FIELDS`]: crate::EXTERNAL_FIELDS
+    pub fn external_calls(mut self, enabled: bool) -> Self {
+        self.external_calls = enabled;
+        self
+    }
+
+    /// Add a leaf node for every target of `log` records bridged into
+    /// `tracing` by `tracing-log`, with an edge from the span each record was
+    /// logged in.
+    ///
+    /// This shows legacy libraries instrumented with `log` rather than
+    /// `tracing` in the graph. Nodes are named `log: ` followed by the target
+    /// of the record, and count the records logged. Other events are ignored.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn connect() {
+    ///     // The fields of a record bridged by `tracing-log`.
+    ///     tracing::info!(log.target = "legacy::pool", "connecting");
+    /// }
+    ///
+    /// let snapshot = GraphLayer::builder().log_events(true).record(connect);
+    /// assert_eq!(snapshot.edge("connect", "log: legacy::pool").unwrap().count, 1);
+    /// ```
+    pub fn log_events(mut self, enabled: bool) -> Self {
+        self.log_events = enabled;
+        self
+    }
+
+    /// Also write every call along an edge to `writer` as it happens, as a
+    /// line of JSON with the `caller`, the `callee`, the `timestamp` in
+    /// microseconds since the Unix epoch and the `thread`, for piping into
+    /// `jq`, Vector or a log pipeline.
+    ///
+    /// Lines are buffered and written whole, once the buffer fills and
+    /// whenever the layer's [`FlushGuard`]s flush, which also flush
+    /// `writer`. Calls without a caller aren't written. If
+    /// writing fails, the error is reported and nothing more is written.
+    /// Written to a socket, the calls of several processes can be combined
+    /// into one graph by a [`Collector`].
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let path = std::env::temp_dir().join("edge-stream-example.ndjson");
+    /// let file = std::fs::File::create(&path).unwrap();
+    /// GraphLayer::builder().edge_stream(file).record(outer);
+    ///
+    /// let lines = std::fs::read_to_string(&path).unwrap();
+    /// assert_eq!(lines.lines().count(), 1);
+    /// assert!(lines.starts_with(r#"{"caller":"outer","callee":"inner","timestamp":"#));
+    /// ```
+    ///
+    /// [`Collector`]: crate::Collector
+    pub fn edge_stream<W>(mut self, writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        self.stream = Some(Arc::new(EdgeStream::new(writer)));
+        self
+    }
+
+    /// Hand the graph to `report` when the process panics, and once `errors`
+    /// error events were recorded, so that the call structure at the time of
+    /// a failure can be attached to an incident, such as with
+    /// `sentry::configure_scope(|scope| scope.add_attachment(..))`.
+    ///
+    /// Error events are those at the `ERROR` level or with an `error` field,
+    /// as counted on [`EdgeInfo::errors`]. With `errors` set to 0, only panics
+    /// are reported. The panic hook is set up when the layer is built, and
+    /// runs before the hook set up earlier, so build the layer after setting
+    /// up the error tracker for it to capture the attachment along with the
+    /// panic.
+    ///
+    /// ```rust
+    /// use std::sync::{Arc, Mutex};
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn handle() {
+    ///     tracing::error!("upstream unavailable");
+    /// }
+    ///
+    /// let reported = Arc::new(Mutex::new(Vec::new()));
+    /// let attachments = reported.clone();
+    /// GraphLayer::builder()
+    ///     .on_failure(2, move |snapshot| attachments.lock().unwrap().push(snapshot.to_dot()))
+    ///     .record(|| {
+    ///         for _ in 0..3 {
+    ///             handle();
+    ///         }
+    ///     });
+    /// let reported = reported.lock().unwrap();
+    /// assert_eq!(reported.len(), 1);
+    /// assert!(reported[0].contains(r#"label = "handle""#));
+    /// ```
+    ///
+    /// [`EdgeInfo::errors`]: crate::EdgeInfo::errors
+    pub fn on_failure<F>(mut self, errors: usize, report: F) -> Self
+    where
+        F: Fn(&CallGraphSnapshot) + Send + Sync + 'static,
+    {
+        self.failure = Some(Arc::new(Failure::new(errors, report)));
+        self
+    }
+
+    /// Hand every call to `observe` as it is recorded, with the name of its
+    /// caller, if any, the name of the callee and the time spent in it, so
+    /// that calls can be reported as metrics too, such as with
+    /// `metrics::counter!("calls", "caller" => caller, "callee" => callee)`
+    /// and `metrics::histogram!("call_duration", "callee" => callee)`.
+    ///
+    /// Calls are handed over when their span exits, after being recorded
+    /// into the graph. With [`min_duration`], the calls of a span are handed
+    /// over once, with their total time, when it closes.
+    ///
+    /// ```rust
+    /// use std::{
+    ///     collections::HashMap,
+    ///     sync::{Arc, Mutex},
+    /// };
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn handle() {
+    ///     query();
+    ///     query();
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {}
+    ///
+    /// let counters = Arc::new(Mutex::new(HashMap::new()));
+    /// let observed = counters.clone();
+    /// GraphLayer::builder()
+    ///     .on_call(move |caller, callee, _elapsed| {
+    ///         let edge = format!("{} -> {}", caller.unwrap_or("root"), callee);
+    ///         *observed.lock().unwrap().entry(edge).or_insert(0) += 1;
+    ///     })
+    ///     .record(handle);
+    /// let counters = counters.lock().unwrap();
+    /// assert_eq!(counters["root -> handle"], 1);
+    /// assert_eq!(counters["handle -> query"], 2);
+    /// ```
+    ///
+    /// [`min_duration`]: Builder::min_duration
+    pub fn on_call<F>(mut self, observe: F) -> Self
+    where
+        F: Fn(Option<&str>, &str, Duration) + Send + Sync + 'static,
+    {
+        self.observer = Some(Arc::new(Observer::new(observe)));
+        self
+    }
+
+    /// Choose how nodes are told apart when spans from different callsites
+    /// share a name, see [`Disambiguation`].
+    ///
+    /// Common names like `run` or `new` would otherwise merge unrelated
+    /// spans into one node. Names are only suffixed once a second callsite
+    /// uses them, so unique names are left as they are.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{Disambiguation, GraphLayer};
+    ///
+    /// mod reader {
+    ///     #[tracing::instrument]
+    ///     pub fn run() {}
+    /// }
+    ///
+    /// mod writer {
+    ///     #[tracing::instrument]
+    ///     pub fn run() {}
+    /// }
+    ///
+    /// let both = || {
+    ///     reader::run();
+    ///     writer::run();
+    /// };
+    /// let snapshot = GraphLayer::builder().record(both);
+    /// let names: Vec<_> = snapshot.nodes().iter().map(|node| &node.name).collect();
+    /// assert_eq!(names, ["run (rust_out::reader)", "run (rust_out::writer)"]);
+    ///
+    /// let merged = GraphLayer::builder()
+    ///     .disambiguation(Disambiguation::Merge)
+    ///     .record(both);
+    /// assert_eq!(merged.nodes()[0].calls, 2);
+    /// ```
+    pub fn disambiguation(mut self, disambiguation: Disambiguation) -> Self {
+        self.config.disambiguation = disambiguation;
+        self
+    }
+
+    /// Choose the node spans from each callsite are recorded as with `key`,
+    /// such as one node per module or per target, rather than per span name.
+    ///
+    /// `key` is called once per callsite. Callsites it returns the same
+    /// [`NodeKey::Name`] for are merged into one node, while those it returns
+    /// [`NodeKey::Default`] for are named as usual.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{GraphLayer, NodeKey};
+    ///
+    /// mod db {
+    ///     #[tracing::instrument]
+    ///     pub fn connect() {
+    ///         query()
+    ///     }
+    ///
+    ///     #[tracing::instrument]
+    ///     fn query() {}
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn handler() {
+    ///     db::connect()
+    /// }
+    ///
+    /// let snapshot = GraphLayer::builder()
+    ///     .node_key(|metadata| match metadata.module_path() {
+    ///         Some(module) if module.ends_with("::db") => NodeKey::from("database"),
+    ///         _ => NodeKey::Default,
+    ///     })
+    ///     .record(handler);
+    /// assert_eq!(snapshot.edge("handler", "database").unwrap().count, 1);
+    /// assert_eq!(snapshot.edge("database", "database").unwrap().count, 1);
+    /// ```
+    pub fn node_key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Metadata<'_>) -> NodeKey + Send + Sync + 'static,
+    {
+        self.config.node_keys = Some(NodeKeys::new(key));
+        self
+    }
+
+    /// Choose how generic parameters and closure segments, such as in
+    /// `Decoder<u8>::decode` or `main::{{closure}}`, are simplified before
+    /// span names become nodes, see [`Simplification`].
+    ///
+    /// ```rust
+    /// use tracing::info_span;
+    /// use tracing_callgraph::{GraphLayer, Simplification};
+    ///
+    /// let decode = || {
+    ///     info_span!("Decoder<u8>::decode").in_scope(|| {});
+    ///     info_span!("Decoder<u16>::decode").in_scope(|| {});
+    /// };
+    /// let snapshot = GraphLayer::builder()
+    ///     .simplify_names(Simplification::Strip)
+    ///     .record(decode);
+    /// let names: Vec<_> = snapshot.nodes().iter().map(|node| &node.name).collect();
+    /// assert_eq!(names, ["Decoder::decode", "Decoder::decode #2"]);
+    ///
+    /// let merged = GraphLayer::builder()
+    ///     .simplify_names(Simplification::Merge)
+    ///     .record(decode);
+    /// assert_eq!(merged.nodes().len(), 1);
+    /// assert_eq!(merged.nodes()[0].calls, 2);
+    /// ```
+    pub fn simplify_names(mut self, simplification: Simplification) -> Self {
+        self.config.simplification = simplification;
+        self
+    }
+
+    /// List the [`unexecuted_callsites`] in a comment after the graph
+    /// written by a [`FlushGuard`].
+    ///
+    /// [`unexecuted_callsites`]: GraphLayer::unexecuted_callsites
+    pub fn report_unexecuted(mut self, enabled: bool) -> Self {
+        self.config.report_unexecuted = enabled;
+        self
+    }
+
+    /// Combine the values of a span field onto the edges into its spans as
+    /// configured by `aggregation`, into [`EdgeInfo::value`], which `dot`
+    /// output labels edges with in place of their count.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{Aggregation, GraphLayer};
+    ///
+    /// #[tracing::instrument]
+    /// fn handler() {
+    ///     for user in ["ann", "bob", "ann"] {
+    ///         query(user, 2);
+    ///     }
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn query(user: &str, rows: u64) {}
+    ///
+    /// let distinct = GraphLayer::builder()
+    ///     .aggregate_edges(Aggregation::Distinct("user"))
+    ///     .record(handler);
+    /// assert_eq!(distinct.edge("handler", "query").unwrap().value, Some(2));
+    ///
+    /// let sum = GraphLayer::builder()
+    ///     .aggregate_edges(Aggregation::Sum("rows"))
+    ///     .record(handler);
+    /// assert_eq!(sum.edge("handler", "query").unwrap().value, Some(6));
+    /// ```
+    ///
+    /// [`EdgeInfo::value`]: crate::EdgeInfo::value
+    pub fn aggregate_edges(mut self, aggregation: Aggregation) -> Self {
+        self.config.aggregation = Some(aggregation);
+        self
+    }
+
+    /// Count the spans recorded without a caller, and list them with the
+    /// thread they were recorded on in a comment after the graph written by
+    /// a [`FlushGuard`], see [`GraphLayer::orphans`].
+    pub fn report_orphans(mut self, enabled: bool) -> Self {
+        self.config.report_orphans = enabled;
+        self
+    }
+
+    /// Tag every span node with the name of the service or process the layer
+    /// runs in, as `service/span`, see [`SERVICE_SEPARATOR`].
+    ///
+    /// This keeps spans of different services apart when their snapshots
+    /// are combined with [`CallGraphSnapshot::merge_services`]. Nodes for
+    /// external calls and [`OTHER_NODE`] are not tagged, so they are shared
+    /// between services.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn checkout() {}
+    ///
+    /// let snapshot = GraphLayer::builder().service_name("shop").record(checkout);
+    /// assert_eq!(snapshot.nodes()[0].name, "shop/checkout");
+    /// ```
+    ///
+    /// [`SERVICE_SEPARATOR`]: crate::SERVICE_SEPARATOR
+    /// [`OTHER_NODE`]: crate::OTHER_NODE
+    pub fn service_name(mut self, name: impl Into<String>) -> Self {
+        self.config.service = Some(name.into());
+        self
+    }
+
+    /// Embed where and how the graph was recorded in its output, see
+    /// [`RunInfo`]: the binary and its `version`, usually
+    /// `env!("CARGO_PKG_VERSION")`, the command line, the hostname, when
+    /// recording started and ended, and the configuration of the layer.
+    ///
+    /// The information is written as a comment before the `dot` output,
+    /// where [`CallGraphSnapshot::from_dot`] reads it back, and is part of
+    /// serialized snapshots.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn work() {}
+    ///
+    /// let snapshot = GraphLayer::builder()
+    ///     .run_metadata(env!("CARGO_PKG_VERSION"))
+    ///     .record(work);
+    /// let run = snapshot.run().unwrap();
+    /// assert!(run.started <= run.ended);
+    ///
+    /// let dot = snapshot.to_dot();
+    /// assert!(dot.starts_with("// run:\n//     binary: "));
+    /// let archived = tracing_callgraph::CallGraphSnapshot::from_dot(&dot).unwrap();
+    /// assert_eq!(archived.run(), Some(run));
+    /// ```
+    ///
+    /// [`RunInfo`]: crate::RunInfo
+    pub fn run_metadata(mut self, version: impl Into<String>) -> Self {
+        self.config.run_version = Some(version.into());
+        self
+    }
+
+    /// Builds the configured [`GraphLayer`].
+    pub fn build(mut self) -> GraphLayer {
+        match self.noise {
+            Some(Noise::Drop) => {
+                let filter = self.filter.take();
+                self.filter = Some(Filter::new(move |metadata| {
+                    !noise::is_noisy(metadata)
+                        && filter
+                            .as_ref()
+                            .is_none_or(|filter| filter.matches(metadata))
+                }));
+            }
+            Some(Noise::Collapse) => {
+                let keys = self.config.node_keys.take();
+                self.config.node_keys = Some(NodeKeys::new(move |metadata| match &keys {
+                    _ if noise::is_noisy(metadata) => {
+                        NodeKey::from(noise::collapsed_name(metadata))
+                    }
+                    Some(keys) => keys.key(metadata),
+                    None => NodeKey::Default,
+                }));
+            }
+            None => {}
+        }
+        let slices = self.time_slices.as_ref().map(|(path, slicing)| {
+            let writer = RotatingFile::new(template::expand(path), Rotation::EveryFlush);
+            Arc::new(Slices::new(*slicing, writer, self.dot_options.clone()))
+        });
+        let graph = Arc::new(Graphs::new(&self.config));
+        if let Some(failure) = &self.failure {
+            failure.install(Arc::downgrade(&graph));
+        }
+        let layer = GraphLayer {
+            graph,
+            dot_options: self.dot_options,
+            folded_file: self.folded_file.as_deref().map(template::expand),
+            speedscope_file: self.speedscope_file.as_deref().map(template::expand),
+            slices,
+            partition_file: self.partition_file.as_deref().map(template::expand),
+            external_calls: self.external_calls,
+            log_events: self.log_events,
+            counting: self.counting,
+            skip_unrecorded_parents: self.skip_unrecorded_parents,
+            filter: self.filter,
+            gate: self.gate,
+            min_duration: self.min_duration,
+            top_nodes: self.top_nodes,
+            fallback: self.fallback,
+            stream: self.stream,
+            failure: self.failure,
+            observer: self.observer,
+            delta_flushes: self.delta_flushes,
+            ..GraphLayer::default()
+        };
+        match self.top_node {
+            Some(name) => layer.enable_top_node(name),
+            None => layer,
+        }
+    }
+
+    /// Records the call graph of `f` with the configured [`GraphLayer`], see
+    /// [`with_graph_recording`].
+    ///
+    /// [`with_graph_recording`]: crate::with_graph_recording
+    pub fn record<F>(self, f: F) -> CallGraphSnapshot
+    where
+        F: FnOnce(),
+    {
+        let layer = self.build();
+        let subscriber = Registry::default().with(layer.clone());
+        tracing::subscriber::with_default(subscriber, f);
+        layer.snapshot()
+    }
+
+    /// Have the layer's [`FlushGuard`]s write only the calls recorded since
+    /// their previous flush, and the full graph every `full_every` flushes,
+    /// starting with the first. This reduces the output of frequent periodic
+    /// flushes of large graphs.
+    ///
+    /// A delta holds the nodes and edges called since the previous flush,
+    /// with the counts and durations of those calls only, and ends with a
+    /// `// delta since the previous flush` comment. Merging the deltas into
+    /// the full graph before them, with [`CallGraphSnapshot::merge`], gives
+    /// the graph at their flush. As every flush replaces the file of
+    /// [`with_file`], deltas suit [`with_rotating_file`] or a stream.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{CallGraphSnapshot, GraphLayer, Rotation};
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// #[tracing::instrument]
+    /// fn handle() {
+    ///     render()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn render() {}
+    ///
+    /// let dir = std::env::temp_dir();
+    /// let (layer, mut guard) = GraphLayer::builder()
+    ///     .delta_flushes(10)
+    ///     .with_rotating_file(dir.join("delta-example.dot"), Rotation::EveryFlush);
+    /// tracing::subscriber::with_default(Registry::default().with(layer), || {
+    ///     handle();
+    ///     guard.flush().unwrap();
+    ///     render();
+    ///     guard.flush().unwrap();
+    /// });
+    ///
+    /// let read = |path| {
+    ///     let dot = std::fs::read_to_string(dir.join(path)).unwrap();
+    ///     CallGraphSnapshot::from_dot(&dot).unwrap()
+    /// };
+    /// assert_eq!(read("delta-example.1.dot").nodes().len(), 2);
+    /// let delta = read("delta-example.2.dot");
+    /// assert_eq!(delta.nodes().len(), 1);
+    /// assert_eq!(delta.nodes()[0].name, "render");
+    /// ```
+    ///
+    /// [`CallGraphSnapshot::merge`]: crate::CallGraphSnapshot::merge
+    /// [`with_file`]: Builder::with_file
+    /// [`with_rotating_file`]: Builder::with_rotating_file
+    pub fn delta_flushes(mut self, full_every: usize) -> Self {
+        self.delta_flushes = Some(full_every);
+        self
+    }
+
+    /// Write the graph to `fallback` when the writer of a [`FlushGuard`]
+    /// fails, so it isn't lost when a disk fills up or a path becomes
+    /// unwritable.
+    ///
+    /// ```rust
+    /// use std::io::{self, Write};
+    /// use tracing_callgraph::{Fallback, GraphLayer};
+    ///
+    /// struct Full;
+    ///
+    /// impl Write for Full {
+    ///     fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+    ///         Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("fallback-example.dot");
+    /// let layer = GraphLayer::builder()
+    ///     .fallback(Fallback::File(path.clone()))
+    ///     .build();
+    /// let mut guard = layer.flush_on_drop(Full);
+    /// assert!(guard.flush().is_err());
+    /// assert!(std::fs::read_to_string(&path).unwrap().starts_with("digraph"));
+    /// ```
+    pub fn fallback(mut self, fallback: Fallback) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    /// Have [`with_file`] load the graph already in its output file, if any,
+    /// and add the newly recorded counts on top of it when flushing.
+    ///
+    /// Repeated short runs, such as invocations of a CLI, then build up one
+    /// aggregate call graph. As the `dot` output carries no durations, only
+    /// counts accumulate.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let path = std::env::temp_dir().join("accumulate-example.dot");
+    /// let _ = std::fs::remove_file(&path);
+    /// for _ in 0..3 {
+    ///     let (layer, _guard) = GraphLayer::builder()
+    ///         .accumulate(true)
+    ///         .with_file(&path)
+    ///         .unwrap();
+    ///     let subscriber = Registry::default().with(layer);
+    ///     tracing::subscriber::with_default(subscriber, outer);
+    /// }
+    ///
+    /// let dot = std::fs::read_to_string(&path).unwrap();
+    /// let snapshot = tracing_callgraph::CallGraphSnapshot::from_dot(&dot).unwrap();
+    /// assert_eq!(snapshot.edge("outer", "inner").unwrap().count, 3);
+    /// ```
+    ///
+    /// [`with_file`]: Builder::with_file
+    pub fn accumulate(mut self, enabled: bool) -> Self {
+        self.accumulate = enabled;
+        self
+    }
+
+    /// Builds the configured [`GraphLayer`] and a [`FlushGuard`] which writes
+    /// the graph to a `dot` file when dropped.
+    ///
+    /// The file is replaced atomically on every flush, see [`AtomicFile`].
+    ///
+    /// The path can hold placeholders, so that several processes, layers or
+    /// repeated runs don't overwrite each other's files: `{pid}` is replaced
+    /// with the id of the process, `{timestamp}` with the seconds since the
+    /// Unix epoch, and `{seq}` with a number counting the paths expanded with
+    /// it in the process, from 0. The paths given to [`folded_file`] and
+    /// [`speedscope_file`] are expanded the same way.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// let dir = std::env::temp_dir();
+    /// let (_layer, guard) = GraphLayer::builder()
+    ///     .with_file(dir.join("callgraph-{pid}.dot"))
+    ///     .unwrap();
+    /// drop(guard);
+    /// assert!(dir.join(format!("callgraph-{}.dot", std::process::id())).exists());
+    ///
+    /// // Every layer writing to the same template gets its own file.
+    /// let layers: Vec<_> = (0..2)
+    ///     .map(|_| GraphLayer::builder().with_file(dir.join("callgraph-{pid}-{seq}.dot")))
+    ///     .collect();
+    /// drop(layers);
+    /// for seq in 0..2 {
+    ///     let name = format!("callgraph-{}-{}.dot", std::process::id(), seq);
+    ///     assert!(dir.join(name).exists());
+    /// }
+    /// ```
+    ///
+    /// [`folded_file`]: Builder::folded_file
+    /// [`speedscope_file`]: Builder::speedscope_file
+    pub fn with_file(
+        self,
+        path: impl AsRef<Path>,
+    ) -> Result<(GraphLayer, FlushGuard<AtomicFile>), Error> {
+        let path = &template::expand(path.as_ref());
+        let baseline = if self.accumulate {
+            match gzip::read_to_string(path) {
+                Ok(dot) => Some(CallGraphSnapshot::from_dot(&dot)?),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+                Err(source) => {
+                    return Err(Error(Kind::ReadFile {
+                        path: path.into(),
+                        source,
+                    }))
+                }
+            }
+        } else {
+            None
+        };
+        let writer = AtomicFile::create(path)
+            .map_err(|source| Kind::CreateFile {
+                path: path.into(),
+                source,
+            })
+            .map_err(Error)?;
+        let layer = self.build();
+        let mut guard = layer.flush_on_drop(writer);
+        guard.baseline = baseline;
+        Ok((layer, guard))
+    }
+
+    /// Builds the configured [`GraphLayer`] and a [`FlushGuard`] which writes
+    /// the graph to a series of files named after `path`, see
+    /// [`RotatingFile`]. Placeholders in `path` are expanded as for
+    /// [`with_file`].
+    ///
+    /// [`with_file`]: Builder::with_file
+    pub fn with_rotating_file(
+        self,
+        path: impl AsRef<Path>,
+        rotation: Rotation,
+    ) -> (GraphLayer, FlushGuard<RotatingFile>) {
+        self.with_writer(RotatingFile::new(template::expand(path.as_ref()), rotation))
+    }
+
+    /// Builds the configured [`GraphLayer`] and a [`FlushGuard`] which writes
+    /// the graph to `writer` when dropped, such as a socket, a pipe or an
+    /// in-memory buffer.
+    pub fn with_writer<W>(self, writer: W) -> (GraphLayer, FlushGuard<W>)
+    where
+        W: Write + 'static,
+    {
+        let layer = self.build();
+        let guard = layer.flush_on_drop(writer);
+        (layer, guard)
+    }
+
+    /// Builds the configured [`GraphLayer`] and a [`FlushGuard`] which POSTs
+    /// the graph with `push` on every flush, see [`HttpPush`].
+    ///
+    /// With the `serde` feature, the snapshot is sent serialized as JSON,
+    /// with the `application/json` content type, so that a central service
+    /// can deserialize it into a [`CallGraphSnapshot`]. Otherwise, it is sent
+    /// in `dot` representation.
+    #[cfg(feature = "http")]
+    pub fn with_http_push(self, push: HttpPush) -> (GraphLayer, FlushGuard<HttpPush>) {
+        #[cfg(feature = "serde")]
+        let push = push.json();
+        #[allow(unused_mut)]
+        let (layer, mut guard) = self.with_writer(push);
+        #[cfg(feature = "serde")]
+        {
+            guard.json = true;
+        }
+        (layer, guard)
+    }
+
+    /// Builds the configured [`GraphLayer`] and a [`FlushGuard`] which writes
+    /// the graph to stdout when dropped.
+    ///
+    /// Stdout is locked only while the graph is written, which happens in a
+    /// single write, so it doesn't interleave with output of other threads.
+    pub fn with_stdout(self) -> (GraphLayer, FlushGuard<io::Stdout>) {
+        self.with_writer(io::stdout())
+    }
+
+    /// Builds the configured [`GraphLayer`] and a [`FlushGuard`] which writes
+    /// the graph to stderr when dropped, locking it like [`with_stdout`].
+    ///
+    /// [`with_stdout`]: Builder::with_stdout
+    pub fn with_stderr(self) -> (GraphLayer, FlushGuard<io::Stderr>) {
+        self.with_writer(io::stderr())
+    }
+}
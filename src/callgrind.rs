@@ -0,0 +1,76 @@
+use crate::{weight::accumulate, CallGraphSnapshot};
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    time::Duration,
+};
+
+impl CallGraphSnapshot {
+    /// Writes the snapshot to `writer` in the callgrind format, so the graph
+    /// can be explored in KCachegrind or QCachegrind.
+    ///
+    /// The single `ns` event holds time in nanoseconds. The self cost of a
+    /// node is its duration minus the duration of its calls, and every edge
+    /// becomes a call with its count and inclusive duration. Spans have no
+    /// source positions, so all costs are attributed to line `0`.
+    pub fn write_callgrind<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(self.to_callgrind().as_bytes())
+    }
+
+    /// Returns the snapshot in the callgrind format, see [`write_callgrind`].
+    ///
+    /// ```rust
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let callgrind = tracing_callgraph::with_graph_recording(outer).to_callgrind();
+    /// assert!(callgrind.contains("fn=(1) outer\n0 "));
+    /// assert!(callgrind.contains("cfn=(0)\ncalls=1 0\n"));
+    /// ```
+    ///
+    /// [`write_callgrind`]: CallGraphSnapshot::write_callgrind
+    pub fn to_callgrind(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# callgrind format\nversion: 1\ncreator: tracing-callgraph\n");
+        out.push_str("event: ns : Time (ns)\nevents: ns\n\n");
+
+        let mut named = vec![false; self.nodes().len()];
+        let mut name = |node: usize| {
+            if std::mem::replace(&mut named[node], true) {
+                format!("({})", node)
+            } else {
+                format!("({}) {}", node, self.nodes()[node].name)
+            }
+        };
+
+        let callees = self.callee_lists();
+        for (node, info) in self.nodes().iter().enumerate() {
+            let mut calls = Duration::default();
+            for edge in self.edges() {
+                if edge.caller == node {
+                    accumulate(&mut calls, edge.duration);
+                }
+            }
+            let _ = writeln!(out, "fn={}", name(node));
+            let _ = writeln!(out, "0 {}", info.duration.saturating_sub(calls).as_nanos());
+            for &(callee, count) in &callees[node] {
+                let duration = self
+                    .edge(&info.name, &self.nodes()[callee].name)
+                    .map_or(Duration::default(), |edge| edge.duration);
+                let _ = writeln!(out, "cfn={}", name(callee));
+                let _ = writeln!(out, "calls={} 0", count);
+                let _ = writeln!(out, "0 {}", duration.as_nanos());
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{weight::accumulate, CallGraphSnapshot};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+};
+
+/// The damping factor of [`Centrality::PageRank`].
+const DAMPING: f64 = 0.85;
+/// The most iterations spent on [`Centrality::PageRank`].
+const MAX_ITERATIONS: usize = 100;
+/// The total change of [`Centrality::PageRank`] scores below which they are
+/// considered converged.
+const TOLERANCE: f64 = 1e-9;
+
+/// A measure of how structurally important nodes are, see
+/// [`CallGraphSnapshot::centrality`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Centrality {
+    /// PageRank over edges weighted by their counts, which favors nodes that
+    /// are called often by nodes that are themselves called often. Scores
+    /// sum to `1.0`.
+    PageRank,
+    /// Betweenness over edges shortened by their counts, which favors nodes
+    /// that many of the most frequently taken paths between other nodes go
+    /// through. Scores range from `0.0` to `1.0`.
+    Betweenness,
+}
+
+impl CallGraphSnapshot {
+    /// Returns the score of every node by `measure`, in the order of
+    /// [`nodes`].
+    ///
+    /// [`nodes`]: CallGraphSnapshot::nodes
+    pub fn centrality(&self, measure: Centrality) -> Vec<f64> {
+        match measure {
+            Centrality::PageRank => self.pagerank(),
+            Centrality::Betweenness => self.betweenness(),
+        }
+    }
+
+    /// Returns the indices of all nodes with their score by `measure`,
+    /// highest first.
+    ///
+    /// Unlike raw call counts, this highlights code that is structurally
+    /// critical, such as a rarely called function that every request passes
+    /// through.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::Centrality;
+    ///
+    /// #[tracing::instrument]
+    /// fn handler() {
+    ///     for _ in 0..3 {
+    ///         route();
+    ///     }
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn route() {
+    ///     auth();
+    ///     query();
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn auth() {}
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {}
+    ///
+    /// let snapshot = tracing_callgraph::with_graph_recording(handler);
+    /// let (top, _) = snapshot.hotspots(Centrality::Betweenness)[0];
+    /// assert_eq!(snapshot.nodes()[top].name, "route");
+    /// ```
+    pub fn hotspots(&self, measure: Centrality) -> Vec<(usize, f64)> {
+        let mut ranked: Vec<_> = self.centrality(measure).into_iter().enumerate().collect();
+        ranked.sort_by(|(a, a_score), (b, b_score)| b_score.total_cmp(a_score).then(a.cmp(b)));
+        ranked
+    }
+
+    fn pagerank(&self) -> Vec<f64> {
+        let n = self.nodes().len();
+        if n == 0 {
+            return Vec::new();
+        }
+        let mut calls_out = vec![0; n];
+        for edge in self.edges() {
+            accumulate(&mut calls_out[edge.caller], edge.count);
+        }
+
+        let mut ranks = vec![1.0 / n as f64; n];
+        for _ in 0..MAX_ITERATIONS {
+            // Nodes calling nothing hand their rank to every node alike.
+            let dangling: f64 = (0..n)
+                .filter(|&node| calls_out[node] == 0)
+                .map(|node| ranks[node])
+                .sum();
+            let base = (1.0 - DAMPING + DAMPING * dangling) / n as f64;
+            let mut next = vec![base; n];
+            for edge in self.edges() {
+                let share = edge.count as f64 / calls_out[edge.caller] as f64;
+                next[edge.callee] += DAMPING * ranks[edge.caller] * share;
+            }
+            let change: f64 = next.iter().zip(&ranks).map(|(a, b)| (a - b).abs()).sum();
+            ranks = next;
+            if change < TOLERANCE {
+                break;
+            }
+        }
+        ranks
+    }
+
+    /// Computes betweenness with Brandes' algorithm, taking the length of an
+    /// edge to be the inverse of its count.
+    fn betweenness(&self) -> Vec<f64> {
+        let n = self.nodes().len();
+        let mut callees = vec![Vec::new(); n];
+        for edge in self.edges() {
+            if edge.caller != edge.callee && edge.count > 0 {
+                callees[edge.caller].push((edge.callee, 1.0 / edge.count as f64));
+            }
+        }
+
+        let mut scores = vec![0.0; n];
+        for source in 0..n {
+            let mut order = Vec::with_capacity(n);
+            let mut predecessors = vec![Vec::new(); n];
+            let mut paths = vec![0.0; n];
+            let mut distances = vec![f64::INFINITY; n];
+            let mut settled = vec![false; n];
+            paths[source] = 1.0;
+            distances[source] = 0.0;
+
+            let mut queue = BinaryHeap::new();
+            queue.push(Reverse(Distance(0.0, source)));
+            while let Some(Reverse(Distance(distance, node))) = queue.pop() {
+                if distance > distances[node] || settled[node] {
+                    continue;
+                }
+                settled[node] = true;
+                order.push(node);
+                for &(callee, length) in &callees[node] {
+                    let through = distance + length;
+                    if through < distances[callee] && !nearly_equal(through, distances[callee]) {
+                        distances[callee] = through;
+                        paths[callee] = 0.0;
+                        predecessors[callee].clear();
+                        queue.push(Reverse(Distance(through, callee)));
+                    }
+                    if nearly_equal(through, distances[callee]) {
+                        paths[callee] += paths[node];
+                        predecessors[callee].push(node);
+                    }
+                }
+            }
+
+            let mut dependencies = vec![0.0; n];
+            for &node in order.iter().rev() {
+                for &predecessor in &predecessors[node] {
+                    dependencies[predecessor] +=
+                        paths[predecessor] / paths[node] * (1.0 + dependencies[node]);
+                }
+                if node != source {
+                    scores[node] += dependencies[node];
+                }
+            }
+        }
+
+        if n > 2 {
+            let pairs = ((n - 1) * (n - 2)) as f64;
+            scores.iter_mut().for_each(|score| *score /= pairs);
+        }
+        scores
+    }
+}
+
+/// Returns whether `a` and `b` are equal up to rounding errors.
+fn nearly_equal(a: f64, b: f64) -> bool {
+    a.is_finite() && b.is_finite() && (a - b).abs() <= 1e-12 * a.abs().max(b.abs())
+}
+
+/// A distance from the source of [`CallGraphSnapshot::betweenness`], ordered
+/// by its length.
+#[derive(PartialEq)]
+struct Distance(f64, usize);
+
+impl Eq for Distance {}
+
+impl PartialOrd for Distance {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Distance {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
@@ -0,0 +1,234 @@
+use crate::{sync::RwLock, weight::accumulate, CallGraphSnapshot, EdgeInfo, NodeInfo};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read},
+    net::TcpListener,
+    str::Chars,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// Aggregates the calls forwarded by the layers of other processes into one
+/// graph, for fork-based and multi-service test rigs.
+///
+/// Worker processes forward their calls with [`Builder::edge_stream`] over a
+/// socket, or any other byte stream, and the collector sums them up as they
+/// arrive. Forwarded lines hold no durations, so the combined graph counts
+/// calls only, and every node counts the calls along its incoming edges.
+/// Lines that can't be read are skipped.
+///
+/// ```rust
+/// use std::{net::{TcpListener, TcpStream}, thread, time::Duration};
+/// use tracing_callgraph::{Collector, GraphLayer};
+///
+/// #[tracing::instrument]
+/// fn outer() {
+///     inner()
+/// }
+///
+/// #[tracing::instrument]
+/// fn inner() {}
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let address = listener.local_addr().unwrap();
+/// let collector = Collector::new();
+/// collector.listen(listener).unwrap();
+///
+/// // In every worker process.
+/// for _ in 0..2 {
+///     let socket = TcpStream::connect(address).unwrap();
+///     GraphLayer::builder().edge_stream(socket).record(outer);
+/// }
+///
+/// while collector.snapshot().edge("outer", "inner").map_or(0, |edge| edge.count) < 2 {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+/// ```
+///
+/// [`Builder::edge_stream`]: crate::Builder::edge_stream
+#[derive(Clone, Debug, Default)]
+pub struct Collector {
+    graph: Arc<RwLock<Collected>>,
+}
+
+/// The nodes and edges collected so far.
+#[derive(Debug, Default)]
+struct Collected {
+    indices: HashMap<String, usize>,
+    nodes: Vec<NodeInfo>,
+    edges: HashMap<(usize, usize), EdgeInfo>,
+}
+
+impl Collected {
+    fn node(&mut self, name: String) -> usize {
+        let nodes = &mut self.nodes;
+        *self.indices.entry(name).or_insert_with_key(|name| {
+            nodes.push(NodeInfo {
+                name: name.clone(),
+                calls: 0,
+                duration: Duration::default(),
+                callsite: None,
+                sla_violations: 0,
+            });
+            nodes.len() - 1
+        })
+    }
+
+    fn record(&mut self, caller: String, callee: String) {
+        let caller = self.node(caller);
+        let callee = self.node(callee);
+        accumulate(&mut self.nodes[callee].calls, 1);
+        let edge = self
+            .edges
+            .entry((caller, callee))
+            .or_insert_with(|| EdgeInfo {
+                caller,
+                callee,
+                count: 0,
+                duration: Duration::default(),
+                order: None,
+                errors: 0,
+                sla_violations: 0,
+                value: None,
+            });
+        accumulate(&mut edge.count, 1);
+    }
+}
+
+impl Collector {
+    /// Returns a collector that collected nothing yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collects the calls forwarded over every connection accepted by
+    /// `listener`, on background threads, until the connection is closed.
+    pub fn listen(&self, listener: TcpListener) -> io::Result<()> {
+        let collector = self.clone();
+        self.spawn(move || {
+            for stream in listener.incoming().flatten() {
+                collector.spawn_reader(stream);
+            }
+        })
+    }
+
+    /// Collects the calls forwarded over every connection accepted by the
+    /// Unix socket `listener`, like [`listen`].
+    ///
+    /// [`listen`]: Collector::listen
+    #[cfg(unix)]
+    pub fn listen_unix(&self, listener: UnixListener) -> io::Result<()> {
+        let collector = self.clone();
+        self.spawn(move || {
+            for stream in listener.incoming().flatten() {
+                collector.spawn_reader(stream);
+            }
+        })
+    }
+
+    /// Collects the calls forwarded over `reader` until it ends, on the
+    /// current thread.
+    ///
+    /// Lines may also be written by other tools, such as with Python's
+    /// `json.dumps`, which escapes names outside the basic multilingual
+    /// plane as surrogate pairs.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::Collector;
+    ///
+    /// let collector = Collector::new();
+    /// collector.read(&br#"{"caller": "handle", "callee": "\ud83e\udd80"}"#[..]);
+    /// assert_eq!(collector.snapshot().edge("handle", "\u{1f980}").unwrap().count, 1);
+    /// ```
+    pub fn read<R>(&self, reader: R)
+    where
+        R: Read,
+    {
+        for line in BufReader::new(reader).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if let (Some(caller), Some(callee)) = (field(&line, "caller"), field(&line, "callee")) {
+                self.graph.write().record(caller, callee);
+            }
+        }
+    }
+
+    /// Returns the graph of the calls collected so far.
+    pub fn snapshot(&self) -> CallGraphSnapshot {
+        let graph = self.graph.read();
+        CallGraphSnapshot::from_parts(graph.nodes.clone(), graph.edges.values().cloned().collect())
+    }
+
+    fn spawn_reader<R>(&self, reader: R)
+    where
+        R: Read + Send + 'static,
+    {
+        let collector = self.clone();
+        // A connection that can't be read from has nothing to collect.
+        let _ = self.spawn(move || collector.read(reader));
+    }
+
+    fn spawn<F>(&self, f: F) -> io::Result<()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        thread::Builder::new()
+            .name("tracing-callgraph-collector".into())
+            .spawn(f)
+            .map(drop)
+    }
+}
+
+/// Returns the string value of the field called `name` in a line of JSON
+/// written by the edge stream.
+fn field(line: &str, name: &str) -> Option<String> {
+    let key = format!("\"{}\":", name);
+    let start = line.find(&key)? + key.len();
+    let mut chars = line[start..].trim_start().strip_prefix('"')?.chars();
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                'b' => value.push('\u{8}'),
+                'f' => value.push('\u{c}'),
+                'u' => {
+                    let code = match hex(&mut chars)? {
+                        // A surrogate pair, escaped as two code units.
+                        high @ 0xd800..=0xdbff => {
+                            if (chars.next()?, chars.next()?) != ('\\', 'u') {
+                                return None;
+                            }
+                            match hex(&mut chars)? {
+                                low @ 0xdc00..=0xdfff => {
+                                    0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                                }
+                                _ => return None,
+                            }
+                        }
+                        code => code,
+                    };
+                    value.push(char::from_u32(code)?);
+                }
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// Returns the code unit escaped by the 4 hexadecimal digits of a `\u`
+/// escape.
+fn hex(chars: &mut Chars<'_>) -> Option<u32> {
+    let code: String = chars.by_ref().take(4).collect();
+    u32::from_str_radix(&code, 16).ok()
+}
@@ -0,0 +1,20 @@
+/// What the calls of nodes and edges count, see [`Builder::counting`].
+///
+/// [`Builder::counting`]: crate::Builder::counting
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Counting {
+    /// Count every time a span is entered.
+    ///
+    /// Synchronous spans are entered once, but futures instrumented with
+    /// `tracing` enter their span on every poll, so their counts reflect how
+    /// often they were polled.
+    #[default]
+    Enters,
+    /// Count every span once, when it is created, however often it is
+    /// entered.
+    ///
+    /// This counts calls of async functions rather than polls. Durations
+    /// still only include the time the spans were entered.
+    Spans,
+}
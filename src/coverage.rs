@@ -0,0 +1,353 @@
+use crate::{
+    error::{Error, Kind},
+    CallGraphSnapshot, EdgeInfo, NodeInfo,
+};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Write as _,
+    io::{self, Write},
+    time::Duration,
+};
+
+/// The nodes of LLVM call graphs standing for calls from and to outside the
+/// module, which aren't functions.
+const LLVM_PSEUDO_NODES: &[&str] = &["external node", "null function"];
+
+/// Which edges of a static call graph were followed at runtime, see
+/// [`CallGraphSnapshot::coverage`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Coverage {
+    /// The static edges followed at runtime, as the names of their caller
+    /// and callee in the static graph.
+    pub exercised: Vec<(String, String)>,
+    /// The static edges never followed at runtime.
+    pub missed: Vec<(String, String)>,
+    /// The edges recorded at runtime without a static counterpart, as the
+    /// names of their caller and callee in the recorded graph. These are
+    /// calls the static analysis couldn't see, such as through trait
+    /// objects, or calls through functions that aren't instrumented.
+    pub unexpected: Vec<(String, String)>,
+}
+
+impl Coverage {
+    /// Returns the share of the static edges that were exercised, or `1.0`
+    /// if the static graph has no edges.
+    pub fn ratio(&self) -> f64 {
+        let total = self.exercised.len() + self.missed.len();
+        match total {
+            0 => 1.0,
+            total => self.exercised.len() as f64 / total as f64,
+        }
+    }
+}
+
+impl CallGraphSnapshot {
+    /// Reads a static call graph from the `dot` output of a static analysis,
+    /// such as `cargo call-stack` or LLVM's `opt -passes=dot-callgraph`, for
+    /// comparison with a recorded graph by [`coverage`].
+    ///
+    /// Nodes are named by the first line of their label, or by their id if
+    /// they have none, without the hash Rust appends to symbol names. The
+    /// nodes LLVM adds for calls from and to outside the module are left
+    /// out. Counts and durations are zero. Symbol names aren't demangled, so
+    /// the output of LLVM is best piped through `rustfilt` first.
+    ///
+    /// [`coverage`]: CallGraphSnapshot::coverage
+    pub fn from_static_dot(input: &str) -> Result<Self, Error> {
+        let mut labels = HashMap::new();
+        let mut edges = Vec::new();
+        for (index, line) in input.lines().enumerate() {
+            let line = line.trim().trim_end_matches(';').trim_end();
+            let error = || Error(Kind::ParseDot { line: index + 1 });
+            // Graph attributes and defaults, and the braces of the graph.
+            if line.is_empty()
+                || line.starts_with("//")
+                || line.starts_with("digraph")
+                || line.starts_with('}')
+                || ["graph", "node", "edge"].contains(&statement_id(line))
+                || !line.contains('[') && !line.contains("->")
+            {
+                continue;
+            }
+            let (head, attributes) = match line.find('[') {
+                Some(start) => (&line[..start], Some(&line[start..])),
+                None => (line, None),
+            };
+            match head.split_once("->") {
+                Some((caller, callee)) => {
+                    edges.push((statement_id(caller), statement_id(callee)));
+                }
+                None => {
+                    let label = match attributes.map(label) {
+                        Some(Some(label)) => label.ok_or_else(error)?,
+                        _ => continue,
+                    };
+                    labels.insert(statement_id(head), function_name(&label));
+                }
+            }
+        }
+
+        let name = |id: &str| match labels.get(id) {
+            Some(name) => name.clone(),
+            None => function_name(id),
+        };
+        let called: BTreeSet<_> = edges
+            .iter()
+            .map(|&(caller, callee)| (name(caller), name(callee)))
+            .filter(|(caller, callee)| {
+                [caller, callee]
+                    .iter()
+                    .all(|name| !name.is_empty() && !LLVM_PSEUDO_NODES.contains(&name.as_str()))
+            })
+            .collect();
+        let names: BTreeSet<_> = called
+            .iter()
+            .flat_map(|(caller, callee)| [caller, callee])
+            .collect();
+        let indices: HashMap<_, _> = names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (*name, index))
+            .collect();
+        let nodes = names
+            .iter()
+            .map(|name| NodeInfo {
+                name: name.to_string(),
+                calls: 0,
+                duration: Duration::default(),
+                callsite: None,
+                sla_violations: 0,
+            })
+            .collect();
+        let edges = called
+            .iter()
+            .map(|(caller, callee)| EdgeInfo {
+                caller: indices[caller],
+                callee: indices[callee],
+                count: 0,
+                duration: Duration::default(),
+                order: None,
+                errors: 0,
+                sla_violations: 0,
+                value: None,
+            })
+            .collect();
+        Ok(Self::from_parts(nodes, edges))
+    }
+
+    /// Compares this recorded graph against a `static_graph`, such as one
+    /// read by [`from_static_dot`], returning which of the statically
+    /// possible calls were actually made: the call graph coverage of the
+    /// run.
+    ///
+    /// A recorded node stands for every static function whose path is its
+    /// name or ends with `::` and its name, so spans named after their
+    /// function, as by `#[tracing::instrument]`, match the full paths of a
+    /// static analysis. A static edge is exercised if it was recorded
+    /// between matching nodes. Calls made through functions that aren't
+    /// instrumented are recorded as direct edges, so they show up as
+    /// unexpected, and the static edges they took as missed.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::CallGraphSnapshot;
+    ///
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// // As written by `cargo call-stack`.
+    /// let static_graph = CallGraphSnapshot::from_static_dot(
+    ///     r#"digraph {
+    ///         node [fontname=monospace shape=box]
+    ///         0 [label="app::outer\nmax >= 0"]
+    ///         1 [label="app::inner\nmax = 0"]
+    ///         2 [label="app::fallback\nmax = 0"]
+    ///         0 -> 1
+    ///         0 -> 2
+    ///     }"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let snapshot = tracing_callgraph::with_graph_recording(outer);
+    /// let coverage = snapshot.coverage(&static_graph);
+    /// assert_eq!(coverage.exercised, [("app::outer".into(), "app::inner".into())]);
+    /// assert_eq!(coverage.missed, [("app::outer".into(), "app::fallback".into())]);
+    /// assert_eq!(coverage.ratio(), 0.5);
+    /// ```
+    ///
+    /// [`from_static_dot`]: CallGraphSnapshot::from_static_dot
+    pub fn coverage(&self, static_graph: &CallGraphSnapshot) -> Coverage {
+        let indices: HashMap<_, _> = self
+            .nodes()
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node.name.as_str(), index))
+            .collect();
+        // The recorded nodes matching every static node.
+        let matching: Vec<Vec<usize>> = static_graph
+            .nodes()
+            .iter()
+            .map(|node| {
+                suffixes(&node.name)
+                    .filter_map(|suffix| indices.get(suffix).copied())
+                    .collect()
+            })
+            .collect();
+        let recorded: HashSet<_> = self
+            .edges()
+            .iter()
+            .map(|edge| (edge.caller, edge.callee))
+            .collect();
+
+        let mut coverage = Coverage::default();
+        let mut expected = HashSet::new();
+        let name = |snapshot: &CallGraphSnapshot, node: usize| snapshot.nodes()[node].name.clone();
+        for edge in static_graph.edges() {
+            let mut exercised = false;
+            for &caller in &matching[edge.caller] {
+                for &callee in &matching[edge.callee] {
+                    if recorded.contains(&(caller, callee)) {
+                        expected.insert((caller, callee));
+                        exercised = true;
+                    }
+                }
+            }
+            let pair = (
+                name(static_graph, edge.caller),
+                name(static_graph, edge.callee),
+            );
+            match exercised {
+                true => coverage.exercised.push(pair),
+                false => coverage.missed.push(pair),
+            }
+        }
+        coverage.unexpected = self
+            .edges()
+            .iter()
+            .filter(|edge| !expected.contains(&(edge.caller, edge.callee)))
+            .map(|edge| (name(self, edge.caller), name(self, edge.callee)))
+            .collect();
+        coverage
+    }
+
+    /// Writes the [`coverage`] of `static_graph` by this recorded graph to
+    /// `writer`: the share of static edges exercised, followed by the edges
+    /// missed, exercised, and recorded without a static counterpart.
+    ///
+    /// [`coverage`]: CallGraphSnapshot::coverage
+    pub fn write_coverage_report<W>(
+        &self,
+        mut writer: W,
+        static_graph: &CallGraphSnapshot,
+    ) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(self.to_coverage_report(static_graph).as_bytes())
+    }
+
+    /// Returns the report of [`write_coverage_report`].
+    ///
+    /// [`write_coverage_report`]: CallGraphSnapshot::write_coverage_report
+    pub fn to_coverage_report(&self, static_graph: &CallGraphSnapshot) -> String {
+        let coverage = self.coverage(static_graph);
+        let mut report = String::new();
+        let _ = writeln!(
+            report,
+            "coverage: {} of {} static edges exercised ({:.1}%)",
+            coverage.exercised.len(),
+            coverage.exercised.len() + coverage.missed.len(),
+            coverage.ratio() * 100.0
+        );
+        let sections = [
+            ("missed", &coverage.missed),
+            ("exercised", &coverage.exercised),
+            ("not in the static graph", &coverage.unexpected),
+        ];
+        for (title, edges) in sections {
+            if edges.is_empty() {
+                continue;
+            }
+            let _ = writeln!(report, "\n{}:", title);
+            for (caller, callee) in edges {
+                let _ = writeln!(report, "    {} -> {}", caller, callee);
+            }
+        }
+        report
+    }
+}
+
+/// Returns the id a statement starts with, without quotes.
+fn statement_id(statement: &str) -> &str {
+    let statement = statement.trim();
+    let id = statement
+        .split(|c: char| c.is_whitespace() || c == '[')
+        .next()
+        .unwrap_or_default();
+    id.trim_matches('"')
+}
+
+/// Returns the value of the `label` attribute among `attributes`, if there
+/// is one, or an error if it is malformed.
+fn label(attributes: &str) -> Option<Option<String>> {
+    let start = attributes.find("label")?;
+    let value = attributes[start + "label".len()..].trim_start();
+    let value = match value.strip_prefix('=') {
+        Some(value) => value.trim_start(),
+        None => return Some(None),
+    };
+    let quoted = match value.strip_prefix('"') {
+        Some(quoted) => quoted,
+        None => {
+            let end = value
+                .find(|c: char| c == ',' || c == ']' || c.is_whitespace())
+                .unwrap_or(value.len());
+            return Some(Some(value[..end].to_string()));
+        }
+    };
+    let mut label = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(Some(label)),
+            '\\' => match chars.next() {
+                // Line breaks, of which only the first line is kept.
+                Some('n' | 'l' | 'r') => label.push('\n'),
+                Some(c) => label.push(c),
+                None => return Some(None),
+            },
+            c => label.push(c),
+        }
+    }
+    Some(None)
+}
+
+/// Returns the function named by the first line of `label`, without the
+/// braces of LLVM's record labels or the hash of Rust symbol names.
+fn function_name(label: &str) -> String {
+    let name = label.lines().next().unwrap_or_default().trim();
+    let name = name
+        .strip_prefix('{')
+        .and_then(|name| name.strip_suffix('}'))
+        .unwrap_or(name);
+    let hashed = name
+        .rsplit_once("::h")
+        .filter(|(_, hash)| hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()));
+    match hashed {
+        Some((name, _)) => name.to_string(),
+        None => name.to_string(),
+    }
+}
+
+/// Returns `path` and every suffix of it following a `::`.
+fn suffixes(path: &str) -> impl Iterator<Item = &str> {
+    std::iter::once(path).chain(
+        path.match_indices("::")
+            .map(move |(index, _)| &path[index + 2..]),
+    )
+}
@@ -0,0 +1,258 @@
+use crate::{
+    graph::{CallGraph, Counters, NodeId},
+    hash::{BuildHasher, HashMap},
+    sync::RwLock,
+    time::Instant,
+};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// The decayed weights of the nodes and edges of a graph, see
+/// [`Builder::half_life`].
+///
+/// Every call is weighed by the time it is made, measured from one clock:
+/// a call made a half-life later weighs twice as much, and snapshots scale
+/// all weights down to the weight of a call made now. Weights are stored
+/// relative to a landmark, which moves forward every half-life, when the
+/// calls recorded next rescale them and drop the nodes and edges weighing
+/// less than one call. Taking a snapshot changes nothing.
+///
+/// [`Builder::half_life`]: crate::Builder::half_life
+#[derive(Debug)]
+pub(crate) struct Decay {
+    start: Instant,
+    /// The half-life, in seconds.
+    half_life: f64,
+    weights: RwLock<Weights>,
+}
+
+#[derive(Debug)]
+struct Weights {
+    /// The number of half-lives after the start at which stored weights are
+    /// the weights of calls made then.
+    landmark: u64,
+    nodes: HashMap<NodeId, Weighted>,
+    edges: HashMap<(NodeId, NodeId), Weighted>,
+}
+
+/// The weights recorded for a node or an edge, relative to the landmark.
+#[derive(Debug, Default)]
+struct Weighted {
+    count: AtomicScalar,
+    /// The weighted time spent inside the node, in seconds.
+    duration: AtomicScalar,
+    errors: AtomicScalar,
+    sla_violations: AtomicScalar,
+    /// The lowest position of the edge, as in [`Counters`], which doesn't
+    /// decay.
+    order: AtomicU64,
+}
+
+/// What a call adds to the weights of its node and edge.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Call {
+    Enter,
+    Exit(Duration),
+    Order(usize),
+    Error,
+    SlaViolation,
+}
+
+impl Weighted {
+    /// Adds `call`, weighing `weight`, to the weights of a node, or of an
+    /// edge if `edge`.
+    fn add(&self, call: Call, weight: f64, edge: bool) {
+        match call {
+            Call::Enter => self.count.add(weight),
+            Call::Exit(elapsed) => self.duration.add(elapsed.as_secs_f64() * weight),
+            Call::Order(order) if edge => {
+                let order = order as u64;
+                let _ = self
+                    .order
+                    .fetch_update(
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                        |current| match current {
+                            0 => Some(order),
+                            current if order < current => Some(order),
+                            _ => None,
+                        },
+                    );
+            }
+            Call::Error if edge => self.errors.add(weight),
+            Call::SlaViolation => self.sla_violations.add(weight),
+            Call::Order(_) | Call::Error => {}
+        }
+    }
+
+    /// Returns the weights scaled by `factor` and rounded to whole counts.
+    fn counters(&self, factor: f64) -> Counters {
+        let order = match self.order.load(Ordering::Relaxed) {
+            0 => None,
+            order => Some(order as usize),
+        };
+        Counters::new(
+            count(self.count.get() * factor),
+            Duration::try_from_secs_f64(self.duration.get() * factor).unwrap_or(Duration::MAX),
+            count(self.errors.get() * factor),
+            count(self.sla_violations.get() * factor),
+            order,
+        )
+    }
+
+    fn scale(&mut self, factor: f64) {
+        self.count.scale(factor);
+        self.duration.scale(factor);
+        self.errors.scale(factor);
+        self.sla_violations.scale(factor);
+    }
+}
+
+/// A floating-point weight that can be added to with shared access.
+#[derive(Debug, Default)]
+struct AtomicScalar(AtomicU64);
+
+impl AtomicScalar {
+    fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn add(&self, weight: f64) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + weight).to_bits())
+            });
+    }
+
+    fn scale(&mut self, factor: f64) {
+        let scaled = self.get() * factor;
+        *self.0.get_mut() = scaled.to_bits();
+    }
+}
+
+impl Decay {
+    pub(crate) fn new(half_life: Duration, hasher: BuildHasher) -> Self {
+        Self {
+            start: Instant::now(),
+            half_life: half_life.max(Duration::from_nanos(1)).as_secs_f64(),
+            weights: RwLock::new(Weights {
+                landmark: 0,
+                nodes: HashMap::with_hasher(hasher.clone()),
+                edges: HashMap::with_hasher(hasher),
+            }),
+        }
+    }
+
+    /// Returns the number of half-lives since the start.
+    fn half_lives(&self) -> f64 {
+        Instant::now().duration_since(self.start).as_secs_f64() / self.half_life
+    }
+
+    /// Records `call` of `callee`, from `caller` if it is known, weighing
+    /// the calls made so far down if the landmark is a half-life old.
+    ///
+    /// Like [`Graphs::record_enter`], this takes shared access to the
+    /// weights if the node and edge were recorded before.
+    ///
+    /// [`Graphs::record_enter`]: crate::storage::Graphs::record_enter
+    pub(crate) fn record(&self, caller: Option<NodeId>, callee: NodeId, call: Call) {
+        let half_lives = self.half_lives();
+        let landmark = half_lives.min(u64::MAX as f64) as u64;
+        let weights = self.weights.read();
+        let weights = if weights.landmark < landmark {
+            drop(weights);
+            self.rescale(landmark);
+            self.weights.read()
+        } else {
+            weights
+        };
+        let weight = (half_lives - weights.landmark as f64).exp2();
+
+        let edge = match caller {
+            Some(caller) => weights.edges.get(&(caller, callee)).map(Some),
+            None => Some(None),
+        };
+        if let (Some(node), Some(edge)) = (weights.nodes.get(&callee), edge) {
+            node.add(call, weight, false);
+            if let Some(edge) = edge {
+                edge.add(call, weight, true);
+            }
+            return;
+        }
+        drop(weights);
+
+        let mut weights = self.weights.write();
+        let weights = &mut *weights;
+        let weight = (half_lives - weights.landmark as f64).exp2();
+        if let Some(caller) = caller {
+            weights.nodes.entry(caller).or_default();
+            weights
+                .edges
+                .entry((caller, callee))
+                .or_default()
+                .add(call, weight, true);
+        }
+        weights
+            .nodes
+            .entry(callee)
+            .or_default()
+            .add(call, weight, false);
+    }
+
+    /// Moves the landmark forward to `landmark`, rescaling the weights and
+    /// dropping the nodes and edges weighing less than one call.
+    fn rescale(&self, landmark: u64) {
+        let mut weights = self.weights.write();
+        let weights = &mut *weights;
+        // Another thread may have rescaled in the meantime.
+        if weights.landmark >= landmark {
+            return;
+        }
+        let factor = (weights.landmark as f64 - landmark as f64).exp2();
+        weights.landmark = landmark;
+        for weighted in weights.nodes.values_mut().chain(weights.edges.values_mut()) {
+            weighted.scale(factor);
+        }
+        weights
+            .nodes
+            .retain(|_, weighted| weighted.count.get() >= 1.0);
+        weights
+            .edges
+            .retain(|_, weighted| weighted.count.get() >= 1.0);
+    }
+
+    /// Adds the nodes and edges weighing at least one call now, with their
+    /// decayed weights, to `merged`.
+    pub(crate) fn merge_into(&self, merged: &mut CallGraph) {
+        let half_lives = self.half_lives();
+        let weights = self.weights.read();
+        let factor = (weights.landmark as f64 - half_lives).exp2();
+        for (&callee, weighted) in &weights.nodes {
+            if weighted.count.get() * factor >= 1.0 {
+                merged.add_counters(None, callee, &weighted.counters(factor));
+            }
+        }
+        for (&(caller, callee), weighted) in &weights.edges {
+            if weighted.count.get() * factor >= 1.0 {
+                merged.add_counters(Some(caller), callee, &weighted.counters(factor));
+            }
+        }
+    }
+
+    /// Removes a node along with all of its edges.
+    pub(crate) fn remove_node(&self, id: NodeId) {
+        let mut weights = self.weights.write();
+        weights.nodes.remove(&id);
+        weights
+            .edges
+            .retain(|(caller, callee), _| *caller != id && *callee != id);
+    }
+}
+
+/// Rounds a decayed count to the nearest count, saturating.
+fn count(weight: f64) -> u64 {
+    weight.round() as u64
+}
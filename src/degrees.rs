@@ -0,0 +1,111 @@
+use crate::{weight::accumulate, CallGraphSnapshot};
+use std::{
+    cmp::Reverse,
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+/// The fan-in and fan-out of a node, see [`CallGraphSnapshot::degrees`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NodeDegree {
+    /// Index of the node in [`CallGraphSnapshot::nodes`].
+    pub node: usize,
+    /// Number of distinct callers of the node.
+    pub fan_in: usize,
+    /// Number of distinct callees of the node.
+    pub fan_out: usize,
+    /// Number of calls into the node, summed over its callers.
+    pub calls_in: u64,
+    /// Number of calls out of the node, summed over its callees.
+    pub calls_out: u64,
+}
+
+impl CallGraphSnapshot {
+    /// Returns the fan-in and fan-out of every node, in the order of
+    /// [`nodes`].
+    ///
+    /// Nodes with a large fan-in are central utilities that much of the code
+    /// depends on, and nodes with a large fan-out are candidates for god
+    /// functions that coordinate too much.
+    ///
+    /// [`nodes`]: CallGraphSnapshot::nodes
+    pub fn degrees(&self) -> Vec<NodeDegree> {
+        let mut degrees: Vec<_> = (0..self.nodes().len())
+            .map(|node| NodeDegree {
+                node,
+                ..Default::default()
+            })
+            .collect();
+        for edge in self.edges() {
+            degrees[edge.callee].fan_in += 1;
+            accumulate(&mut degrees[edge.callee].calls_in, edge.count);
+            degrees[edge.caller].fan_out += 1;
+            accumulate(&mut degrees[edge.caller].calls_out, edge.count);
+        }
+        degrees
+    }
+
+    /// Writes the `n` nodes with the highest fan-in and fan-out to `writer`,
+    /// both by distinct nodes and by calls.
+    ///
+    /// ```rust
+    /// #[tracing::instrument]
+    /// fn handler() {
+    ///     for _ in 0..3 {
+    ///         log();
+    ///     }
+    ///     query();
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {
+    ///     log()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn log() {}
+    ///
+    /// let snapshot = tracing_callgraph::with_graph_recording(handler);
+    /// assert_eq!(
+    ///     snapshot.to_degree_report(1),
+    ///     "fan-in:\n    2 log\n\
+    ///      fan-out:\n    2 handler\n\
+    ///      calls in:\n    4 log\n\
+    ///      calls out:\n    4 handler\n",
+    /// );
+    /// ```
+    pub fn write_degree_report<W>(&self, mut writer: W, n: usize) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(self.to_degree_report(n).as_bytes())
+    }
+
+    /// Returns the report of [`write_degree_report`].
+    ///
+    /// [`write_degree_report`]: CallGraphSnapshot::write_degree_report
+    pub fn to_degree_report(&self, n: usize) -> String {
+        let degrees = self.degrees();
+        type Value = fn(&NodeDegree) -> u64;
+        let sections: [(&str, Value); 4] = [
+            ("fan-in", |degree| degree.fan_in as u64),
+            ("fan-out", |degree| degree.fan_out as u64),
+            ("calls in", |degree| degree.calls_in),
+            ("calls out", |degree| degree.calls_out),
+        ];
+
+        let mut report = String::new();
+        for (title, value) in sections {
+            let mut ranked: Vec<_> = degrees.iter().filter(|degree| value(degree) > 0).collect();
+            // Ties are broken by index, and so by name.
+            ranked.sort_by_key(|degree| (Reverse(value(degree)), degree.node));
+            let _ = writeln!(report, "{}:", title);
+            for degree in ranked.into_iter().take(n) {
+                let name = &self.nodes()[degree.node].name;
+                let _ = writeln!(report, "    {} {}", value(degree), name);
+            }
+        }
+        report
+    }
+}
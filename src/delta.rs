@@ -0,0 +1,40 @@
+use crate::CallGraphSnapshot;
+
+/// The graph written by the previous flush of a [`FlushGuard`], for writing
+/// only what changed since, see [`Builder::delta_flushes`].
+///
+/// [`FlushGuard`]: crate::FlushGuard
+/// [`Builder::delta_flushes`]: crate::Builder::delta_flushes
+#[derive(Debug)]
+pub(crate) struct DeltaFlushes {
+    full_every: usize,
+    flushes: usize,
+    previous: Option<CallGraphSnapshot>,
+}
+
+impl DeltaFlushes {
+    pub(crate) fn new(full_every: usize) -> Self {
+        Self {
+            full_every: full_every.max(1),
+            flushes: 0,
+            previous: None,
+        }
+    }
+
+    /// Returns the calls recorded in `snapshot` since the previous flush, or
+    /// `None` if the next flush writes the full graph.
+    pub(crate) fn delta(&self, snapshot: &CallGraphSnapshot) -> Option<CallGraphSnapshot> {
+        match &self.previous {
+            Some(previous) if !self.flushes.is_multiple_of(self.full_every) => {
+                Some(snapshot.since(previous))
+            }
+            _ => None,
+        }
+    }
+
+    /// Remembers `snapshot` as written by a successful flush.
+    pub(crate) fn flushed(&mut self, snapshot: CallGraphSnapshot) {
+        self.flushes += 1;
+        self.previous = Some(snapshot);
+    }
+}
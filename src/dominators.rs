@@ -0,0 +1,58 @@
+use crate::{CallGraphSnapshot, EdgeInfo};
+use petgraph::{
+    algo::dominators::{self, Dominators},
+    graph::NodeIndex,
+};
+use std::collections::HashSet;
+
+impl CallGraphSnapshot {
+    /// Computes the dominators of every node reachable from `root`.
+    ///
+    /// A node `a` dominates `b` if every call chain from `root` to `b` passes
+    /// through `a`. Node indices match [`nodes`], as with [`to_graph`].
+    /// Returns `None` if there is no node named `root`.
+    ///
+    /// [`nodes`]: CallGraphSnapshot::nodes
+    /// [`to_graph`]: CallGraphSnapshot::to_graph
+    pub fn dominators(&self, root: &str) -> Option<Dominators<NodeIndex>> {
+        let root = self.node_index(root)?;
+        Some(dominators::simple_fast(
+            &self.to_graph(),
+            NodeIndex::new(root),
+        ))
+    }
+
+    /// Returns the dominator tree rooted at `root` as a snapshot, for export.
+    ///
+    /// Each node reachable from `root` gets a single edge from its immediate
+    /// dominator, holding the total calls and duration of the node. Nodes
+    /// that aren't reachable are left out, while `root` is kept even if it
+    /// calls nothing.
+    /// Returns `None` if there is no node named `root`.
+    pub fn dominator_tree(&self, root: &str) -> Option<CallGraphSnapshot> {
+        let dominators = self.dominators(root)?;
+        let edges = (0..self.nodes().len())
+            .filter_map(|node| {
+                let dominator = dominators.immediate_dominator(NodeIndex::new(node))?;
+                Some(EdgeInfo {
+                    caller: dominator.index(),
+                    callee: node,
+                    count: self.nodes()[node].calls,
+                    duration: self.nodes()[node].duration,
+                    order: None,
+                    errors: 0,
+                    sla_violations: 0,
+                    value: None,
+                })
+            })
+            .collect();
+
+        let reachable: HashSet<_> = (0..self.nodes().len())
+            .filter(|&node| dominators.dominators(NodeIndex::new(node)).is_some())
+            .map(|node| self.nodes()[node].name.as_str())
+            .collect();
+        let mut tree = CallGraphSnapshot::from_parts(self.nodes().to_vec(), edges);
+        tree.retain_nodes(|node| reachable.contains(node.name.as_str()));
+        Some(tree)
+    }
+}
@@ -0,0 +1,1174 @@
+use crate::{
+    edge_label::EdgeLabel,
+    error::{Error, Kind},
+    theme::HIGHLIGHT_COLOR,
+    weight::accumulate,
+    CallGraphSnapshot, Centrality, EdgeInfo, EdgeRules, EdgeStats, NodeInfo, RunInfo, Theme,
+};
+use petgraph::{
+    dot::{Config, Dot},
+    graph::{DiGraph, EdgeReference, NodeIndex},
+    visit::EdgeRef,
+};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+    time::Duration,
+};
+
+/// Options controlling the `dot` output of a [`CallGraphSnapshot`].
+///
+/// These can be passed to [`CallGraphSnapshot::write_dot_with`], or to
+/// [`Builder::dot_options`] to configure what a [`FlushGuard`] writes.
+///
+/// [`Builder::dot_options`]: crate::Builder::dot_options
+/// [`FlushGuard`]: crate::FlushGuard
+#[derive(Clone, Debug, Default)]
+pub struct DotOptions {
+    stats: bool,
+    critical_path: bool,
+    labels: NodeLabel,
+    max_label_width: Option<usize>,
+    label_overflow: LabelOverflow,
+    theme: Option<Theme>,
+    edge_labels: Option<EdgeLabel>,
+    rules: Option<EdgeRules>,
+    node_sizing: Option<Centrality>,
+    edge_order: bool,
+    node_tables: bool,
+    strip_common_prefix: bool,
+}
+
+/// What the labels of nodes show, see [`DotOptions::with_labels`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NodeLabel {
+    /// The name of the node.
+    #[default]
+    Name,
+    /// The target and span name of the callsite of the node, such as
+    /// `app::db::query`.
+    Target,
+    /// The target and span name of the callsite of the node, followed by its
+    /// file and line, such as `src/db.rs:12`, on a second line.
+    Location,
+}
+
+/// How labels longer than [`DotOptions::with_max_label_width`] are shortened.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LabelOverflow {
+    /// Break the label into several lines, preferably after `::`, commas and
+    /// spaces.
+    #[default]
+    Wrap,
+    /// Elide the middle of the label, keeping its start and end.
+    Truncate,
+}
+
+impl DotOptions {
+    /// Returns the default options.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Append the [`GraphStats`] of the graph as a comment block after it.
+    ///
+    /// [`GraphStats`]: crate::GraphStats
+    pub fn with_stats(mut self, enabled: bool) -> Self {
+        self.stats = enabled;
+        self
+    }
+
+    /// Highlight the [`critical_path`] of the graph.
+    ///
+    /// [`critical_path`]: CallGraphSnapshot::critical_path
+    pub fn with_critical_path(mut self, enabled: bool) -> Self {
+        self.critical_path = enabled;
+        self
+    }
+
+    /// Style the graph with a preset of fonts, colors, arrowheads and
+    /// background, instead of the Graphviz defaults.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{DotOptions, GraphLayer, Theme};
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {}
+    ///
+    /// let snapshot = GraphLayer::builder().record(query);
+    /// let dot = snapshot.to_dot_with(&DotOptions::new().with_theme(Theme::Dark));
+    /// assert!(dot.contains(r##"graph [ bgcolor = "#1e1e2e""##));
+    /// ```
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = Some(theme);
+        self
+    }
+
+    /// Label edges with the output of `format`, instead of their count.
+    /// Relabelled edges keep their count as a `tooltip`, which is what
+    /// [`from_dot`] reads back.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{DotOptions, GraphLayer};
+    ///
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let snapshot = GraphLayer::builder().record(outer);
+    /// let options = DotOptions::new().with_edge_labels(|edge| {
+    ///     format!("{} calls ({:.0}%)", edge.count, edge.share_of_calls * 100.0)
+    /// });
+    /// let dot = snapshot.to_dot_with(&options);
+    /// assert!(dot.contains(r#"1 -> 0 [ label = "1 calls (100%)" tooltip = "1" ]"#));
+    /// ```
+    ///
+    /// [`from_dot`]: CallGraphSnapshot::from_dot
+    pub fn with_edge_labels<F>(mut self, format: F) -> Self
+    where
+        F: Fn(&EdgeStats<'_>) -> String + Send + Sync + 'static,
+    {
+        self.edge_labels = Some(EdgeLabel::new(format));
+        self
+    }
+
+    /// Draw edges violating `rules` dashed and highlighted, and list them in
+    /// a comment block after the graph.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{DotOptions, EdgeRules, GraphLayer};
+    ///
+    /// #[tracing::instrument]
+    /// fn handler() {
+    ///     query()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {}
+    ///
+    /// let snapshot = GraphLayer::builder().record(handler);
+    /// let rules = EdgeRules::new().deny("handler", "query");
+    /// let dot = snapshot.to_dot_with(&DotOptions::new().with_rules(rules));
+    /// assert!(dot.contains("// violations:\n//     handler -> query: 1 (deny handler -> query)"));
+    /// ```
+    pub fn with_rules(mut self, rules: EdgeRules) -> Self {
+        self.rules = Some(rules);
+        self
+    }
+
+    /// Prefix the labels of edges with their [`EdgeInfo::order`], if
+    /// recorded, such as `#2: 5` for the second edge followed, called five
+    /// times, so the graph conveys rough execution order. Relabelled edges
+    /// keep their count as a `tooltip`, which is what [`from_dot`] reads
+    /// back.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{DotOptions, GraphLayer};
+    ///
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let snapshot = GraphLayer::builder().edge_order(true).record(outer);
+    /// let dot = snapshot.to_dot_with(&DotOptions::new().with_edge_order(true));
+    /// assert!(dot.contains(r##"1 -> 0 [ label = "#1: 1" tooltip = "1" ]"##));
+    /// ```
+    ///
+    /// [`from_dot`]: CallGraphSnapshot::from_dot
+    pub fn with_edge_order(mut self, enabled: bool) -> Self {
+        self.edge_order = enabled;
+        self
+    }
+
+    /// Draw every node as a table of its statistics under its label: its
+    /// calls, total time, self time excluding the time of its callees, and
+    /// the errors reported in it, see [`EdgeInfo::errors`]. Nodes keep their
+    /// name as a `tooltip`, which is what [`from_dot`] reads back.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{CallGraphSnapshot, DotOptions, GraphLayer};
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {}
+    ///
+    /// let snapshot = GraphLayer::builder().record(query);
+    /// let dot = snapshot.to_dot_with(&DotOptions::new().with_node_tables(true));
+    /// assert!(dot.contains("<tr><td align=\"left\">calls</td><td align=\"right\">1</td></tr>"));
+    ///
+    /// let parsed = CallGraphSnapshot::from_dot(&dot).unwrap();
+    /// assert_eq!(parsed.nodes()[0].name, "query");
+    /// ```
+    ///
+    /// [`from_dot`]: CallGraphSnapshot::from_dot
+    pub fn with_node_tables(mut self, enabled: bool) -> Self {
+        self.node_tables = enabled;
+        self
+    }
+
+    /// Scale the font, and so the size, of every node by its score by
+    /// `measure`, so that structurally critical nodes stand out, see
+    /// [`CallGraphSnapshot::hotspots`].
+    pub fn with_node_sizing(mut self, measure: Centrality) -> Self {
+        self.node_sizing = Some(measure);
+        self
+    }
+
+    /// Label nodes as configured by `labels`, without changing the names
+    /// that identify them. Nodes that weren't recorded from spans, or were
+    /// read from `dot`, are always labelled with their name, and relabelled
+    /// nodes keep their name as a `tooltip`, which is what [`from_dot`]
+    /// reads back.
+    ///
+    /// Fully qualified labels keep graphs of large workspaces unambiguous
+    /// when spans in different crates share a name.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{DotOptions, GraphLayer, NodeLabel};
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {}
+    ///
+    /// let snapshot = GraphLayer::builder().record(query);
+    /// let dot = snapshot.to_dot_with(&DotOptions::new().with_labels(NodeLabel::Target));
+    /// assert!(dot.contains(r#"label = "rust_out::query" tooltip = "query""#));
+    /// ```
+    ///
+    /// [`from_dot`]: CallGraphSnapshot::from_dot
+    pub fn with_labels(mut self, labels: NodeLabel) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Shorten node labels longer than `width` characters, as configured by
+    /// [`with_label_overflow`]. Shortened nodes keep their full name as a
+    /// `tooltip`, which is also what [`from_dot`] reads back. Labels of
+    /// several lines are shortened line by line.
+    ///
+    /// ```
+    /// use tracing_callgraph::{CallGraphSnapshot, DotOptions};
+    ///
+    /// let snapshot = CallGraphSnapshot::from_dot(
+    ///     r#"digraph {
+    ///         0 [ label = "app::server::handlers::users::list" ]
+    ///     }"#,
+    /// )
+    /// .unwrap();
+    /// let dot = snapshot.to_dot_with(&DotOptions::new().with_max_label_width(20));
+    /// assert!(dot.contains(r#"label = "app::server::\lhandlers::users::\llist""#));
+    ///
+    /// let parsed = CallGraphSnapshot::from_dot(&dot).unwrap();
+    /// assert_eq!(parsed.nodes()[0].name, "app::server::handlers::users::list");
+    /// ```
+    ///
+    /// [`with_label_overflow`]: DotOptions::with_label_overflow
+    /// [`from_dot`]: CallGraphSnapshot::from_dot
+    pub fn with_max_label_width(mut self, width: usize) -> Self {
+        self.max_label_width = Some(width.max(1));
+        self
+    }
+
+    /// Strip the longest prefix of whole `::` segments shared by every label
+    /// qualified with `::`, such as `app::services::` from
+    /// `app::services::billing::charge` and `app::services::orders::list`,
+    /// before shortening labels to the [`with_max_label_width`]. The last
+    /// segment of a label is always kept, and labels without `::`, such as
+    /// those of external nodes, don't count. Relabelled nodes keep their
+    /// name as a `tooltip`, which is what [`from_dot`] reads back.
+    ///
+    /// ```
+    /// use tracing_callgraph::{CallGraphSnapshot, DotOptions};
+    ///
+    /// let snapshot = CallGraphSnapshot::from_dot(
+    ///     r#"digraph {
+    ///         0 [ label = "main" ]
+    ///         1 [ label = "app::services::billing::charge" ]
+    ///         2 [ label = "app::services::orders::list" ]
+    ///     }"#,
+    /// )
+    /// .unwrap();
+    /// let dot = snapshot.to_dot_with(&DotOptions::new().with_common_prefix_stripped(true));
+    /// assert!(dot.contains(r#"label = "main" ]"#));
+    /// assert!(dot.contains(r#"label = "billing::charge" tooltip"#));
+    /// assert!(dot.contains(r#"label = "orders::list" tooltip"#));
+    /// ```
+    ///
+    /// [`with_max_label_width`]: DotOptions::with_max_label_width
+    /// [`from_dot`]: CallGraphSnapshot::from_dot
+    pub fn with_common_prefix_stripped(mut self, enabled: bool) -> Self {
+        self.strip_common_prefix = enabled;
+        self
+    }
+
+    /// Whether labels longer than the [`with_max_label_width`] are wrapped or
+    /// truncated, defaults to [`LabelOverflow::Wrap`].
+    ///
+    /// [`with_max_label_width`]: DotOptions::with_max_label_width
+    pub fn with_label_overflow(mut self, overflow: LabelOverflow) -> Self {
+        self.label_overflow = overflow;
+        self
+    }
+
+    /// Returns the prefix stripped from the labels of the nodes of
+    /// `snapshot`, see [`with_common_prefix_stripped`].
+    ///
+    /// [`with_common_prefix_stripped`]: DotOptions::with_common_prefix_stripped
+    fn common_prefix(&self, snapshot: &CallGraphSnapshot) -> String {
+        if !self.strip_common_prefix {
+            return String::new();
+        }
+        let mut common: Option<Vec<String>> = None;
+        for node in snapshot.nodes() {
+            let label = self.full_label(node);
+            let first_line = label.lines().next().unwrap_or_default();
+            let segments: Vec<_> = first_line.split("::").collect();
+            if segments.len() < 2 {
+                continue;
+            }
+            // Keep the last segment of every label.
+            let qualifiers = &segments[..segments.len() - 1];
+            common = Some(match common {
+                None => qualifiers
+                    .iter()
+                    .map(|segment| segment.to_string())
+                    .collect(),
+                Some(mut common) => {
+                    let shared = common
+                        .iter()
+                        .zip(qualifiers)
+                        .take_while(|(a, b)| a == *b)
+                        .count();
+                    common.truncate(shared);
+                    common
+                }
+            });
+        }
+        common
+            .unwrap_or_default()
+            .iter()
+            .map(|segment| format!("{}::", segment))
+            .collect()
+    }
+
+    /// Returns the label of `node`, without `prefix`, see [`common_prefix`].
+    ///
+    /// [`common_prefix`]: DotOptions::common_prefix
+    fn label<'a>(&self, node: &'a NodeInfo, prefix: &str) -> Cow<'a, str> {
+        let label = match self.full_label(node) {
+            Cow::Borrowed(label) => Cow::Borrowed(label.strip_prefix(prefix).unwrap_or(label)),
+            Cow::Owned(label) => match label.strip_prefix(prefix) {
+                Some(stripped) => Cow::Owned(stripped.to_string()),
+                None => Cow::Owned(label),
+            },
+        };
+        let width = match self.max_label_width {
+            Some(width) if label.lines().any(|line| line.chars().count() > width) => width,
+            _ => return label,
+        };
+        let lines: Vec<_> = label
+            .lines()
+            .map(|line| match self.label_overflow {
+                _ if line.chars().count() <= width => line.to_string(),
+                LabelOverflow::Wrap => wrap(line, width),
+                LabelOverflow::Truncate => truncate(line, width),
+            })
+            .collect();
+        Cow::Owned(lines.join("\n"))
+    }
+
+    /// Returns the label of `node` as configured by [`with_labels`], before
+    /// it is stripped and shortened.
+    ///
+    /// [`with_labels`]: DotOptions::with_labels
+    fn full_label<'a>(&self, node: &'a NodeInfo) -> Cow<'a, str> {
+        match (self.labels, &node.callsite) {
+            (NodeLabel::Target, Some(callsite)) => {
+                Cow::Owned(format!("{}::{}", callsite.target, callsite.name))
+            }
+            (NodeLabel::Location, Some(callsite)) => {
+                let mut label = format!("{}::{}", callsite.target, callsite.name);
+                if let (Some(file), Some(line)) = (&callsite.file, callsite.line) {
+                    label += &format!("\n{}:{}", file, line);
+                }
+                Cow::Owned(label)
+            }
+            _ => Cow::Borrowed(node.name.as_str()),
+        }
+    }
+}
+
+/// The colors of added, removed and significantly changed edges in
+/// [`CallGraphSnapshot::write_diff_dot`].
+const ADDED_COLOR: &str = "green";
+const REMOVED_COLOR: &str = "red";
+const CHANGED_COLOR: &str = "gold";
+
+/// Labels are written with the other attributes, escaped by [`escape`],
+/// rather than by `petgraph`.
+const DOT_CONFIG: &[Config] = &[
+    Config::GraphContentOnly,
+    Config::NodeNoLabel,
+    Config::EdgeNoLabel,
+];
+
+/// The color of the labels of edges with errors, see [`EdgeInfo::errors`].
+const ERROR_COLOR: &str = "red";
+
+/// The color of nodes and edges that exceeded their threshold, see
+/// [`Builder::sla`].
+///
+/// [`Builder::sla`]: crate::Builder::sla
+const SLA_COLOR: &str = "orange";
+
+/// The font sizes of the least and most central nodes with
+/// [`DotOptions::with_node_sizing`].
+const MIN_FONT_SIZE: f64 = 10.0;
+const MAX_FONT_SIZE: f64 = 30.0;
+
+impl CallGraphSnapshot {
+    /// Writes the snapshot to `writer` in Graphviz `dot` representation.
+    pub fn write_dot<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.write_dot_with(writer, &DotOptions::default())
+    }
+
+    /// Writes the snapshot to `writer` in Graphviz `dot` representation, as
+    /// configured by `options`.
+    ///
+    /// Labels and tooltips are escaped for quoted `dot` strings, so names
+    /// from any source, such as field values, are shown as they are and read
+    /// back by [`from_dot`]. Line breaks become left-justified `\l` breaks,
+    /// and other control characters and `&` become HTML entities, which
+    /// Graphviz decodes. Other characters are written as UTF-8.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{CallGraphSnapshot, GraphLayer};
+    ///
+    /// #[tracing::instrument(fields(peer.service = "say \"hi\" \\ to\n✓ &\t"))]
+    /// fn call() {}
+    ///
+    /// let snapshot = GraphLayer::builder().external_calls(true).record(call);
+    /// let dot = snapshot.to_dot();
+    /// assert!(dot.contains(r#"label = "external: say \"hi\" \\ to\l✓ &amp;&#9;""#));
+    ///
+    /// let parsed = CallGraphSnapshot::from_dot(&dot).unwrap();
+    /// assert_eq!(parsed.nodes()[1].name, "external: say \"hi\" \\ to\n✓ &\t");
+    /// ```
+    ///
+    /// [`from_dot`]: CallGraphSnapshot::from_dot
+    pub fn write_dot_with<W>(&self, mut writer: W, options: &DotOptions) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mut graph =
+            DiGraph::<Cow<'_, str>, String>::with_capacity(self.nodes().len(), self.edges().len());
+        let prefix = options.common_prefix(self);
+        for node in self.nodes() {
+            graph.add_node(options.label(node, &prefix));
+        }
+        let total_calls = self
+            .edges()
+            .iter()
+            .fold(0, |total: u64, edge| total.saturating_add(edge.count));
+        for edge in self.edges() {
+            let caller = NodeIndex::new(edge.caller);
+            let callee = NodeIndex::new(edge.callee);
+            let mut label = match &options.edge_labels {
+                Some(format) => format.format(&self.edge_stats(edge, total_calls)),
+                None => {
+                    // Aggregated values stand in for the count, which is
+                    // kept in the tooltip.
+                    let value = edge.value.unwrap_or(edge.count);
+                    let mut notes = Vec::new();
+                    match edge.errors {
+                        0 => {}
+                        1 => notes.push("1 error".to_string()),
+                        errors => notes.push(format!("{} errors", errors)),
+                    }
+                    if edge.sla_violations > 0 {
+                        notes.push(format!("{} over SLA", edge.sla_violations));
+                    }
+                    match notes.is_empty() {
+                        true => value.to_string(),
+                        false => format!("{} ({})", value, notes.join(", ")),
+                    }
+                }
+            };
+            if let (true, Some(order)) = (options.edge_order, edge.order) {
+                label = format!("#{}: {}", order, label);
+            }
+            graph.add_edge(caller, callee, label);
+        }
+
+        let highlighted = if options.critical_path {
+            self.critical_path()
+        } else {
+            Vec::new()
+        };
+        let style = options.theme.map(Theme::style);
+        let color = style
+            .as_ref()
+            .map_or(HIGHLIGHT_COLOR, |style| style.highlight);
+        let highlight = |enabled| {
+            if enabled {
+                format!("color = \"{}\" penwidth = 2 ", color)
+            } else {
+                String::new()
+            }
+        };
+        let sizes = options.node_sizing.map(|measure| {
+            let scores = self.centrality(measure);
+            let max = scores.iter().copied().fold(0.0, f64::max);
+            let scale = |score: f64| if max > 0.0 { score / max } else { 0.0 };
+            let sizes: Vec<_> = scores.into_iter().map(scale).collect();
+            sizes
+        });
+        let tables = if options.node_tables {
+            Some(self.node_tables(options, &prefix))
+        } else {
+            None
+        };
+        let node_attributes = |_, (node, label): (NodeIndex, &Cow<'_, str>)| {
+            let name = &self.nodes()[node.index()].name;
+            let mut attributes = match &tables {
+                Some(tables) => format!(
+                    "label = <{}> shape = \"box\" margin = 0 ",
+                    tables[node.index()]
+                ),
+                None => format!("label = \"{}\" ", escape(label)),
+            };
+            // Names in quotes would be read back as labels of older versions.
+            if label != name || tables.is_some() || name.starts_with('"') {
+                attributes += &format!("tooltip = \"{}\" ", escape(name));
+            }
+            if let Some(sizes) = &sizes {
+                let size = MIN_FONT_SIZE + (MAX_FONT_SIZE - MIN_FONT_SIZE) * sizes[node.index()];
+                attributes += &format!("fontsize = {:.1} ", size);
+            }
+            let violations = self.nodes()[node.index()].sla_violations;
+            if violations > 0 {
+                attributes += &format!(
+                    "color = \"{}\" xlabel = \"{} over SLA\" ",
+                    SLA_COLOR, violations
+                );
+            }
+            attributes + &highlight(highlighted.contains(&node.index()))
+        };
+        let violations = match &options.rules {
+            Some(rules) => self.indexed_violations(rules),
+            None => Vec::new(),
+        };
+        let edge_attributes = |_, edge: EdgeReference<'_, String>| {
+            let (caller, callee) = (edge.source().index(), edge.target().index());
+            let on_path = highlighted.windows(2).any(|pair| pair == [caller, callee]);
+            let violation = violations
+                .binary_search_by_key(&edge.id().index(), |(index, _)| *index)
+                .is_ok();
+            // Edges were added in order, so their indices match the snapshot.
+            let info = &self.edges()[edge.id().index()];
+            let mut tooltip = format!("label = \"{}\" ", escape(edge.weight()));
+            tooltip += &if *edge.weight() != info.count.to_string() {
+                format!("tooltip = \"{}\" ", info.count)
+            } else {
+                String::new()
+            };
+            if info.errors > 0 {
+                tooltip += &format!("fontcolor = \"{}\" ", ERROR_COLOR);
+            }
+            if info.sla_violations > 0 {
+                tooltip += &format!("color = \"{}\" ", SLA_COLOR);
+            }
+            let style = if violation { "style = \"dashed\" " } else { "" };
+            tooltip + &highlight(on_path || violation) + style
+        };
+        let dot = Dot::with_attr_getters(&graph, DOT_CONFIG, &edge_attributes, &node_attributes);
+        if let Some(run) = self.run() {
+            writeln!(writer, "// run:")?;
+            for (name, value) in &run.fields() {
+                writeln!(writer, "//     {}: {}", name, value.replace('\n', " "))?;
+            }
+        }
+        writeln!(writer, "digraph {{")?;
+        if let Some(style) = style {
+            writeln!(writer, "    graph [ {} ]", style.graph)?;
+            writeln!(writer, "    node [ {} ]", style.node)?;
+            writeln!(writer, "    edge [ {} ]", style.edge)?;
+        }
+        writeln!(writer, "{:?}}}\n", dot)?;
+
+        if options.stats {
+            self.write_stats_comment(&mut writer)?;
+        }
+        if !violations.is_empty() {
+            writeln!(writer, "// violations:")?;
+            for (_, violation) in &violations {
+                writeln!(writer, "//     {}", violation)?;
+            }
+        }
+        if let Some(overhead) = self.overhead() {
+            writeln!(
+                writer,
+                "// overhead: events={} recording={:?} lock_wait={:?}",
+                overhead.events, overhead.recording, overhead.lock_wait
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the snapshot in Graphviz `dot` representation, see
+    /// [`write_dot`].
+    ///
+    /// [`write_dot`]: CallGraphSnapshot::write_dot
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(&DotOptions::default())
+    }
+
+    /// Returns the snapshot in Graphviz `dot` representation, as configured
+    /// by `options`.
+    pub fn to_dot_with(&self, options: &DotOptions) -> String {
+        let mut dot = Vec::new();
+        self.write_dot_with(&mut dot, options)
+            .expect("expected: writing to a Vec never fails");
+        String::from_utf8(dot).expect("expected: dot output is UTF-8")
+    }
+
+    /// Writes the nodes and edges of both this snapshot and `baseline` to
+    /// `writer` in Graphviz `dot` representation, colored by how they
+    /// changed, for reviewing the behavioral impact of a refactor.
+    ///
+    /// Edges and nodes only in this snapshot are green, and the ones only in
+    /// `baseline` are red and dashed. Edges whose count changed by at least
+    /// `threshold` of its baseline count, such as `0.5` for 50%, are yellow
+    /// and labelled with both counts. Removed edges are labelled with their
+    /// baseline count, all others with their count in this snapshot.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn handler(cached: bool) {
+    ///     if cached {
+    ///         cache()
+    ///     } else {
+    ///         query();
+    ///         query();
+    ///     }
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn cache() {}
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {}
+    ///
+    /// let baseline = GraphLayer::builder().record(|| handler(false));
+    /// let snapshot = GraphLayer::builder().record(|| handler(true));
+    /// let dot = snapshot.to_diff_dot(&baseline, 0.5);
+    /// assert!(dot.contains(r#"1 -> 0 [ label = "1" color = "green" fontcolor = "green" ]"#));
+    /// assert!(dot.contains(r#"1 -> 2 [ label = "2" color = "red" fontcolor = "red" style = "dashed" ]"#));
+    /// ```
+    pub fn write_diff_dot<W>(
+        &self,
+        mut writer: W,
+        baseline: &CallGraphSnapshot,
+        threshold: f64,
+    ) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let names: BTreeSet<&str> = self
+            .nodes()
+            .iter()
+            .chain(baseline.nodes())
+            .map(|node| node.name.as_str())
+            .collect();
+        let names: Vec<_> = names.into_iter().collect();
+        let index = |name: &str| {
+            names
+                .binary_search(&name)
+                .expect("expected: every name is in the union")
+        };
+
+        // The counts of every edge in the baseline and in this snapshot.
+        let mut counts = BTreeMap::<(usize, usize), (u64, u64)>::new();
+        for edge in baseline.edges() {
+            let caller = index(&baseline.nodes()[edge.caller].name);
+            let callee = index(&baseline.nodes()[edge.callee].name);
+            accumulate(
+                &mut counts.entry((caller, callee)).or_default().0,
+                edge.count,
+            );
+        }
+        for edge in self.edges() {
+            let caller = index(&self.nodes()[edge.caller].name);
+            let callee = index(&self.nodes()[edge.callee].name);
+            accumulate(
+                &mut counts.entry((caller, callee)).or_default().1,
+                edge.count,
+            );
+        }
+
+        let mut graph = DiGraph::<&str, String>::with_capacity(names.len(), counts.len());
+        for name in &names {
+            graph.add_node(name);
+        }
+        let mut changes = Vec::with_capacity(counts.len());
+        for (&(caller, callee), &(before, after)) in &counts {
+            let (label, change) = match (before, after) {
+                (0, after) => (after.to_string(), Change::Added),
+                (before, 0) => (before.to_string(), Change::Removed),
+                (before, after) => {
+                    let ratio = (after as f64 - before as f64).abs() / before as f64;
+                    if ratio >= threshold {
+                        (format!("{} → {}", before, after), Change::Changed)
+                    } else {
+                        (after.to_string(), Change::Unchanged)
+                    }
+                }
+            };
+            graph.add_edge(NodeIndex::new(caller), NodeIndex::new(callee), label);
+            changes.push(change);
+        }
+
+        let node_change = |name: &str| match (
+            baseline.node_index(name).is_some(),
+            self.node_index(name).is_some(),
+        ) {
+            (false, _) => Change::Added,
+            (_, false) => Change::Removed,
+            _ => Change::Unchanged,
+        };
+        let node_attributes = |_, (_, name): (NodeIndex, &&str)| {
+            let mut attributes = format!("label = \"{}\" ", escape(name));
+            if name.starts_with('"') {
+                attributes += &format!("tooltip = \"{}\" ", escape(name));
+            }
+            attributes + &node_change(name).attributes()
+        };
+        let edge_attributes = |_, edge: EdgeReference<'_, String>| {
+            format!("label = \"{}\" ", escape(edge.weight()))
+                + &changes[edge.id().index()].attributes()
+        };
+        let dot = Dot::with_attr_getters(&graph, DOT_CONFIG, &edge_attributes, &node_attributes);
+        writeln!(writer, "digraph {{\n{:?}}}\n", dot)
+    }
+
+    /// Returns the changes between `baseline` and this snapshot in Graphviz
+    /// `dot` representation, see [`write_diff_dot`].
+    ///
+    /// [`write_diff_dot`]: CallGraphSnapshot::write_diff_dot
+    pub fn to_diff_dot(&self, baseline: &CallGraphSnapshot, threshold: f64) -> String {
+        let mut dot = Vec::new();
+        self.write_diff_dot(&mut dot, baseline, threshold)
+            .expect("expected: writing to a Vec never fails");
+        String::from_utf8(dot).expect("expected: dot output is UTF-8")
+    }
+
+    fn edge_stats<'a>(&'a self, edge: &EdgeInfo, total_calls: u64) -> EdgeStats<'a> {
+        let share = |count, total| match total {
+            0 => 0.0,
+            total => count as f64 / total as f64,
+        };
+        let callee = &self.nodes()[edge.callee];
+        EdgeStats {
+            caller: &self.nodes()[edge.caller].name,
+            callee: &callee.name,
+            count: edge.count,
+            duration: edge.duration,
+            errors: edge.errors,
+            sla_violations: edge.sla_violations,
+            value: edge.value,
+            share_of_calls: share(edge.count, total_calls),
+            share_of_callee: share(edge.count, callee.calls),
+        }
+    }
+
+    /// Returns the table of statistics of every node under its label as
+    /// configured by `options`, for [`DotOptions::with_node_tables`].
+    fn node_tables(&self, options: &DotOptions, prefix: &str) -> Vec<String> {
+        let mut callees = vec![Duration::default(); self.nodes().len()];
+        let mut errors = vec![0; self.nodes().len()];
+        for edge in self.edges() {
+            if edge.caller != edge.callee {
+                accumulate(&mut callees[edge.caller], edge.duration);
+            }
+            accumulate(&mut errors[edge.callee], edge.errors);
+        }
+        self.nodes()
+            .iter()
+            .enumerate()
+            .map(|(index, node)| {
+                let label = options.label(node, prefix);
+                let header = html_escape(&label).replace('\n', "<br/>");
+                let rows = [
+                    ("calls", node.calls.to_string()),
+                    ("total", format!("{:?}", node.duration)),
+                    (
+                        "self",
+                        format!("{:?}", node.duration.saturating_sub(callees[index])),
+                    ),
+                    ("errors", errors[index].to_string()),
+                ];
+                let mut table = format!(
+                    "<table border=\"0\" cellborder=\"0\" cellspacing=\"0\" cellpadding=\"2\">\
+                     <tr><td colspan=\"2\"><b>{}</b></td></tr>",
+                    header
+                );
+                for (name, value) in &rows {
+                    table += &format!(
+                        "<tr><td align=\"left\">{}</td><td align=\"right\">{}</td></tr>",
+                        name, value
+                    );
+                }
+                table + "</table>"
+            })
+            .collect()
+    }
+
+    fn write_stats_comment<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let stats = self.stats();
+        writeln!(writer, "// nodes: {}", stats.nodes)?;
+        writeln!(writer, "// edges: {}", stats.edges)?;
+        writeln!(writer, "// total calls: {}", stats.total_calls)?;
+        writeln!(writer, "// max fan-in: {}", stats.max_fan_in)?;
+        writeln!(writer, "// max fan-out: {}", stats.max_fan_out)?;
+        writeln!(writer, "// max depth: {}", stats.max_depth)?;
+        writeln!(writer, "// heaviest edges:")?;
+        for edge in &stats.heaviest_edges {
+            writeln!(
+                writer,
+                "//     {} -> {}: {}",
+                self.nodes()[edge.caller].name,
+                self.nodes()[edge.callee].name,
+                edge.count
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Parses a graph previously written by [`write_dot`] or a [`FlushGuard`].
+    ///
+    /// This is not a general purpose `dot` parser, only the subset emitted by
+    /// this crate is understood. Nodes are named, and edges counted, by their
+    /// `tooltip` if they have one, and by their label otherwise. Durations
+    /// are not part of the `dot` output and are left at zero, and the calls
+    /// of each node are taken from its incoming edges.
+    ///
+    /// [`write_dot`]: CallGraphSnapshot::write_dot
+    /// [`FlushGuard`]: crate::FlushGuard
+    pub fn from_dot(input: &str) -> Result<Self, Error> {
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        let mut run: Option<RunInfo> = None;
+        let mut in_run = false;
+        for (index, line) in input.lines().enumerate() {
+            let line = line.trim();
+            // The run information, as `//     name: value` lines after
+            // `// run:`.
+            if line == "// run:" {
+                in_run = true;
+                run = Some(RunInfo::default());
+                continue;
+            }
+            if let (true, Some(run), Some(field)) = (in_run, &mut run, line.strip_prefix("//")) {
+                let field = field.trim_start();
+                if let Some((name, value)) = field.split_once(':') {
+                    if run.set(name, value.trim_start()) {
+                        continue;
+                    }
+                }
+            }
+            in_run = false;
+            if line.is_empty() || line.starts_with("//") || line == "digraph {" || line == "}" {
+                continue;
+            }
+            // The attributes of a theme.
+            if ["graph [", "node [", "edge ["]
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+            {
+                continue;
+            }
+            let error = || Error(Kind::ParseDot { line: index + 1 });
+
+            let (head, label, attributes) = split_statement(line).ok_or_else(error)?;
+            if let Some((caller, callee)) = split_edge(head) {
+                let count = match tooltip(attributes) {
+                    Some(tooltip) => tooltip.ok_or_else(error)?,
+                    None => label,
+                };
+                let count = count.parse().map_err(|_| error())?;
+                edges.push(EdgeInfo {
+                    caller,
+                    callee,
+                    count,
+                    duration: Duration::default(),
+                    order: None,
+                    errors: 0,
+                    sla_violations: 0,
+                    value: None,
+                });
+            } else {
+                let id: usize = head.parse().map_err(|_| error())?;
+                if id != nodes.len() {
+                    return Err(error());
+                }
+                let name = match tooltip(attributes) {
+                    Some(tooltip) => tooltip.ok_or_else(error)?,
+                    // Older versions quoted labels as the `Debug` of the name.
+                    None if label.starts_with('"') => parse_debug_str(&label).ok_or_else(error)?,
+                    None => label,
+                };
+                nodes.push(NodeInfo {
+                    name,
+                    calls: 0,
+                    duration: Duration::default(),
+                    callsite: None,
+                    sla_violations: 0,
+                });
+            }
+        }
+
+        if edges
+            .iter()
+            .any(|edge| edge.caller >= nodes.len() || edge.callee >= nodes.len())
+        {
+            return Err(Error(Kind::ParseDot {
+                line: input.lines().count(),
+            }));
+        }
+        for edge in &edges {
+            accumulate(&mut nodes[edge.callee].calls, edge.count);
+        }
+        let mut snapshot = Self::from_parts(nodes, edges);
+        snapshot.run = run;
+        Ok(snapshot)
+    }
+}
+
+/// Splits `<head> [ label = "<label>" <attributes>]` into its head, unescaped
+/// label and other attributes. HTML-like labels, of
+/// [`DotOptions::with_node_tables`], are skipped and returned empty.
+fn split_statement(line: &str) -> Option<(&str, String, &str)> {
+    let (head, rest) = line.split_at(line.find(" [ label = ")?);
+    if let Some(html) = rest.strip_prefix(" [ label = <") {
+        let attributes = skip_html(html)?;
+        return match attributes.ends_with(']') {
+            true => Some((head.trim(), String::new(), attributes)),
+            false => None,
+        };
+    }
+    let rest = rest.strip_prefix(" [ label = \"")?;
+    if !rest.ends_with(']') {
+        return None;
+    }
+    let (label, attributes) = unescape(rest)?;
+    Some((head.trim(), label, attributes))
+}
+
+/// Skips an HTML-like label from after its opening `<` up to its closing
+/// `>`, and returns the input left after it.
+fn skip_html(input: &str) -> Option<&str> {
+    let mut depth = 1;
+    for (index, c) in input.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            _ => continue,
+        }
+        if depth == 0 {
+            return Some(&input[index + 1..]);
+        }
+    }
+    None
+}
+
+/// Escapes `input` for the text of an HTML-like label.
+fn html_escape(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+/// Returns the unescaped `tooltip` of `attributes`, if there is one, or
+/// `Some(None)` if it is malformed.
+fn tooltip(attributes: &str) -> Option<Option<String>> {
+    const START: &str = "tooltip = \"";
+    let value = &attributes[attributes.find(START)? + START.len()..];
+    Some(unescape(value).map(|(value, _)| value))
+}
+
+/// Unescapes a quoted string from after its opening quote up to its closing
+/// quote, and returns it with the input left after it, reversing [`escape`].
+fn unescape(input: &str) -> Option<(String, &str)> {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.char_indices();
+    loop {
+        match chars.next()? {
+            (end, '"') => return Some((output, &input[end + 1..])),
+            (_, '\\') => match chars.next()?.1 {
+                'l' | 'n' => output.push('\n'),
+                c => output.push(c),
+            },
+            (start, '&') => match entity(&input[start..]) {
+                Some((c, len)) => {
+                    output.push(c);
+                    // Skip the rest of the entity after its `&`.
+                    chars.nth(len - 2);
+                }
+                None => output.push('&'),
+            },
+            (_, c) => output.push(c),
+        }
+    }
+}
+
+/// Decodes the entity `input` starts with, as written by [`escape`], and
+/// returns it with the length of the entity in bytes.
+fn entity(input: &str) -> Option<(char, usize)> {
+    if input.starts_with("&amp;") {
+        return Some(('&', "&amp;".len()));
+    }
+    let end = input.find(';')?;
+    let code = input[..end].strip_prefix("&#")?.parse().ok()?;
+    Some((std::char::from_u32(code)?, end + 1))
+}
+
+/// Escapes `input` for a quoted `dot` string, see
+/// [`CallGraphSnapshot::write_dot_with`].
+fn escape(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\l"),
+            '&' => output.push_str("&amp;"),
+            c if c.is_control() => output.push_str(&format!("&#{};", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+/// How a node or edge changed between a baseline and a snapshot, see
+/// [`CallGraphSnapshot::write_diff_dot`].
+#[derive(Clone, Copy)]
+enum Change {
+    Unchanged,
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Change {
+    fn attributes(self) -> String {
+        let (color, style) = match self {
+            Self::Unchanged => return String::new(),
+            Self::Added => (ADDED_COLOR, ""),
+            Self::Removed => (REMOVED_COLOR, "style = \"dashed\" "),
+            Self::Changed => (CHANGED_COLOR, "penwidth = 2 "),
+        };
+        format!("color = \"{0}\" fontcolor = \"{0}\" {1}", color, style)
+    }
+}
+
+/// Breaks `name` into lines of at most `width` characters, preferably after
+/// `::`, commas and spaces.
+fn wrap(name: &str, width: usize) -> String {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    for (index, c) in name.char_indices() {
+        if matches!(c, ',' | ' ') || (c == ':' && name[..index].ends_with(':')) {
+            let end = index + c.len_utf8();
+            tokens.push(&name[start..end]);
+            start = end;
+        }
+    }
+    tokens.push(&name[start..]);
+
+    let mut lines = vec![String::new()];
+    for mut token in tokens {
+        let mut line = lines.pop().expect("expected: there is always a line");
+        if !line.is_empty() && line.chars().count() + token.chars().count() > width {
+            lines.push(line);
+            line = String::new();
+        }
+        // Tokens too long for a line of their own are split anywhere.
+        while let Some((split, _)) = token.char_indices().nth(width - line.chars().count()) {
+            line.push_str(&token[..split]);
+            lines.push(line);
+            line = String::new();
+            token = &token[split..];
+        }
+        line.push_str(token);
+        lines.push(line);
+    }
+    let lines: Vec<_> = lines.iter().map(|line| line.trim_end()).collect();
+    lines.join("\n")
+}
+
+/// Elides the middle of `name` to shorten it to `width` characters.
+fn truncate(name: &str, width: usize) -> String {
+    let kept = width - 1;
+    let head = name.chars().take(kept - kept / 2);
+    let tail = name.chars().skip(name.chars().count() - kept / 2);
+    head.chain(Some('…')).chain(tail).collect()
+}
+
+/// Splits `<caller> -> <callee>` into its node ids.
+fn split_edge(head: &str) -> Option<(usize, usize)> {
+    let mut parts = head.split(" -> ");
+    let caller = parts.next()?.trim().parse().ok()?;
+    let callee = parts.next()?.trim().parse().ok()?;
+    Some((caller, callee))
+}
+
+/// Reverses the `Debug` formatting of a `&str`.
+fn parse_debug_str(input: &str) -> Option<String> {
+    let input = input.strip_prefix('"')?.strip_suffix('"')?;
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => output.push('\n'),
+            'r' => output.push('\r'),
+            't' => output.push('\t'),
+            '0' => output.push('\0'),
+            'u' => {
+                let digits: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let digits = digits.strip_prefix('{')?;
+                output.push(std::char::from_u32(u32::from_str_radix(digits, 16).ok()?)?);
+            }
+            c => output.push(c),
+        }
+    }
+    Some(output)
+}
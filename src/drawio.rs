@@ -0,0 +1,117 @@
+use crate::{layout::NODE_HEIGHT, CallGraphSnapshot};
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+/// The styles of nodes and edges, close to the defaults of draw.io.
+const NODE_STYLE: &str = "rounded=1;whiteSpace=wrap;";
+const EDGE_STYLE: &str = "edgeStyle=orthogonalEdgeStyle;rounded=1;endArrow=classic;";
+const DOWN_PORTS: &str = "exitX=0.5;exitY=1;entryX=0.5;entryY=0;";
+
+impl CallGraphSnapshot {
+    /// Writes the snapshot to `writer` as a draw.io (diagrams.net) diagram in
+    /// mxGraph XML, to be opened and annotated in draw.io for design
+    /// documents.
+    ///
+    /// Nodes are laid out in layers like the SVG output, with callers above
+    /// their callees, and edges are labelled with their counts, so the
+    /// diagram is readable as is and can be rearranged by hand.
+    ///
+    /// Requires the `drawio` feature.
+    pub fn write_drawio<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(self.to_drawio().as_bytes())
+    }
+
+    /// Returns the snapshot as a draw.io diagram, see [`write_drawio`].
+    ///
+    /// ```rust
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let drawio = tracing_callgraph::with_graph_recording(outer).to_drawio();
+    /// assert!(drawio.starts_with("<mxfile"));
+    /// assert!(drawio.contains(r#"<mxCell id="node-1" value="outer""#));
+    /// assert!(drawio.contains(r#"source="node-1" target="node-0""#));
+    /// ```
+    ///
+    /// [`write_drawio`]: CallGraphSnapshot::write_drawio
+    pub fn to_drawio(&self) -> String {
+        let placed = self.layout();
+        let mut xml = String::new();
+        xml.push_str(concat!(
+            r#"<mxfile host="tracing-callgraph">"#,
+            "\n",
+            r#"  <diagram id="callgraph" name="Call graph">"#,
+            "\n",
+            "    <mxGraphModel>\n",
+            "      <root>\n",
+            r#"        <mxCell id="0"/>"#,
+            "\n",
+            r#"        <mxCell id="1" parent="0"/>"#,
+            "\n",
+        ));
+
+        for (index, (node, place)) in self.nodes().iter().zip(&placed).enumerate() {
+            let _ = writeln!(
+                xml,
+                r#"        <mxCell id="node-{}" value="{}" style="{}" vertex="1" parent="1">"#,
+                index,
+                escape(&node.name),
+                NODE_STYLE
+            );
+            let _ = writeln!(
+                xml,
+                r#"          <mxGeometry x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" as="geometry"/>"#,
+                place.x - place.width / 2.0,
+                place.y - NODE_HEIGHT / 2.0,
+                place.width,
+                NODE_HEIGHT
+            );
+            xml.push_str("        </mxCell>\n");
+        }
+        for (index, edge) in self.edges().iter().enumerate() {
+            // Calls down the layers leave their caller at the bottom and enter
+            // their callee at the top, calls closing a cycle are routed freely.
+            let ports = if placed[edge.callee].layer > placed[edge.caller].layer {
+                DOWN_PORTS
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                xml,
+                r#"        <mxCell id="edge-{}" value="{}" style="{}{}" edge="1" parent="1" source="node-{}" target="node-{}">"#,
+                index, edge.count, EDGE_STYLE, ports, edge.caller, edge.callee
+            );
+            xml.push_str("          <mxGeometry relative=\"1\" as=\"geometry\"/>\n");
+            xml.push_str("        </mxCell>\n");
+        }
+
+        xml.push_str("      </root>\n    </mxGraphModel>\n  </diagram>\n</mxfile>\n");
+        xml
+    }
+}
+
+/// Escapes `text` for use in an XML attribute value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#xa;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
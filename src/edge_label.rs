@@ -0,0 +1,64 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+/// What is known about an edge when formatting its label, see
+/// [`DotOptions::with_edge_labels`].
+///
+/// [`DotOptions::with_edge_labels`]: crate::DotOptions::with_edge_labels
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct EdgeStats<'a> {
+    /// Name of the calling node.
+    pub caller: &'a str,
+    /// Name of the called node.
+    pub callee: &'a str,
+    /// Number of times the callee was entered from the caller.
+    pub count: u64,
+    /// Total time spent inside the callee when entered from the caller.
+    pub duration: Duration,
+    /// Number of error events reported inside the callee when entered from
+    /// the caller, see [`EdgeInfo::errors`].
+    ///
+    /// [`EdgeInfo::errors`]: crate::EdgeInfo::errors
+    pub errors: u64,
+    /// Number of times the callee ran for longer than its threshold when
+    /// entered from the caller, see [`EdgeInfo::sla_violations`].
+    ///
+    /// [`EdgeInfo::sla_violations`]: crate::EdgeInfo::sla_violations
+    pub sla_violations: u64,
+    /// The values of a span field combined onto the edge, see
+    /// [`EdgeInfo::value`].
+    ///
+    /// [`EdgeInfo::value`]: crate::EdgeInfo::value
+    pub value: Option<u64>,
+    /// The share of all calls in the graph made along this edge, from `0.0`
+    /// to `1.0`.
+    pub share_of_calls: f64,
+    /// The share of the calls of the callee made by this caller, from `0.0`
+    /// to `1.0`.
+    pub share_of_callee: f64,
+}
+
+/// A formatter for edge labels, see [`DotOptions::with_edge_labels`].
+///
+/// [`DotOptions::with_edge_labels`]: crate::DotOptions::with_edge_labels
+#[derive(Clone)]
+pub(crate) struct EdgeLabel(Arc<dyn Fn(&EdgeStats<'_>) -> String + Send + Sync>);
+
+impl EdgeLabel {
+    pub(crate) fn new<F>(format: F) -> Self
+    where
+        F: Fn(&EdgeStats<'_>) -> String + Send + Sync + 'static,
+    {
+        Self(Arc::new(format))
+    }
+
+    pub(crate) fn format(&self, stats: &EdgeStats<'_>) -> String {
+        (self.0)(stats)
+    }
+}
+
+impl fmt::Debug for EdgeLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EdgeLabel").finish()
+    }
+}
@@ -0,0 +1,11 @@
+//! Binary encodings shared by the pprof and Parquet outputs.
+
+/// Appends `value` to `out` as an unsigned LEB128 varint, as used by
+/// protobuf and the Thrift compact protocol.
+pub(crate) fn varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
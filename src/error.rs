@@ -1,3 +1,4 @@
+use crate::Violation;
 use std::fmt;
 use std::path::PathBuf;
 
@@ -32,6 +33,8 @@ impl std::error::Error for Error {
         match &self.0 {
             Kind::CreateFile { ref source, .. } => Some(source),
             Kind::FlushFile(ref source) => Some(source),
+            Kind::ReadFile { ref source, .. } => Some(source),
+            Kind::ParseDot { .. } | Kind::ParseRules { .. } | Kind::Violations(_) => None,
         }
     }
 }
@@ -43,6 +46,17 @@ pub(crate) enum Kind {
         path: PathBuf,
     },
     FlushFile(std::io::Error),
+    ReadFile {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+    ParseDot {
+        line: usize,
+    },
+    ParseRules {
+        line: usize,
+    },
+    Violations(Vec<Violation>),
 }
 
 impl fmt::Display for Kind {
@@ -52,6 +66,22 @@ impl fmt::Display for Kind {
                 write!(f, "cannot create output file. path={}", path.display())
             }
             Self::FlushFile { .. } => write!(f, "cannot flush output buffer"),
+            Self::ReadFile { path, .. } => {
+                write!(
+                    f,
+                    "cannot read existing output file. path={}",
+                    path.display()
+                )
+            }
+            Self::ParseDot { line } => write!(f, "cannot parse dot input. line={}", line),
+            Self::ParseRules { line } => write!(f, "cannot parse edge rules. line={}", line),
+            Self::Violations(violations) => {
+                write!(f, "edges violate the rules.")?;
+                for violation in violations {
+                    write!(f, " edge={}", violation)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -0,0 +1,269 @@
+use crate::CallGraphSnapshot;
+use std::fmt;
+
+/// A set of expectations about a [`CallGraphSnapshot`], for integration
+/// tests that check more of a graph than [`assert_edge!`] does at a time.
+///
+/// All expectations are checked by [`verify`], which reports every one that
+/// failed along with what was recorded instead.
+///
+/// ```rust
+/// use tracing_callgraph::{with_graph_recording, GraphExpectation};
+///
+/// #[tracing::instrument]
+/// fn handler() {
+///     service()
+/// }
+///
+/// #[tracing::instrument]
+/// fn service() {
+///     query()
+/// }
+///
+/// #[tracing::instrument]
+/// fn query() {}
+///
+/// let snapshot = with_graph_recording(handler);
+/// GraphExpectation::new()
+///     .edge("handler", "service")
+///     .path(["handler", "service", "query"])
+///     .absent_edge("handler", "query")
+///     .assert(&snapshot);
+///
+/// let failure = GraphExpectation::new()
+///     .edge("handler", "query")
+///     .verify(&snapshot)
+///     .unwrap_err();
+/// assert_eq!(
+///     failure.to_string(),
+///     "1 of 1 graph expectations failed:\n    \
+///      expected edge `handler -> query`, but `handler` only calls `service`",
+/// );
+/// ```
+///
+/// [`assert_edge!`]: crate::assert_edge
+/// [`verify`]: GraphExpectation::verify
+#[derive(Clone, Debug, Default)]
+pub struct GraphExpectation {
+    expectations: Vec<Expectation>,
+}
+
+#[derive(Clone, Debug)]
+enum Expectation {
+    Node(String),
+    AbsentNode(String),
+    Edge {
+        caller: String,
+        callee: String,
+        count: Option<u64>,
+    },
+    AbsentEdge {
+        caller: String,
+        callee: String,
+    },
+    Path(Vec<String>),
+}
+
+/// The expectations of a [`GraphExpectation`] that a snapshot failed, see
+/// [`GraphExpectation::verify`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpectationFailure {
+    failures: Vec<String>,
+    expectations: usize,
+}
+
+impl ExpectationFailure {
+    /// Returns a description of every failed expectation, in the order they
+    /// were added.
+    pub fn failures(&self) -> &[String] {
+        &self.failures
+    }
+}
+
+impl fmt::Display for ExpectationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} graph expectations failed:",
+            self.failures.len(),
+            self.expectations
+        )?;
+        for failure in &self.failures {
+            write!(f, "\n    {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ExpectationFailure {}
+
+impl GraphExpectation {
+    /// Returns an expectation that every snapshot meets.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Expect a node called `name`.
+    pub fn node(mut self, name: impl Into<String>) -> Self {
+        self.expectations.push(Expectation::Node(name.into()));
+        self
+    }
+
+    /// Expect no node called `name`.
+    pub fn absent_node(mut self, name: impl Into<String>) -> Self {
+        self.expectations.push(Expectation::AbsentNode(name.into()));
+        self
+    }
+
+    /// Expect an edge from `caller` to `callee`.
+    pub fn edge(mut self, caller: impl Into<String>, callee: impl Into<String>) -> Self {
+        self.expectations.push(Expectation::Edge {
+            caller: caller.into(),
+            callee: callee.into(),
+            count: None,
+        });
+        self
+    }
+
+    /// Expect an edge from `caller` to `callee` called exactly `count` times.
+    pub fn edge_count(
+        mut self,
+        caller: impl Into<String>,
+        callee: impl Into<String>,
+        count: u64,
+    ) -> Self {
+        self.expectations.push(Expectation::Edge {
+            caller: caller.into(),
+            callee: callee.into(),
+            count: Some(count),
+        });
+        self
+    }
+
+    /// Expect no edge from `caller` to `callee`.
+    pub fn absent_edge(mut self, caller: impl Into<String>, callee: impl Into<String>) -> Self {
+        self.expectations.push(Expectation::AbsentEdge {
+            caller: caller.into(),
+            callee: callee.into(),
+        });
+        self
+    }
+
+    /// Expect an edge between every consecutive pair of `nodes`.
+    pub fn path<I>(mut self, nodes: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let nodes = nodes.into_iter().map(Into::into).collect();
+        self.expectations.push(Expectation::Path(nodes));
+        self
+    }
+
+    /// Checks every expectation against `snapshot`, returning the ones it
+    /// failed, if any.
+    pub fn verify(&self, snapshot: &CallGraphSnapshot) -> Result<(), ExpectationFailure> {
+        let failures: Vec<_> = self
+            .expectations
+            .iter()
+            .filter_map(|expectation| expectation.check(snapshot))
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(ExpectationFailure {
+                failures,
+                expectations: self.expectations.len(),
+            })
+        }
+    }
+
+    /// Checks every expectation against `snapshot`, see [`verify`].
+    ///
+    /// # Panics
+    ///
+    /// Panics listing the failed expectations, if any.
+    ///
+    /// [`verify`]: GraphExpectation::verify
+    #[track_caller]
+    pub fn assert(&self, snapshot: &CallGraphSnapshot) {
+        if let Err(failure) = self.verify(snapshot) {
+            panic!("{}", failure);
+        }
+    }
+}
+
+impl Expectation {
+    /// Returns a description of the failure, if `snapshot` fails this.
+    fn check(&self, snapshot: &CallGraphSnapshot) -> Option<String> {
+        match self {
+            Self::Node(name) => match snapshot.node_index(name) {
+                Some(_) => None,
+                None => Some(format!(
+                    "expected node `{}`, but it was never recorded",
+                    name
+                )),
+            },
+            Self::AbsentNode(name) => snapshot.node_index(name).map(|node| {
+                format!(
+                    "unexpected node `{}` with {} calls",
+                    name,
+                    snapshot.nodes()[node].calls
+                )
+            }),
+            Self::Edge {
+                caller,
+                callee,
+                count,
+            } => match (snapshot.edge(caller, callee), count) {
+                (Some(edge), Some(count)) if edge.count != *count => Some(format!(
+                    "edge `{} -> {}` has count {}, expected count {}",
+                    caller, callee, edge.count, count
+                )),
+                (Some(_), _) => None,
+                (None, _) => Some(format!(
+                    "expected edge `{} -> {}`, but {}",
+                    caller,
+                    callee,
+                    missing_edge(snapshot, caller)
+                )),
+            },
+            Self::AbsentEdge { caller, callee } => snapshot.edge(caller, callee).map(|edge| {
+                format!(
+                    "unexpected edge `{} -> {}` with count {}",
+                    caller, callee, edge.count
+                )
+            }),
+            Self::Path(nodes) => {
+                let missing = nodes
+                    .windows(2)
+                    .find(|pair| snapshot.edge(&pair[0], &pair[1]).is_none())?;
+                Some(format!(
+                    "expected path `{}`, but {}",
+                    nodes.join(" -> "),
+                    missing_edge(snapshot, &missing[0])
+                ))
+            }
+        }
+    }
+}
+
+/// Describes what `caller` calls, to explain why an edge from it is
+/// missing.
+fn missing_edge(snapshot: &CallGraphSnapshot, caller: &str) -> String {
+    let index = match snapshot.node_index(caller) {
+        Some(index) => index,
+        None => return format!("`{}` was never recorded", caller),
+    };
+    let callees: Vec<_> = snapshot
+        .edges()
+        .iter()
+        .filter(|edge| edge.caller == index)
+        .map(|edge| format!("`{}`", snapshot.nodes()[edge.callee].name))
+        .collect();
+    if callees.is_empty() {
+        format!("`{}` calls nothing", caller)
+    } else {
+        format!("`{}` only calls {}", caller, callees.join(", "))
+    }
+}
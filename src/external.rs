@@ -0,0 +1,74 @@
+use std::fmt;
+use tracing::field::{Field, Visit};
+
+/// The span fields recognized as naming an external dependency, with the
+/// most specific first, see [`Builder::external_calls`].
+///
+/// [`Builder::external_calls`]: crate::Builder::external_calls
+pub const EXTERNAL_FIELDS: &[&str] = &[
+    "peer.service",
+    "rpc.service",
+    "db.system",
+    "messaging.system",
+    "server.address",
+    "net.peer.name",
+    "url.full",
+    "http.url",
+];
+
+/// The prefix of the names of external nodes.
+pub(crate) const EXTERNAL_PREFIX: &str = "external: ";
+
+/// Finds the external dependency named by the fields of a span.
+#[derive(Debug, Default)]
+pub(crate) struct ExternalVisitor {
+    found: Option<(usize, String)>,
+}
+
+impl ExternalVisitor {
+    /// Returns the name of the node for the most specific field found.
+    pub(crate) fn node_name(self) -> Option<String> {
+        let (_, value) = self.found?;
+        Some(format!("{}{}", EXTERNAL_PREFIX, value))
+    }
+}
+
+impl Visit for ExternalVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let rank = match EXTERNAL_FIELDS
+            .iter()
+            .position(|name| *name == field.name())
+        {
+            Some(rank) => rank,
+            None => return,
+        };
+        if matches!(self.found, Some((found, _)) if found <= rank) || value.is_empty() {
+            return;
+        }
+        let value = match field.name() {
+            "url.full" | "http.url" => host(value),
+            _ => value,
+        };
+        self.found = Some((rank, value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if EXTERNAL_FIELDS.contains(&field.name()) {
+            self.record_str(field, &format!("{:?}", value));
+        }
+    }
+}
+
+/// Returns the host and port of `url`, so that calls to different paths of
+/// the same service share a node.
+fn host(url: &str) -> &str {
+    let authority = match url.find("://") {
+        Some(scheme) => &url[scheme + 3..],
+        None => url,
+    };
+    let authority = authority.split(['/', '?', '#']).next().unwrap_or(authority);
+    match authority.rfind('@') {
+        Some(user_info) => &authority[user_info + 1..],
+        None => authority,
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{storage::Graphs, CallGraphSnapshot};
+use std::{
+    fmt, panic,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+};
+
+type Report = dyn Fn(&CallGraphSnapshot) + Send + Sync;
+
+/// Hands the graph to a callback when the process panics or enough errors
+/// were reported, see [`Builder::on_failure`].
+///
+/// [`Builder::on_failure`]: crate::Builder::on_failure
+pub(crate) struct Failure {
+    errors: usize,
+    seen: AtomicUsize,
+    report: Box<Report>,
+}
+
+impl Failure {
+    pub(crate) fn new<F>(errors: usize, report: F) -> Self
+    where
+        F: Fn(&CallGraphSnapshot) + Send + Sync + 'static,
+    {
+        Self {
+            errors,
+            seen: AtomicUsize::new(0),
+            report: Box::new(report),
+        }
+    }
+
+    /// Counts an error event, and reports the graph when that reaches the
+    /// threshold, taking the `snapshot` only then.
+    pub(crate) fn error<F>(&self, snapshot: F)
+    where
+        F: FnOnce() -> CallGraphSnapshot,
+    {
+        let seen = self.seen.fetch_add(1, Ordering::Relaxed) + 1;
+        if seen == self.errors {
+            (self.report)(&snapshot());
+        }
+    }
+
+    /// Reports the graph before any panic hook set up earlier runs, such as
+    /// one capturing the panic in an error tracker.
+    pub(crate) fn install(self: &Arc<Self>, graph: Weak<Graphs>) {
+        let failure = self.clone();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if let Some(graph) = graph.upgrade() {
+                (failure.report)(&graph.snapshot());
+            }
+            previous(info);
+        }));
+    }
+}
+
+impl fmt::Debug for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Failure")
+            .field("errors", &self.errors)
+            .field("seen", &self.seen)
+            .finish_non_exhaustive()
+    }
+}
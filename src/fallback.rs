@@ -0,0 +1,36 @@
+use crate::AtomicFile;
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// Where [`FlushGuard`]s write the graph when their writer fails, see
+/// [`Builder::fallback`].
+///
+/// [`FlushGuard`]: crate::FlushGuard
+/// [`Builder::fallback`]: crate::Builder::fallback
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Fallback {
+    /// Write to stderr.
+    Stderr,
+    /// Replace the file at the path, see [`AtomicFile`].
+    File(PathBuf),
+}
+
+impl Fallback {
+    pub(crate) fn write(&self, output: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Stderr => {
+                let mut stderr = io::stderr().lock();
+                stderr.write_all(output)?;
+                stderr.flush()
+            }
+            Self::File(path) => {
+                let mut file = AtomicFile::create(path)?;
+                file.write_all(output)?;
+                file.flush()
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+use tracing::field::{Field, Visit};
+
+/// The fields a span was created with, handed to the predicate of
+/// [`Builder::record_when`].
+///
+/// Strings are kept as is, and every other value in its `Debug`
+/// representation, so `true`, `42` and `"canary"` read as `"true"`, `"42"`
+/// and `"canary"`.
+///
+/// [`Builder::record_when`]: crate::Builder::record_when
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpanFields {
+    values: Vec<(&'static str, String)>,
+}
+
+impl SpanFields {
+    /// Returns the value of the field called `name`, if the span has it.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the name and value of every field, in the order they were
+    /// recorded.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        self.values
+            .iter()
+            .map(|(field, value)| (*field, value.as_str()))
+    }
+}
+
+impl Visit for SpanFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.values.push((field.name(), value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.values.push((field.name(), format!("{:?}", value)));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{NodeKey, SpanFields};
+use std::{fmt, sync::Arc};
+use tracing::Metadata;
+
+/// A predicate choosing the spans a layer records, see [`Builder::filter`].
+///
+/// [`Builder::filter`]: crate::Builder::filter
+#[derive(Clone)]
+pub(crate) struct Filter(Arc<dyn Fn(&Metadata<'_>) -> bool + Send + Sync>);
+
+impl Filter {
+    pub(crate) fn new<F>(filter: F) -> Self
+    where
+        F: Fn(&Metadata<'_>) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(filter))
+    }
+
+    pub(crate) fn matches(&self, metadata: &Metadata<'_>) -> bool {
+        (self.0)(metadata)
+    }
+}
+
+impl fmt::Debug for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Filter").finish()
+    }
+}
+
+/// A predicate choosing the top node of spans and events without a recorded
+/// parent, see [`Builder::top_nodes`].
+///
+/// [`Builder::top_nodes`]: crate::Builder::top_nodes
+#[derive(Clone)]
+pub(crate) struct TopNodes(Arc<SelectTopNode>);
+
+type SelectTopNode = dyn Fn(&Metadata<'_>) -> Option<&'static str> + Send + Sync;
+
+impl TopNodes {
+    pub(crate) fn new<F>(select: F) -> Self
+    where
+        F: Fn(&Metadata<'_>) -> Option<&'static str> + Send + Sync + 'static,
+    {
+        Self(Arc::new(select))
+    }
+
+    pub(crate) fn select(&self, metadata: &Metadata<'_>) -> Option<&'static str> {
+        (self.0)(metadata)
+    }
+}
+
+impl fmt::Debug for TopNodes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TopNodes").finish()
+    }
+}
+
+/// A function choosing the node of spans from a callsite, see
+/// [`Builder::node_key`].
+///
+/// [`Builder::node_key`]: crate::Builder::node_key
+#[derive(Clone)]
+pub(crate) struct NodeKeys(Arc<SelectNodeKey>);
+
+type SelectNodeKey = dyn Fn(&Metadata<'_>) -> NodeKey + Send + Sync;
+
+impl NodeKeys {
+    pub(crate) fn new<F>(select: F) -> Self
+    where
+        F: Fn(&Metadata<'_>) -> NodeKey + Send + Sync + 'static,
+    {
+        Self(Arc::new(select))
+    }
+
+    pub(crate) fn key(&self, metadata: &Metadata<'_>) -> NodeKey {
+        (self.0)(metadata)
+    }
+}
+
+impl fmt::Debug for NodeKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("NodeKeys").finish()
+    }
+}
+
+/// A predicate over the fields of spans, gating whether they and the spans
+/// in them are recorded, see [`Builder::record_when`].
+///
+/// [`Builder::record_when`]: crate::Builder::record_when
+#[derive(Clone)]
+pub(crate) struct FieldGate(Arc<dyn Fn(&SpanFields) -> bool + Send + Sync>);
+
+impl FieldGate {
+    pub(crate) fn new<F>(predicate: F) -> Self
+    where
+        F: Fn(&SpanFields) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(predicate))
+    }
+
+    pub(crate) fn accepts(&self, fields: &SpanFields) -> bool {
+        (self.0)(fields)
+    }
+}
+
+impl fmt::Debug for FieldGate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FieldGate").finish()
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{
+    graph::NodeId,
+    hash::{BuildHasher, HashMap},
+    sync::RwLock,
+    weight::accumulate,
+};
+use std::{
+    convert::TryFrom,
+    io::{self, Write},
+    time::Duration,
+};
+
+/// The time spent in every stack of spans, for output in the folded format
+/// of `tracing-flame` and `inferno`.
+#[derive(Debug)]
+pub(crate) struct FoldedStacks {
+    stacks: RwLock<HashMap<Vec<NodeId>, u64>>,
+}
+
+impl FoldedStacks {
+    pub(crate) fn new(hasher: BuildHasher) -> Self {
+        Self {
+            stacks: RwLock::new(HashMap::with_hasher(hasher)),
+        }
+    }
+
+    /// Adds `busy` to the time spent in `stack`, which starts at the root.
+    pub(crate) fn record(&self, stack: Vec<NodeId>, busy: Duration) {
+        let nanos = u64::try_from(busy.as_nanos()).unwrap_or(u64::MAX);
+        accumulate(self.stacks.write().entry(stack).or_default(), nanos);
+    }
+
+    /// Writes one `root;...;span nanoseconds` line per stack, sorted by
+    /// stack, naming nodes by their index into `names`.
+    pub(crate) fn write<W>(&self, mut writer: W, names: &[String]) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mut lines: Vec<_> = self
+            .stacks
+            .read()
+            .iter()
+            .map(|(stack, nanos)| {
+                let stack: Vec<_> = stack.iter().map(|id| names[id.index()].as_str()).collect();
+                (stack.join(";"), *nanos)
+            })
+            .collect();
+        lines.sort_unstable();
+        for (stack, nanos) in lines {
+            writeln!(writer, "{} {}", stack, nanos)?;
+        }
+        Ok(())
+    }
+}
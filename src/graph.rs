@@ -0,0 +1,365 @@
+use crate::{
+    hash::{BuildHasher, HashMap},
+    weight::AtomicWeight,
+};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// A small integer identifying a node, handed out by the [`Interner`].
+///
+/// [`Interner`]: crate::interner::Interner
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct NodeId(pub(crate) u32);
+
+impl NodeId {
+    pub(crate) fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Atomic counters recorded for a node or an edge.
+///
+/// Every counter is atomic, so known nodes and edges can be recorded with
+/// shared access to the graph and never block each other, and saturates
+/// rather than wrapping around.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    count: AtomicWeight<u64>,
+    duration: AtomicWeight<Duration>,
+    /// The lowest position of the edge among the edges first followed within
+    /// a root span, counting from `1`, or `0` if it wasn't recorded.
+    order: AtomicU64,
+    errors: AtomicWeight<u64>,
+    sla_violations: AtomicWeight<u64>,
+}
+
+impl Counters {
+    /// Returns counters holding the given weights.
+    pub(crate) fn new(
+        count: u64,
+        duration: Duration,
+        errors: u64,
+        sla_violations: u64,
+        order: Option<usize>,
+    ) -> Self {
+        let counters = Self::default();
+        counters.count.add(count);
+        counters.duration.add(duration);
+        counters.errors.add(errors);
+        counters.sla_violations.add(sla_violations);
+        if let Some(order) = order {
+            counters.set_order(order as u64);
+        }
+        counters
+    }
+
+    /// The number of times the node was entered, or the edge followed.
+    pub(crate) fn count(&self) -> u64 {
+        self.count.get()
+    }
+
+    /// The total time spent inside the node, or the callee of the edge.
+    pub(crate) fn duration(&self) -> Duration {
+        self.duration.get()
+    }
+
+    /// The number of errors reported by the callee of the edge, see
+    /// [`CallGraph::record_error`].
+    pub(crate) fn errors(&self) -> u64 {
+        self.errors.get()
+    }
+
+    /// The number of times the node, or the callee of the edge, ran for
+    /// longer than its threshold, see [`CallGraph::record_sla_violation`].
+    pub(crate) fn sla_violations(&self) -> u64 {
+        self.sla_violations.get()
+    }
+
+    /// The position of the edge, see [`CallGraph::record_order`].
+    pub(crate) fn order(&self) -> Option<usize> {
+        match self.order.load(Ordering::Relaxed) {
+            0 => None,
+            order => Some(order as usize),
+        }
+    }
+
+    fn set_order(&self, order: u64) {
+        let _ =
+            self.order.fetch_update(
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+                |current| match current {
+                    0 => Some(order),
+                    current if order < current => Some(order),
+                    _ => None,
+                },
+            );
+    }
+
+    fn enter(&self) {
+        self.count.add(1);
+    }
+
+    fn exit(&self, elapsed: Duration) {
+        self.duration.add(elapsed);
+    }
+
+    fn add(&self, other: &Counters) {
+        self.count.add(other.count());
+        self.duration.add(other.duration());
+        self.errors.add(other.errors());
+        self.sla_violations.add(other.sla_violations());
+        match other.order.load(Ordering::Relaxed) {
+            0 => {}
+            order => self.set_order(order),
+        }
+    }
+}
+
+/// The call graph recorded by a [`GraphLayer`].
+///
+/// Nodes are indexed by their [`NodeId`], so only edges need to be hashed
+/// while recording.
+///
+/// [`GraphLayer`]: crate::GraphLayer
+#[derive(Debug, Default)]
+pub(crate) struct CallGraph {
+    nodes: Vec<Option<Counters>>,
+    edges: HashMap<(NodeId, NodeId), Counters>,
+}
+
+impl CallGraph {
+    pub(crate) fn new(hasher: BuildHasher) -> Self {
+        Self {
+            nodes: Vec::new(),
+            edges: HashMap::with_hasher(hasher),
+        }
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &Counters {
+        if self.nodes.len() <= id.index() {
+            self.nodes.resize_with(id.index() + 1, || None);
+        }
+        self.nodes[id.index()].get_or_insert_with(Default::default)
+    }
+
+    pub(crate) fn add_node(&mut self, id: NodeId) {
+        self.node_mut(id);
+    }
+
+    /// Removes a node along with all of its edges.
+    pub(crate) fn remove_node(&mut self, id: NodeId) {
+        if let Some(node) = self.nodes.get_mut(id.index()) {
+            *node = None;
+        }
+        self.edges
+            .retain(|(caller, callee), _| *caller != id && *callee != id);
+    }
+
+    /// Returns the counters of `callee` and of the edge from `caller`, if
+    /// both were recorded before.
+    fn counters(
+        &self,
+        caller: Option<NodeId>,
+        callee: NodeId,
+    ) -> Option<(&Counters, Option<&Counters>)> {
+        let node = self.nodes.get(callee.index())?.as_ref()?;
+        let edge = match caller {
+            Some(caller) => Some(self.edges.get(&(caller, callee))?),
+            None => None,
+        };
+        Some((node, edge))
+    }
+
+    /// Returns the counters of `callee` and of the edge from `caller`,
+    /// adding them if they are missing.
+    fn counters_mut(
+        &mut self,
+        caller: Option<NodeId>,
+        callee: NodeId,
+    ) -> (&Counters, Option<&Counters>) {
+        if let Some(caller) = caller {
+            self.node_mut(caller);
+            self.edges.entry((caller, callee)).or_default();
+        }
+        self.node_mut(callee);
+        self.counters(caller, callee)
+            .expect("expected: counters were just added")
+    }
+
+    /// Records `callee` being entered, from `caller` if it is known.
+    pub(crate) fn record_enter(&mut self, caller: Option<NodeId>, callee: NodeId) {
+        let (node, edge) = self.counters_mut(caller, callee);
+        node.enter();
+        if let Some(edge) = edge {
+            edge.enter();
+        }
+    }
+
+    /// Records `callee` being exited after running for `elapsed`.
+    ///
+    /// The span may have been entered on another thread, so this adds any
+    /// missing nodes and edges rather than relying on `record_enter`.
+    pub(crate) fn record_exit(
+        &mut self,
+        caller: Option<NodeId>,
+        callee: NodeId,
+        elapsed: Duration,
+    ) {
+        let (node, edge) = self.counters_mut(caller, callee);
+        node.exit(elapsed);
+        if let Some(edge) = edge {
+            edge.exit(elapsed);
+        }
+    }
+
+    /// Records `callee` being entered like [`record_enter`], but only if its
+    /// node and edge already exist. Returns `false` if nothing was recorded.
+    ///
+    /// [`record_enter`]: CallGraph::record_enter
+    pub(crate) fn try_record_enter(&self, caller: Option<NodeId>, callee: NodeId) -> bool {
+        match self.counters(caller, callee) {
+            Some((node, edge)) => {
+                node.enter();
+                if let Some(edge) = edge {
+                    edge.enter();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records `callee` being exited like [`record_exit`], but only if its
+    /// node and edge already exist. Returns `false` if nothing was recorded.
+    ///
+    /// [`record_exit`]: CallGraph::record_exit
+    pub(crate) fn try_record_exit(
+        &self,
+        caller: Option<NodeId>,
+        callee: NodeId,
+        elapsed: Duration,
+    ) -> bool {
+        match self.counters(caller, callee) {
+            Some((node, edge)) => {
+                node.exit(elapsed);
+                if let Some(edge) = edge {
+                    edge.exit(elapsed);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records that the edge from `caller` to `callee` was the `order`th
+    /// edge, counting from `1`, first followed within a root span, keeping
+    /// the lowest position recorded.
+    pub(crate) fn record_order(&mut self, caller: NodeId, callee: NodeId, order: usize) {
+        if let (_, Some(edge)) = self.counters_mut(Some(caller), callee) {
+            edge.set_order(order as u64);
+        }
+    }
+
+    /// Records the position of an edge like [`record_order`], but only if
+    /// it already exists. Returns `false` if nothing was recorded.
+    ///
+    /// [`record_order`]: CallGraph::record_order
+    pub(crate) fn try_record_order(&self, caller: NodeId, callee: NodeId, order: usize) -> bool {
+        match self.edges.get(&(caller, callee)) {
+            Some(edge) => {
+                edge.set_order(order as u64);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records an error reported by `callee` while called from `caller`.
+    pub(crate) fn record_error(&mut self, caller: NodeId, callee: NodeId) {
+        if let (_, Some(edge)) = self.counters_mut(Some(caller), callee) {
+            edge.errors.add(1);
+        }
+    }
+
+    /// Records an error like [`record_error`], but only if the edge already
+    /// exists. Returns `false` if nothing was recorded.
+    ///
+    /// [`record_error`]: CallGraph::record_error
+    pub(crate) fn try_record_error(&self, caller: NodeId, callee: NodeId) -> bool {
+        match self.edges.get(&(caller, callee)) {
+            Some(edge) => {
+                edge.errors.add(1);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records `callee` running for longer than its threshold, while called
+    /// from `caller` if it is known.
+    pub(crate) fn record_sla_violation(&mut self, caller: Option<NodeId>, callee: NodeId) {
+        let (node, edge) = self.counters_mut(caller, callee);
+        node.sla_violations.add(1);
+        if let Some(edge) = edge {
+            edge.sla_violations.add(1);
+        }
+    }
+
+    /// Records a threshold being exceeded like [`record_sla_violation`], but
+    /// only if its node and edge already exist. Returns `false` if nothing
+    /// was recorded.
+    ///
+    /// [`record_sla_violation`]: CallGraph::record_sla_violation
+    pub(crate) fn try_record_sla_violation(&self, caller: Option<NodeId>, callee: NodeId) -> bool {
+        match self.counters(caller, callee) {
+            Some((node, edge)) => {
+                node.sla_violations.add(1);
+                if let Some(edge) = edge {
+                    edge.sla_violations.add(1);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds `counters` to the node of `callee`, or to the edge from `caller`
+    /// if it is known, adding any missing nodes.
+    pub(crate) fn add_counters(
+        &mut self,
+        caller: Option<NodeId>,
+        callee: NodeId,
+        counters: &Counters,
+    ) {
+        match self.counters_mut(caller, callee) {
+            (_, Some(edge)) => edge.add(counters),
+            (node, None) => node.add(counters),
+        }
+    }
+
+    /// Adds the nodes and edges of `other`, summing common statistics.
+    pub(crate) fn merge(&mut self, other: &CallGraph) {
+        for (id, counters) in other.nodes() {
+            self.node_mut(id).add(counters);
+        }
+        for (key, counters) in &other.edges {
+            self.edges.entry(*key).or_default().add(counters);
+        }
+    }
+
+    pub(crate) fn nodes(&self) -> impl Iterator<Item = (NodeId, &Counters)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, stats)| Some((NodeId(index as u32), stats.as_ref()?)))
+    }
+
+    pub(crate) fn edges(&self) -> impl Iterator<Item = (NodeId, NodeId, &Counters)> {
+        self.edges
+            .iter()
+            .map(|((caller, callee), stats)| (*caller, *callee, stats))
+    }
+}
@@ -0,0 +1,37 @@
+//! Gzip compression of output files ending in `.gz`, with the `flate2`
+//! feature. Without it, such files are written and read uncompressed.
+
+use std::{fs, io, path::Path};
+
+/// Returns whether the file at `path` is compressed.
+pub(crate) fn is_gzip(path: &Path) -> bool {
+    cfg!(feature = "flate2") && path.extension().is_some_and(|extension| extension == "gz")
+}
+
+#[cfg(feature = "flate2")]
+pub(crate) fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+#[cfg(not(feature = "flate2"))]
+pub(crate) fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    Ok(data.to_vec())
+}
+
+/// Reads the file at `path`, decompressing it if [`is_gzip`].
+pub(crate) fn read_to_string(path: &Path) -> io::Result<String> {
+    #[cfg(feature = "flate2")]
+    if is_gzip(path) {
+        use std::io::Read;
+
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(fs::File::open(path)?).read_to_string(&mut text)?;
+        return Ok(text);
+    }
+    fs::read_to_string(path)
+}
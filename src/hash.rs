@@ -0,0 +1,109 @@
+use std::{
+    collections::hash_map::{DefaultHasher, RandomState},
+    convert::TryInto,
+    hash,
+};
+
+/// The hash function of the maps a [`GraphLayer`] records into.
+///
+/// [`GraphLayer`]: crate::GraphLayer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HashFunction {
+    /// The standard library's randomly keyed SipHash, which resists
+    /// collision attacks.
+    #[default]
+    Sip,
+    /// The much faster FxHash used by `rustc`. Nodes are keyed by small
+    /// integer ids, so there is little to gain from a stronger function.
+    Fx,
+}
+
+/// A `HashMap` using the configured [`HashFunction`].
+pub(crate) type HashMap<K, V> = std::collections::HashMap<K, V, BuildHasher>;
+
+/// Builds hashers for the configured [`HashFunction`].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BuildHasher {
+    function: HashFunction,
+    sip: RandomState,
+}
+
+impl BuildHasher {
+    pub(crate) fn new(function: HashFunction) -> Self {
+        Self {
+            function,
+            sip: RandomState::new(),
+        }
+    }
+}
+
+impl hash::BuildHasher for BuildHasher {
+    type Hasher = Hasher;
+
+    fn build_hasher(&self) -> Hasher {
+        match self.function {
+            HashFunction::Sip => Hasher::Sip(self.sip.build_hasher()),
+            HashFunction::Fx => Hasher::Fx(0),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum Hasher {
+    Sip(DefaultHasher),
+    Fx(u64),
+}
+
+impl Hasher {
+    fn add_to_fx(hash: &mut u64, word: u64) {
+        const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+        *hash = (hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+}
+
+impl hash::Hasher for Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Sip(hasher) => hasher.write(bytes),
+            Self::Fx(hash) => {
+                let mut chunks = bytes.chunks_exact(8);
+                for chunk in &mut chunks {
+                    let word = u64::from_le_bytes(chunk.try_into().unwrap());
+                    Self::add_to_fx(hash, word);
+                }
+                for &byte in chunks.remainder() {
+                    Self::add_to_fx(hash, byte.into());
+                }
+            }
+        }
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        match self {
+            Self::Sip(hasher) => hasher.write_u32(n),
+            Self::Fx(hash) => Self::add_to_fx(hash, n.into()),
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        match self {
+            Self::Sip(hasher) => hasher.write_u64(n),
+            Self::Fx(hash) => Self::add_to_fx(hash, n),
+        }
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        match self {
+            Self::Sip(hasher) => hasher.write_usize(n),
+            Self::Fx(hash) => Self::add_to_fx(hash, n as u64),
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        match self {
+            Self::Sip(hasher) => hasher.finish(),
+            Self::Fx(hash) => *hash,
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use crate::{
+    filter::NodeKeys,
+    graph::NodeId,
+    hash::{BuildHasher, HashMap},
+    simplify::{simplify, Simplification},
+    sync::RwLock,
+    CallsiteInfo, OTHER_NODE, SERVICE_SEPARATOR,
+};
+use std::borrow::Cow;
+use tracing::{callsite, Metadata};
+
+/// How nodes are told apart when spans from different callsites share a
+/// name, see [`Builder::disambiguation`].
+///
+/// [`Builder::disambiguation`]: crate::Builder::disambiguation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Disambiguation {
+    /// Record all spans with the same name as one node.
+    Merge,
+    /// Suffix the names with the module path of their callsite, such as
+    /// `run (app::worker)`. Callsites in the same module are still merged.
+    #[default]
+    ModulePath,
+    /// Suffix the names with the file and line of their callsite, such as
+    /// `run (src/worker.rs:12)`.
+    FileLine,
+}
+
+impl Disambiguation {
+    fn suffix(self, metadata: &Metadata<'_>) -> Option<String> {
+        match self {
+            Self::Merge => None,
+            Self::ModulePath => metadata.module_path().map(str::to_string),
+            Self::FileLine => Some(format!("{}:{}", metadata.file()?, metadata.line()?)),
+        }
+    }
+}
+
+/// The node spans from a callsite are recorded as, see
+/// [`Builder::node_key`].
+///
+/// [`Builder::node_key`]: crate::Builder::node_key
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NodeKey {
+    /// The node named after the spans, as usual.
+    Default,
+    /// The node with the given name, shared by every callsite with the same
+    /// key. The name is tagged with the service, if any, but not simplified
+    /// or disambiguated.
+    Name(Cow<'static, str>),
+}
+
+impl From<&'static str> for NodeKey {
+    fn from(name: &'static str) -> Self {
+        Self::Name(Cow::Borrowed(name))
+    }
+}
+
+impl From<String> for NodeKey {
+    fn from(name: String) -> Self {
+        Self::Name(Cow::Owned(name))
+    }
+}
+
+/// Hands out a [`NodeId`] for every distinct node name.
+///
+/// Names are resolved once per callsite, after which spans from the callsite
+/// only need a lookup by its identifier.
+#[derive(Debug, Default)]
+pub(crate) struct Interner {
+    inner: RwLock<Inner>,
+    node_keys: Option<NodeKeys>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    names: Vec<Cow<'static, str>>,
+    /// The metadata of the first callsite of every node, indexed by its id.
+    metadata: Vec<Option<&'static Metadata<'static>>>,
+    /// The span callsites registered with the layer, and their nodes.
+    registered: Vec<(&'static Metadata<'static>, NodeId)>,
+    ids: HashMap<Cow<'static, str>, NodeId>,
+    callsites: HashMap<callsite::Identifier, NodeId>,
+    max_nodes: Option<usize>,
+    service: Option<String>,
+    disambiguation: Disambiguation,
+    simplification: Simplification,
+    /// The simplified name of every span name, once numbered as configured
+    /// by [`Simplification::Strip`].
+    simplified: HashMap<&'static str, Cow<'static, str>>,
+    /// The number of span names simplified to each name.
+    variants: HashMap<Cow<'static, str>, usize>,
+    /// The first callsite of every span name, and whether its node was
+    /// renamed for another callsite with the same name.
+    first_callsites: HashMap<&'static str, (&'static Metadata<'static>, bool)>,
+}
+
+impl Inner {
+    /// Returns the node name of spans called `name`, simplified and tagged
+    /// with the service.
+    fn span_name(&mut self, name: &'static str) -> Cow<'static, str> {
+        let name = self.simplified(name);
+        match &self.service {
+            Some(service) => Cow::Owned(format!("{}{}{}", service, SERVICE_SEPARATOR, name)),
+            None => name,
+        }
+    }
+
+    /// Returns `name` simplified as configured.
+    fn simplified(&mut self, name: &'static str) -> Cow<'static, str> {
+        match self.simplification {
+            Simplification::Keep => Cow::Borrowed(name),
+            Simplification::Merge => simplify(name),
+            Simplification::Strip => {
+                if let Some(simplified) = self.simplified.get(name) {
+                    return simplified.clone();
+                }
+                let simplified = simplify(name);
+                let variants = self.variants.entry(simplified.clone()).or_insert(0);
+                *variants += 1;
+                let simplified = match *variants {
+                    1 => simplified,
+                    variant => Cow::Owned(format!("{} #{}", simplified, variant)),
+                };
+                self.simplified.insert(name, simplified.clone());
+                simplified
+            }
+        }
+    }
+
+    /// Returns the node name of spans from the callsite of `metadata`, as
+    /// chosen by its `key`.
+    fn keyed_name(
+        &mut self,
+        metadata: &'static Metadata<'static>,
+        key: NodeKey,
+    ) -> Cow<'static, str> {
+        match (key, &self.service) {
+            (NodeKey::Default, _) => self.callsite_name(metadata),
+            (NodeKey::Name(name), Some(service)) => {
+                Cow::Owned(format!("{}{}{}", service, SERVICE_SEPARATOR, name))
+            }
+            (NodeKey::Name(name), None) => name,
+        }
+    }
+
+    /// Returns the node name of spans from the callsite of `metadata`.
+    ///
+    /// Once a second callsite shares the name of the first, the nodes of
+    /// both are suffixed as configured, renaming the node of the first.
+    fn callsite_name(&mut self, metadata: &'static Metadata<'static>) -> Cow<'static, str> {
+        let name = metadata.name();
+        let (first, renamed) = match self.first_callsites.get(name) {
+            Some(&entry) => entry,
+            None => {
+                self.first_callsites.insert(name, (metadata, false));
+                return self.span_name(name);
+            }
+        };
+        if first.callsite() == metadata.callsite() && !renamed {
+            return self.span_name(name);
+        }
+        if !renamed {
+            self.first_callsites.insert(name, (first, true));
+            let old = self.span_name(name);
+            let new = self.suffixed_name(first);
+            if let Some(id) = self.ids.remove(&old) {
+                self.names[id.index()] = new.clone();
+                self.ids.insert(new, id);
+            }
+        }
+        self.suffixed_name(metadata)
+    }
+
+    /// Returns the node name of spans from the callsite of `metadata`,
+    /// suffixed as configured.
+    fn suffixed_name(&mut self, metadata: &'static Metadata<'static>) -> Cow<'static, str> {
+        let name = self.span_name(metadata.name());
+        match self.disambiguation.suffix(metadata) {
+            Some(suffix) => Cow::Owned(format!("{} ({})", name, suffix)),
+            None => name,
+        }
+    }
+
+    /// Returns the callsite of `metadata`, with its name simplified as
+    /// configured.
+    fn callsite_info(&self, metadata: &'static Metadata<'static>) -> CallsiteInfo {
+        let name = match self.simplification {
+            Simplification::Keep => Cow::Borrowed(metadata.name()),
+            Simplification::Strip | Simplification::Merge => simplify(metadata.name()),
+        };
+        CallsiteInfo {
+            target: metadata.target().to_string(),
+            name: name.into_owned(),
+            file: metadata.file().map(str::to_string),
+            line: metadata.line(),
+        }
+    }
+
+    fn intern(&mut self, name: Cow<'static, str>) -> NodeId {
+        if let Some(id) = self.ids.get(&name) {
+            return *id;
+        }
+        match self.max_nodes {
+            Some(max_nodes) if name != OTHER_NODE => {
+                // The `OTHER_NODE` bucket itself doesn't count towards the limit.
+                let other = self.ids.contains_key(OTHER_NODE) as usize;
+                if self.names.len() - other >= max_nodes {
+                    return self.intern(Cow::Borrowed(OTHER_NODE));
+                }
+            }
+            _ => {}
+        }
+        let id = NodeId(self.names.len() as u32);
+        self.names.push(name.clone());
+        self.metadata.push(None);
+        self.ids.insert(name, id);
+        id
+    }
+}
+
+impl Interner {
+    /// Returns an interner handing out at most `max_nodes` distinct ids, and
+    /// the id of [`OTHER_NODE`] for any further names. Span names are tagged
+    /// with `service`, if any, after being simplified as configured by
+    /// `simplification`, and duplicates are told apart as configured by
+    /// `disambiguation`, unless `node_keys` chooses their node.
+    pub(crate) fn new(
+        max_nodes: Option<usize>,
+        hasher: BuildHasher,
+        service: Option<String>,
+        disambiguation: Disambiguation,
+        simplification: Simplification,
+        node_keys: Option<NodeKeys>,
+    ) -> Self {
+        Self {
+            inner: RwLock::new(Inner {
+                names: Vec::new(),
+                metadata: Vec::new(),
+                registered: Vec::new(),
+                ids: HashMap::with_hasher(hasher.clone()),
+                callsites: HashMap::with_hasher(hasher.clone()),
+                max_nodes,
+                service,
+                disambiguation,
+                simplification,
+                simplified: HashMap::with_hasher(hasher.clone()),
+                variants: HashMap::with_hasher(hasher.clone()),
+                first_callsites: HashMap::with_hasher(hasher),
+            }),
+            node_keys,
+        }
+    }
+
+    /// Returns the id of the node called `name`.
+    pub(crate) fn intern(&self, name: impl Into<Cow<'static, str>>) -> NodeId {
+        let name = name.into();
+        if let Some(id) = self.inner.read().ids.get(&name) {
+            return *id;
+        }
+        self.inner.write().intern(name)
+    }
+
+    /// Returns the id of the node for spans called `name` in this service.
+    pub(crate) fn span(&self, name: &'static str) -> NodeId {
+        let name = self.inner.write().span_name(name);
+        self.intern(name)
+    }
+
+    /// Returns the id of the node for spans from the callsite of `metadata`.
+    pub(crate) fn callsite(&self, metadata: &'static Metadata<'static>) -> NodeId {
+        let callsite = metadata.callsite();
+        if let Some(id) = self.inner.read().callsites.get(&callsite) {
+            return *id;
+        }
+        // Chosen outside of the lock, in case the key function records spans.
+        let key = match &self.node_keys {
+            Some(keys) => keys.key(metadata),
+            None => NodeKey::Default,
+        };
+        let mut inner = self.inner.write();
+        if let Some(id) = inner.callsites.get(&callsite) {
+            return *id;
+        }
+        let name = inner.keyed_name(metadata, key);
+        let id = inner.intern(name);
+        inner.callsites.insert(callsite, id);
+        if inner.names[id.index()] != OTHER_NODE {
+            inner.metadata[id.index()].get_or_insert(metadata);
+        }
+        id
+    }
+
+    /// Returns the id of the node for spans from the callsite of `metadata`,
+    /// and remembers the callsite as registered.
+    pub(crate) fn register(&self, metadata: &'static Metadata<'static>) -> NodeId {
+        let id = self.callsite(metadata);
+        self.inner.write().registered.push((metadata, id));
+        id
+    }
+
+    /// Returns the registered callsites, along with their nodes.
+    pub(crate) fn registered(&self) -> Vec<(CallsiteInfo, NodeId)> {
+        let inner = self.inner.read();
+        inner
+            .registered
+            .iter()
+            .map(|&(metadata, id)| (inner.callsite_info(metadata), id))
+            .collect()
+    }
+
+    /// Returns the name of the node `id`.
+    pub(crate) fn name(&self, id: NodeId) -> Cow<'static, str> {
+        self.inner.read().names[id.index()].clone()
+    }
+
+    /// Returns the names of all nodes, indexed by their id.
+    pub(crate) fn names(&self) -> Vec<String> {
+        let inner = self.inner.read();
+        inner.names.iter().map(|name| name.to_string()).collect()
+    }
+
+    /// Returns the first callsite of all nodes, indexed by their id.
+    pub(crate) fn callsites(&self) -> Vec<Option<CallsiteInfo>> {
+        let inner = self.inner.read();
+        inner
+            .metadata
+            .iter()
+            .map(|metadata| Some(inner.callsite_info((*metadata)?)))
+            .collect()
+    }
+}
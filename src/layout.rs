@@ -0,0 +1,155 @@
+use crate::CallGraphSnapshot;
+
+pub(crate) const NODE_HEIGHT: f64 = 30.0;
+const CHAR_WIDTH: f64 = 7.5;
+const NODE_PADDING: f64 = 20.0;
+const NODE_GAP: f64 = 30.0;
+pub(crate) const LAYER_GAP: f64 = 70.0;
+pub(crate) const MARGIN: f64 = 20.0;
+const ORDERING_SWEEPS: usize = 4;
+
+/// A node placed by the layered layout.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Placed {
+    pub(crate) layer: usize,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) width: f64,
+}
+
+impl CallGraphSnapshot {
+    /// Places the nodes in layers, so that every call not closing a cycle
+    /// points to a lower layer, ordering each layer to reduce crossings.
+    pub(crate) fn layout(&self) -> Vec<Placed> {
+        let len = self.nodes().len();
+        let forward = self.forward_edges();
+
+        // Longest path layering, visiting nodes in topological order.
+        let mut layers = vec![0; len];
+        let mut incoming = vec![0; len];
+        for &(_, callee) in &forward {
+            incoming[callee] += 1;
+        }
+        let mut ready: Vec<_> = (0..len).filter(|&node| incoming[node] == 0).collect();
+        while let Some(node) = ready.pop() {
+            for &(caller, callee) in &forward {
+                if caller == node {
+                    layers[callee] = layers[callee].max(layers[node] + 1);
+                    incoming[callee] -= 1;
+                    if incoming[callee] == 0 {
+                        ready.push(callee);
+                    }
+                }
+            }
+        }
+
+        let depth = layers.iter().max().map_or(0, |max| max + 1);
+        let mut rows: Vec<Vec<usize>> = vec![Vec::new(); depth];
+        for node in 0..len {
+            rows[layers[node]].push(node);
+        }
+
+        // Barycenter ordering, sweeping down and then up.
+        let mut position = vec![0.0; len];
+        let reposition = |rows: &[Vec<usize>], position: &mut Vec<f64>| {
+            for row in rows {
+                for (index, &node) in row.iter().enumerate() {
+                    position[node] = index as f64;
+                }
+            }
+        };
+        reposition(&rows, &mut position);
+        for sweep in 0..ORDERING_SWEEPS {
+            let down = sweep % 2 == 0;
+            let order: Vec<usize> = if down {
+                (1..depth).collect()
+            } else {
+                (0..depth.saturating_sub(1)).rev().collect()
+            };
+            for layer in order {
+                let barycenter = |node: usize| {
+                    let neighbours: Vec<f64> = forward
+                        .iter()
+                        .filter_map(|&(caller, callee)| match down {
+                            true if callee == node => Some(position[caller]),
+                            false if caller == node => Some(position[callee]),
+                            _ => None,
+                        })
+                        .collect();
+                    if neighbours.is_empty() {
+                        position[node]
+                    } else {
+                        neighbours.iter().sum::<f64>() / neighbours.len() as f64
+                    }
+                };
+                let mut keyed: Vec<_> = rows[layer]
+                    .iter()
+                    .map(|&node| (barycenter(node), node))
+                    .collect();
+                keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+                rows[layer] = keyed.into_iter().map(|(_, node)| node).collect();
+                reposition(&rows, &mut position);
+            }
+        }
+
+        let width = |node: usize| {
+            self.nodes()[node].name.chars().count() as f64 * CHAR_WIDTH + NODE_PADDING
+        };
+        let row_width = |row: &[usize]| {
+            row.iter().map(|&node| width(node)).sum::<f64>()
+                + NODE_GAP * row.len().saturating_sub(1) as f64
+        };
+        let widest = rows.iter().map(|row| row_width(row)).fold(0.0, f64::max);
+
+        let mut placed = vec![Placed::default(); len];
+        for (layer, row) in rows.iter().enumerate() {
+            let mut x = MARGIN + (widest - row_width(row)) / 2.0;
+            for &node in row {
+                let width = width(node);
+                placed[node] = Placed {
+                    layer,
+                    x: x + width / 2.0,
+                    y: MARGIN + NODE_HEIGHT / 2.0 + layer as f64 * (NODE_HEIGHT + LAYER_GAP),
+                    width,
+                };
+                x += width + NODE_GAP;
+            }
+        }
+        placed
+    }
+
+    /// Returns the `(caller, callee)` pairs of all edges that don't close a
+    /// cycle, found by a depth first search from every node in order.
+    fn forward_edges(&self) -> Vec<(usize, usize)> {
+        let callees = self.callee_lists();
+        let len = self.nodes().len();
+        // 0: unvisited, 1: on the stack, 2: done.
+        let mut state = vec![0u8; len];
+        let mut forward = Vec::new();
+        for root in 0..len {
+            if state[root] != 0 {
+                continue;
+            }
+            state[root] = 1;
+            let mut stack = vec![(root, 0)];
+            while let Some((node, next)) = stack.pop() {
+                match callees[node].get(next) {
+                    Some(&(callee, _)) => {
+                        stack.push((node, next + 1));
+                        match state[callee] {
+                            0 => {
+                                forward.push((node, callee));
+                                state[callee] = 1;
+                                stack.push((callee, 0));
+                            }
+                            2 => forward.push((node, callee)),
+                            _ => {}
+                        }
+                    }
+                    None => state[node] = 2,
+                }
+            }
+        }
+        forward
+    }
+}
@@ -48,7 +48,6 @@
     rust_2018_idioms,
     unreachable_pub,
     bad_style,
-    const_err,
     dead_code,
     improper_ctypes,
     non_shorthand_field_patterns,
@@ -56,7 +55,6 @@
     overflowing_literals,
     path_statements,
     patterns_in_fns_without_body,
-    private_in_public,
     unconditional_recursion,
     unused,
     unused_allocation,
@@ -65,51 +63,234 @@
     while_true
 )]
 
+pub use aggregation::Aggregation;
+pub use atomic::AtomicFile;
+pub use bench::BenchRecorder;
+pub use builder::Builder;
+pub use centrality::Centrality;
+pub use collector::Collector;
+pub use counting::Counting;
+pub use coverage::Coverage;
+pub use degrees::NodeDegree;
+pub use dot::{DotOptions, LabelOverflow, NodeLabel};
+pub use edge_label::EdgeStats;
 pub use error::Error;
+pub use expectation::{ExpectationFailure, GraphExpectation};
+pub use external::EXTERNAL_FIELDS;
+pub use fallback::Fallback;
+pub use fields::SpanFields;
+pub use hash::HashFunction;
+pub use interner::{Disambiguation, NodeKey};
+pub use matrix::MatrixFormat;
+pub use noise::{Noise, NOISY_TARGETS};
+pub use orphans::Orphan;
+pub use overhead::Overhead;
+pub use paths::CallPath;
+#[cfg(feature = "http")]
+pub use push::HttpPush;
+pub use rotating::{RotatingFile, Rotation};
+pub use rules::{EdgeRules, Violation};
+pub use run::RunInfo;
+pub use simplify::Simplification;
+pub use slices::Slicing;
+pub use snapshot::{CallGraphSnapshot, CallsiteInfo, EdgeChange, EdgeInfo, NodeInfo};
+pub use stats::GraphStats;
+pub use storage::Backend;
+#[doc(hidden)]
+pub use testing::__test_name;
+pub use testing::{with_graph_recording, TestGraph, TEST_OUTPUT_ENV};
+pub use theme::Theme;
+pub use upload::KeyedUpload;
+#[cfg(feature = "wasm")]
+pub use wasm::JsWriter;
 
+use aggregation::{Aggregates, ValueVisitor};
+use delta::DeltaFlushes;
 use error::Kind;
-use petgraph::{dot::Dot, graphmap::GraphMap, Directed};
+use external::ExternalVisitor;
+use failure::Failure;
+use filter::{FieldGate, Filter, TopNodes};
+use folded::FoldedStacks;
+use graph::NodeId;
+use observer::Observer;
+use overhead::OverheadCounters;
+use partition::{KeyVisitor, Partition, PartitionKey};
+use slices::Slices;
 use std::{
-    fs::File,
-    io::{BufWriter, Write},
-    path::Path,
-    sync::{Arc, Mutex},
+    collections::HashSet,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+    thread::{self, ThreadId},
+    time::Duration,
 };
-use tracing::{span, Subscriber};
-use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+use storage::Graphs;
+use stream::EdgeStream;
+use time::Instant;
+use tracing::{span, subscriber::Interest, Event, Metadata, Subscriber};
+use tracing_subscriber::{
+    layer::Context,
+    registry::{LookupSpan, SpanRef},
+    Layer,
+};
+use weight::accumulate;
 
+mod aggregation;
+mod arborescence;
+mod atomic;
+mod bench;
+mod builder;
+mod callgrind;
+mod centrality;
+mod collector;
+mod counting;
+mod coverage;
+mod decay;
+mod degrees;
+mod delta;
+mod dominators;
+mod dot;
+#[cfg(feature = "drawio")]
+mod drawio;
+mod edge_label;
+#[cfg(any(feature = "pprof", feature = "parquet"))]
+mod encoding;
 mod error;
-
-type CallGraph = GraphMap<&'static str, usize, Directed>;
+mod expectation;
+mod external;
+mod failure;
+mod fallback;
+mod fields;
+mod filter;
+mod folded;
+mod graph;
+mod gzip;
+mod hash;
+mod interner;
+#[cfg(any(feature = "svg", feature = "drawio"))]
+mod layout;
+mod logs;
+mod matrix;
+mod noise;
+mod observer;
+mod open;
+mod orphans;
+mod overhead;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod partition;
+mod paths;
+#[cfg(feature = "pprof")]
+mod pprof;
+#[cfg(feature = "http")]
+mod push;
+mod reachability;
+mod reduction;
+mod rotating;
+mod rules;
+mod run;
+mod scc;
+mod services;
+mod simplify;
+mod sla;
+mod slices;
+mod snapshot;
+mod stats;
+mod storage;
+mod stream;
+#[cfg(feature = "svg")]
+mod svg;
+mod sync;
+mod template;
+mod testing;
+mod text;
+mod theme;
+mod time;
+mod timeline;
+#[cfg(feature = "tui")]
+mod tui;
+mod upload;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod weight;
+mod window;
 
 /// A `Layer` that records span open events as directed edges in a call graph.
 ///
+/// By default each thread records into its own graph, so instrumented threads
+/// don't contend on a shared lock. The per-thread graphs are merged whenever a
+/// snapshot is taken or the graph is flushed. See [`Backend`] for alternatives.
+/// With the `parking_lot` feature enabled, the graphs are guarded by the
+/// faster locks of the `parking_lot` crate.
+///
 /// # Dropping and Flushing
 ///
 /// To ensure all data is flushed when the program exits, `GraphLayer` exposes
 /// the [`flush_on_drop`] function, which returns a [`FlushGuard`]. The [`FlushGuard`]
 /// will flush the writer when it is dropped. If necessary, it can also be used to manually
 /// flush the writer.
+///
+/// # Threads
+///
+/// Clones of a span's handle can enter it on several threads at once. Every
+/// exit is matched with the latest enter of the span on the same thread, so
+/// each of them is timed independently.
+///
+/// ```rust
+/// use std::{thread, time::Duration};
+/// use tracing_callgraph::GraphLayer;
+///
+/// let snapshot = GraphLayer::builder().record(|| {
+///     let span = tracing::info_span!("shared");
+///     let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+///     let entered = span.enter();
+///     thread::scope(|scope| {
+///         scope.spawn(|| tracing::dispatcher::with_default(&dispatch, || drop(span.enter())));
+///     });
+///     thread::sleep(Duration::from_millis(10));
+///     drop(entered);
+/// });
+/// assert_eq!(snapshot.nodes()[0].calls, 2);
+/// assert!(snapshot.nodes()[0].duration >= Duration::from_millis(10));
+/// ```
 #[derive(Clone, Debug)]
 pub struct GraphLayer {
-    graph: Arc<Mutex<CallGraph>>,
-    top_node: Option<&'static str>,
+    graph: Arc<Graphs>,
+    top_node: Option<NodeId>,
+    top_nodes: Option<TopNodes>,
+    dot_options: DotOptions,
+    folded_file: Option<PathBuf>,
+    speedscope_file: Option<PathBuf>,
+    partition_file: Option<PathBuf>,
+    external_calls: bool,
+    log_events: bool,
+    counting: Counting,
+    skip_unrecorded_parents: bool,
+    filter: Option<Filter>,
+    gate: Option<FieldGate>,
+    min_duration: Option<Duration>,
+    fallback: Option<Fallback>,
+    slices: Option<Arc<Slices>>,
+    stream: Option<Arc<EdgeStream>>,
+    failure: Option<Arc<Failure>>,
+    observer: Option<Arc<Observer>>,
+    delta_flushes: Option<usize>,
 }
 
 impl GraphLayer {
     /// Add a top node to the graph.
-    #[allow(clippy::clone_double_ref)]
     pub fn enable_top_node(mut self, name: &'static str) -> Self {
         self = self.disable_top_node();
-        self.top_node = Some(name.clone());
-        self.graph.lock().unwrap().add_node(name);
+        let id = self.graph.names.span(name);
+        self.top_node = Some(id);
+        self.graph.with_graph(id, |graph| graph.add_node(id));
         self
     }
 
     /// Remove the top node to the graph.
     pub fn disable_top_node(mut self) -> Self {
-        if let Some(name) = self.top_node.take() {
-            self.graph.lock().unwrap().remove_node(name);
+        if let Some(id) = self.top_node.take() {
+            self.graph.remove_node(id);
         }
         self
     }
@@ -122,7 +303,19 @@ pub struct FlushGuard<W>
 where
     W: Write + 'static,
 {
-    graph: Arc<Mutex<CallGraph>>,
+    graph: Arc<Graphs>,
+    dot_options: DotOptions,
+    folded_file: Option<PathBuf>,
+    speedscope_file: Option<PathBuf>,
+    partition_file: Option<PathBuf>,
+    baseline: Option<CallGraphSnapshot>,
+    fallback: Option<Fallback>,
+    slices: Option<Arc<Slices>>,
+    stream: Option<Arc<EdgeStream>>,
+    delta: Option<DeltaFlushes>,
+    /// Whether the snapshot is written as JSON rather than `dot`.
+    #[cfg(feature = "serde")]
+    json: bool,
     writer: W,
 }
 
@@ -131,23 +324,145 @@ where
     W: Write + 'static,
 {
     /// Flush the internal writer, ensuring that the graph is written.
+    ///
+    /// If the writer fails, the graph is written to the fallback set with
+    /// [`Builder::fallback`], if any, and the error of the writer returned.
     pub fn flush(&mut self) -> Result<(), Error> {
-        let graph = match self.graph.lock() {
-            Ok(graph) => graph,
-            Err(e) => {
-                if !std::thread::panicking() {
-                    panic!("{}", e);
-                } else {
-                    return Ok(());
+        let mut snapshot = self.graph.snapshot();
+        if let Some(baseline) = &self.baseline {
+            snapshot.merge(baseline);
+        }
+        let delta = self.delta.as_ref().and_then(|delta| delta.delta(&snapshot));
+        let output = self.output(delta.as_ref().unwrap_or(&snapshot), delta.is_some());
+        if let Err(error) = self
+            .writer
+            .write_all(&output)
+            .and_then(|()| self.writer.flush())
+        {
+            if let Some(fallback) = &self.fallback {
+                if let Err(error) = fallback.write(&output) {
+                    Error(Kind::FlushFile(error)).report();
                 }
             }
-        };
-        writeln!(self.writer, "{:?}", Dot::new(&*graph))
-            .map_err(Kind::FlushFile)
-            .map_err(Error)?;
+            return Err(Error(Kind::FlushFile(error)));
+        }
+        if let Some(delta) = &mut self.delta {
+            delta.flushed(snapshot);
+        }
 
-        self.writer.flush().map_err(Kind::FlushFile).map_err(Error)
+        if let Some(stream) = &self.stream {
+            stream.flush();
+        }
+        if let Some(path) = &self.folded_file {
+            write_file(path, |writer| self.graph.write_folded(writer))?;
+        }
+        if let Some(path) = &self.speedscope_file {
+            write_file(path, |writer| self.graph.write_speedscope(writer))?;
+        }
+        if let Some(path) = &self.partition_file {
+            for (key, snapshot) in self.graph.partition_snapshots() {
+                let path = rotating::rotated(path, &partition::file_part(&key));
+                write_file(&path, |writer| {
+                    snapshot.write_dot_with(writer, &self.dot_options)
+                })?;
+            }
+        }
+        Ok(())
     }
+
+    /// Renders `snapshot`, a delta since the previous flush if `delta`, as
+    /// it is written to the writer.
+    fn output(&self, snapshot: &CallGraphSnapshot, delta: bool) -> Vec<u8> {
+        #[cfg(feature = "serde")]
+        if self.json {
+            return serde_json::to_vec(snapshot).expect("expected: snapshots always serialize");
+        }
+        let mut output = Vec::new();
+        snapshot
+            .write_dot_with(&mut output, &self.dot_options)
+            .and_then(|()| match delta {
+                true => writeln!(output, "// delta since the previous flush"),
+                false => Ok(()),
+            })
+            .and_then(|()| write_open_spans(&mut output, &self.graph.open_spans()))
+            .and_then(|()| write_orphans(&mut output, &self.graph.orphans()))
+            .and_then(|()| match self.graph.report_unexecuted {
+                true => write_unexecuted(&mut output, &self.graph.unexecuted_callsites()),
+                false => Ok(()),
+            })
+            .expect("expected: writing to a Vec never fails");
+        output
+    }
+}
+
+/// Writes a comment listing the spans entered but not exited, if any.
+fn write_open_spans<W>(mut writer: W, open: &[(String, usize)]) -> io::Result<()>
+where
+    W: Write,
+{
+    if open.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "// open spans:")?;
+    for (name, count) in open {
+        writeln!(writer, "//     {}: {}", name, count)?;
+    }
+    Ok(())
+}
+
+/// Writes a comment listing the spans recorded without a caller, if any.
+fn write_orphans<W>(mut writer: W, orphans: &[Orphan]) -> io::Result<()>
+where
+    W: Write,
+{
+    if orphans.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "// orphan spans:")?;
+    for orphan in orphans {
+        writeln!(
+            writer,
+            "//     {} (thread {}): {}",
+            orphan.name, orphan.thread, orphan.count
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes a comment listing the span callsites never executed, if any.
+fn write_unexecuted<W>(mut writer: W, unexecuted: &[CallsiteInfo]) -> io::Result<()>
+where
+    W: Write,
+{
+    if unexecuted.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "// never executed:")?;
+    for callsite in unexecuted {
+        write!(writer, "//     {}::{}", callsite.target, callsite.name)?;
+        if let (Some(file), Some(line)) = (&callsite.file, callsite.line) {
+            write!(writer, " ({}:{})", file, line)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Replaces the file at `path` atomically with the output of `write`.
+fn write_file<F>(path: &Path, write: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut AtomicFile) -> io::Result<()>,
+{
+    let mut writer = AtomicFile::create(path)
+        .map_err(|source| Kind::CreateFile {
+            path: path.into(),
+            source,
+        })
+        .map_err(Error)?;
+    write(&mut writer)
+        .and_then(|()| writer.flush())
+        .map_err(Kind::FlushFile)
+        .map_err(Error)
 }
 
 impl<W> Drop for FlushGuard<W>
@@ -155,6 +470,9 @@ where
     W: Write + 'static,
 {
     fn drop(&mut self) {
+        if let Some(slices) = &self.slices {
+            slices.finish(self.graph.snapshot());
+        }
         match self.flush() {
             Ok(_) => (),
             Err(e) => e.report(),
@@ -164,20 +482,50 @@ where
 
 impl Default for GraphLayer {
     fn default() -> Self {
-        let graph = CallGraph::new();
         Self {
-            graph: Arc::new(Mutex::new(graph)),
+            graph: Arc::new(Graphs::default()),
             top_node: None,
+            top_nodes: None,
+            dot_options: DotOptions::default(),
+            folded_file: None,
+            speedscope_file: None,
+            partition_file: None,
+            external_calls: false,
+            log_events: false,
+            counting: Counting::default(),
+            skip_unrecorded_parents: false,
+            filter: None,
+            gate: None,
+            min_duration: None,
+            fallback: None,
+            slices: None,
+            stream: None,
+            failure: None,
+            observer: None,
+            delta_flushes: None,
         }
     }
 }
 
 impl GraphLayer {
     /// Returns a new [`GraphLayer`] which constructs the call graph.
+    ///
+    /// The graph is only kept in memory. It can be flushed to a writer with
+    /// [`flush_on_drop`], or retrieved at any time with [`snapshot`] or
+    /// rendered with [`to_dot`], which needs no writer or filesystem at all.
+    ///
+    /// [`flush_on_drop`]: GraphLayer::flush_on_drop
+    /// [`snapshot`]: GraphLayer::snapshot
+    /// [`to_dot`]: GraphLayer::to_dot
     pub fn new() -> Self {
         Default::default()
     }
 
+    /// Returns a [`Builder`] for configuring a `GraphLayer`.
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
     /// Returns a [`FlushGuard`] which will flush the `GraphLayer`'s writer when
     /// it is dropped, or when `flush` is manually invoked on the guard.
     pub fn flush_on_drop<W>(&self, writer: W) -> FlushGuard<W>
@@ -186,26 +534,613 @@ impl GraphLayer {
     {
         FlushGuard {
             graph: self.graph.clone(),
+            dot_options: self.dot_options.clone(),
+            folded_file: self.folded_file.clone(),
+            speedscope_file: self.speedscope_file.clone(),
+            partition_file: self.partition_file.clone(),
+            baseline: None,
+            fallback: self.fallback.clone(),
+            slices: self.slices.clone(),
+            stream: self.stream.clone(),
+            delta: self.delta_flushes.map(DeltaFlushes::new),
+            #[cfg(feature = "serde")]
+            json: false,
             writer,
         }
     }
+
+    /// Returns a [`CallGraphSnapshot`] of the graph recorded so far.
+    pub fn snapshot(&self) -> CallGraphSnapshot {
+        self.graph.snapshot()
+    }
+
+    /// Renders the graph recorded so far in Graphviz `dot` representation,
+    /// as a [`FlushGuard`] of the layer would write it.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let layer = GraphLayer::new();
+    /// let subscriber = Registry::default().with(layer.clone());
+    /// tracing::subscriber::with_default(subscriber, outer);
+    ///
+    /// let dot = layer.to_dot();
+    /// assert!(dot.contains("1 -> 0 [ label = \"1\" ]"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        self.snapshot().to_dot_with(&self.dot_options)
+    }
+
+    /// Renders the stacks recorded so far in folded format, see
+    /// [`write_folded`].
+    ///
+    /// [`write_folded`]: GraphLayer::write_folded
+    pub fn to_folded(&self) -> String {
+        let mut folded = Vec::new();
+        self.write_folded(&mut folded)
+            .expect("expected: writing to a Vec never fails");
+        String::from_utf8(folded).expect("expected: folded output is UTF-8")
+    }
+
+    /// Writes the time spent in every stack of spans recorded so far, in the
+    /// folded format of `tracing-flame`. Writes nothing unless stacks are
+    /// recorded, see [`Builder::folded_file`].
+    ///
+    /// Each line holds the names of the spans from the root of the stack,
+    /// separated by `;`, and the nanoseconds spent in the last span but not
+    /// in its children. Lines are sorted, so output is stable across runs.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let layer = GraphLayer::builder().folded_file("folded.txt").build();
+    /// let subscriber = Registry::default().with(layer.clone());
+    /// tracing::subscriber::with_default(subscriber, outer);
+    ///
+    /// let mut folded = Vec::new();
+    /// layer.write_folded(&mut folded).unwrap();
+    /// let stacks: Vec<_> = String::from_utf8(folded)
+    ///     .unwrap()
+    ///     .lines()
+    ///     .map(|line| line.rsplit_once(' ').unwrap().0.to_string())
+    ///     .collect();
+    /// assert_eq!(stacks, ["outer", "outer;inner"]);
+    /// ```
+    pub fn write_folded<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.graph.write_folded(writer)
+    }
+
+    /// Returns the names of the spans entered but not exited yet, with the
+    /// number of times each is open, sorted by name.
+    ///
+    /// Spans open when the graph is flushed are also listed in a comment after
+    /// the `dot` output. Besides spans legitimately running at the time, such
+    /// as the span of `main`, this catches leaked spans and missing exits,
+    /// whose time is missing from the graph.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// let layer = GraphLayer::new();
+    /// let subscriber = Registry::default().with(layer.clone());
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _done = tracing::info_span!("done").entered();
+    ///     let span = tracing::info_span!("leaked");
+    ///     std::mem::forget(span.enter());
+    ///     assert_eq!(layer.open_spans(), [("done".to_string(), 1), ("leaked".to_string(), 1)]);
+    /// });
+    /// assert_eq!(layer.open_spans(), [("leaked".to_string(), 1)]);
+    /// ```
+    pub fn open_spans(&self) -> Vec<(String, usize)> {
+        self.graph.open_spans()
+    }
+
+    /// Returns the spans recorded without a caller, with the thread they
+    /// were recorded on and their number, sorted by name and thread, if
+    /// enabled with [`Builder::report_orphans`].
+    ///
+    /// Without a top node, the edges into these spans are dropped. Spans
+    /// expected to have a parent point to missing instrumentation, or to a
+    /// parent on another thread that wasn't passed along with the work. A
+    /// [`FlushGuard`] lists them in a comment after the graph.
+    ///
+    /// ```rust
+    /// use std::thread;
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// let layer = GraphLayer::builder().report_orphans(true).build();
+    /// let subscriber = Registry::default().with(layer.clone());
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _request = tracing::info_span!("request").entered();
+    ///     let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+    ///     thread::Builder::new()
+    ///         .name("worker".into())
+    ///         .spawn(move || {
+    ///             // The span of the request isn't current on this thread.
+    ///             tracing::dispatcher::with_default(&dispatch, || {
+    ///                 drop(tracing::info_span!("query").entered())
+    ///             })
+    ///         })
+    ///         .unwrap()
+    ///         .join()
+    ///         .unwrap();
+    /// });
+    ///
+    /// let orphans = layer.orphans();
+    /// assert_eq!(orphans[0].name, "query");
+    /// assert_eq!(orphans[0].thread, "worker");
+    /// assert_eq!(orphans[1].name, "request");
+    /// ```
+    ///
+    /// [`Builder::report_orphans`]: crate::Builder::report_orphans
+    pub fn orphans(&self) -> Vec<Orphan> {
+        self.graph.orphans()
+    }
+
+    /// Returns the span callsites registered with the layer whose spans were
+    /// never recorded, sorted by target, file and line.
+    ///
+    /// This is coverage for instrumentation, showing instrumented functions
+    /// that are dead or untested. A [`FlushGuard`] lists them in a comment
+    /// after the graph if enabled with [`Builder::report_unexecuted`].
+    ///
+    /// `tracing` only knows of callsites once they are first reached, so
+    /// these are spans that were created but never entered, and spans
+    /// reached before the subscriber was set, which are registered with it
+    /// as it is set. Instrumented code that was never reached at all isn't
+    /// listed.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// fn handle(retry: bool) {
+    ///     let _span = tracing::info_span!("handle").entered();
+    ///     let retrying = tracing::info_span!("retry");
+    ///     if retry {
+    ///         retrying.in_scope(|| {});
+    ///     }
+    /// }
+    ///
+    /// let layer = GraphLayer::new();
+    /// let subscriber = Registry::default().with(layer.clone());
+    /// tracing::subscriber::with_default(subscriber, || handle(false));
+    ///
+    /// let unexecuted = layer.unexecuted_callsites();
+    /// assert_eq!(unexecuted.len(), 1);
+    /// assert_eq!(unexecuted[0].name, "retry");
+    /// ```
+    ///
+    /// [`Builder::report_unexecuted`]: crate::Builder::report_unexecuted
+    pub fn unexecuted_callsites(&self) -> Vec<CallsiteInfo> {
+        self.graph.unexecuted_callsites()
+    }
+
+    /// Returns the graph recorded for every value of the field set with
+    /// [`Builder::partition_by`], or every root span with
+    /// [`Builder::partition_by_root`], sorted by key.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// #[tracing::instrument]
+    /// fn handle(endpoint: &str) {
+    ///     match endpoint {
+    ///         "/users" => query(),
+    ///         _ => render(),
+    ///     }
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn query() {}
+    ///
+    /// #[tracing::instrument]
+    /// fn render() {}
+    ///
+    /// let layer = GraphLayer::builder().partition_by("endpoint", 10).build();
+    /// let subscriber = Registry::default().with(layer.clone());
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     handle("/users");
+    ///     handle("/");
+    /// });
+    ///
+    /// let partitions = layer.partitions();
+    /// assert_eq!(partitions[0].0, "/");
+    /// assert!(partitions[0].1.edge("handle", "render").is_some());
+    /// assert!(partitions[0].1.edge("handle", "query").is_none());
+    /// assert_eq!(partitions[1].0, "/users");
+    /// ```
+    ///
+    /// [`Builder::partition_by`]: crate::Builder::partition_by
+    /// [`Builder::partition_by_root`]: crate::Builder::partition_by_root
+    pub fn partitions(&self) -> Vec<(String, CallGraphSnapshot)> {
+        self.graph.partition_snapshots()
+    }
+
+    /// Marks the end of a frame, for game and render loops that think in
+    /// frames rather than wall-clock intervals.
+    ///
+    /// With [`Slicing::Frames`] configured by [`Builder::time_slices`], this
+    /// writes a graph of the calls made during every frame, or every given
+    /// number of frames.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{GraphLayer, Slicing};
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// #[tracing::instrument]
+    /// fn update() {}
+    ///
+    /// #[tracing::instrument]
+    /// fn render() {}
+    ///
+    /// let path = std::env::temp_dir().join("frames-example.dot");
+    /// let layer = GraphLayer::builder()
+    ///     .time_slices(&path, Slicing::Frames(2))
+    ///     .build();
+    /// let subscriber = Registry::default().with(layer.clone());
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     for _ in 0..4 {
+    ///         update();
+    ///         render();
+    ///         layer.end_frame();
+    ///     }
+    /// });
+    /// assert_eq!(layer.frames(), 4);
+    ///
+    /// let dot = std::fs::read_to_string(path.with_file_name("frames-example.2.dot")).unwrap();
+    /// let frames = tracing_callgraph::CallGraphSnapshot::from_dot(&dot).unwrap();
+    /// assert_eq!(frames.nodes()[0].name, "render");
+    /// ```
+    ///
+    /// [`Builder::time_slices`]: crate::Builder::time_slices
+    pub fn end_frame(&self) {
+        self.graph.frames.fetch_add(1, Ordering::Relaxed);
+        if let Some(slices) = &self.slices {
+            slices.frame_ended(|| self.graph.snapshot());
+        }
+    }
+
+    /// Returns the number of frames ended with [`end_frame`].
+    ///
+    /// [`end_frame`]: GraphLayer::end_frame
+    pub fn frames(&self) -> u64 {
+        self.graph.frames.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of span events skipped because the registry had no
+    /// span with their id.
+    ///
+    /// Layered setups, such as ones filtering spans per layer, can notify the
+    /// layer of spans it has no data for. Rather than panicking, these events
+    /// are ignored and counted here for diagnostics.
+    pub fn unknown_spans(&self) -> u64 {
+        self.graph.unknown_spans.load(Ordering::Relaxed)
+    }
+
+    /// Renders the timeline recorded so far as a Speedscope profile, see
+    /// [`write_speedscope`].
+    ///
+    /// [`write_speedscope`]: GraphLayer::write_speedscope
+    pub fn to_speedscope(&self) -> String {
+        let mut speedscope = Vec::new();
+        self.write_speedscope(&mut speedscope)
+            .expect("expected: writing to a Vec never fails");
+        String::from_utf8(speedscope).expect("expected: Speedscope output is UTF-8")
+    }
+
+    /// Writes the time every span was entered and exited so far as a
+    /// [Speedscope](https://www.speedscope.app) JSON profile in the evented
+    /// format, with one profile per thread. Writes nothing unless the
+    /// timeline is recorded, see [`Builder::speedscope_file`].
+    ///
+    /// This gives a time-ordered view of the spans, complementing the
+    /// aggregate graph. Frames are the nodes of the graph, and times are in
+    /// nanoseconds since the layer was built.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let layer = GraphLayer::builder().speedscope_file("profile.json").build();
+    /// let subscriber = Registry::default().with(layer.clone());
+    /// tracing::subscriber::with_default(subscriber, outer);
+    ///
+    /// let profile = layer.to_speedscope();
+    /// assert!(profile.contains(r#"{"name":"outer"}"#));
+    /// assert!(profile.contains(r#"{"name":"inner"}"#));
+    /// assert_eq!(profile.matches(r#""type":"O""#).count(), 2);
+    /// assert_eq!(profile.matches(r#""type":"C""#).count(), 2);
+    /// ```
+    pub fn write_speedscope<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.graph.write_speedscope(writer)
+    }
 }
 
+/// The environment variable read by [`GraphLayer::from_env`].
+pub const OUTPUT_ENV: &str = "TRACING_CALLGRAPH_OUTPUT";
+
+/// The name of the node that collects all spans beyond the limit set with
+/// [`Builder::max_nodes`].
+pub const OTHER_NODE: &str = "[other]";
+
+/// The separator between the service and span name of nodes recorded with
+/// [`Builder::service_name`].
+pub const SERVICE_SEPARATOR: char = '/';
+
 impl GraphLayer {
     /// Constructs a `GraphLayer` that constructs the call graph, and a
     /// `FlushGuard` which writes the graph to a `dot` file when dropped.
-    pub fn with_file(path: impl AsRef<Path>) -> Result<(Self, FlushGuard<BufWriter<File>>), Error> {
-        let path = path.as_ref();
-        let file = File::create(path)
-            .map_err(|source| Kind::CreateFile {
-                path: path.into(),
-                source,
-            })
-            .map_err(Error)?;
-        let writer = BufWriter::new(file);
-        let layer = Self::new();
-        let guard = layer.flush_on_drop(writer);
-        Ok((layer, guard))
+    ///
+    /// The file is replaced atomically on every flush, see [`AtomicFile`].
+    pub fn with_file(path: impl AsRef<Path>) -> Result<(Self, FlushGuard<AtomicFile>), Error> {
+        Builder::new().with_file(path)
+    }
+
+    /// Constructs a `GraphLayer` that constructs the call graph, and a
+    /// `FlushGuard` which writes the graph to `writer` when dropped.
+    ///
+    /// ```rust
+    /// use std::{
+    ///     io::{self, Write},
+    ///     sync::{Arc, Mutex},
+    /// };
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{prelude::*, Registry};
+    ///
+    /// #[derive(Clone, Default)]
+    /// struct Buffer(Arc<Mutex<Vec<u8>>>);
+    ///
+    /// impl Write for Buffer {
+    ///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    ///         self.0.lock().unwrap().write(buf)
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn work() {}
+    ///
+    /// let buffer = Buffer::default();
+    /// let (layer, guard) = GraphLayer::with_writer(buffer.clone());
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, work);
+    /// drop(guard);
+    ///
+    /// let dot = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    /// assert!(dot.contains("work"));
+    /// ```
+    pub fn with_writer<W>(writer: W) -> (Self, FlushGuard<W>)
+    where
+        W: Write + 'static,
+    {
+        Builder::new().with_writer(writer)
+    }
+
+    /// Constructs a `GraphLayer` that constructs the call graph, and a
+    /// `FlushGuard` which writes the graph to stdout when dropped.
+    pub fn with_stdout() -> (Self, FlushGuard<io::Stdout>) {
+        Builder::new().with_stdout()
+    }
+
+    /// Constructs a `GraphLayer` that constructs the call graph, and a
+    /// `FlushGuard` which writes the graph to stderr when dropped.
+    pub fn with_stderr() -> (Self, FlushGuard<io::Stderr>) {
+        Builder::new().with_stderr()
+    }
+
+    /// Like [`with_file`], but writes to the path given by the
+    /// `TRACING_CALLGRAPH_OUTPUT` environment variable, falling back to
+    /// `./output.dot`.
+    ///
+    /// This is the hook used by the `cargo callgraph` subcommand, which sets the
+    /// variable before running the target, to a path with a `{seq}`
+    /// placeholder so that every layer of every process writes its own file.
+    ///
+    /// [`with_file`]: GraphLayer::with_file
+    pub fn from_env() -> Result<(Self, FlushGuard<AtomicFile>), Error> {
+        let path = std::env::var_os(OUTPUT_ENV).unwrap_or_else(|| "./output.dot".into());
+        Self::with_file(path)
+    }
+}
+
+/// Values kept by a span for every thread it is entered on, as clones of its
+/// handle can enter it on several threads at once.
+struct PerThread<T>(Vec<(ThreadId, T)>);
+
+impl<T> PerThread<T> {
+    fn new(value: T) -> Self {
+        Self(vec![(thread::current().id(), value)])
+    }
+
+    fn push(&mut self, value: T) {
+        self.0.push((thread::current().id(), value));
+    }
+
+    /// Removes the value pushed last on the current thread.
+    fn pop(&mut self) -> Option<T> {
+        let thread = thread::current().id();
+        let index = self.0.iter().rposition(|(id, _)| *id == thread)?;
+        Some(self.0.remove(index).1)
+    }
+
+    /// Returns the value pushed last on the current thread.
+    fn last_mut(&mut self) -> Option<&mut T> {
+        let thread = thread::current().id();
+        let (_, value) = self.0.iter_mut().rev().find(|(id, _)| *id == thread)?;
+        Some(value)
+    }
+}
+
+/// The times a span was entered and not exited yet, stored in its extensions.
+///
+/// Each exit is matched with the latest enter on the same thread, so a span
+/// entered on several threads, or re-entered, has every enter timed.
+struct EnteredAt(PerThread<Instant>);
+
+/// The node of a span, stored in its extensions.
+struct SpanNode(NodeId);
+
+/// Marks a span left out by the predicate of [`Builder::record_when`], with
+/// all the spans in it, in its extensions.
+struct Gated;
+
+/// The partition a span is recorded into, stored in its extensions.
+struct SpanPartition(Arc<Partition>);
+
+/// The edges followed within a root span so far, stored in its extensions
+/// while recording their order.
+#[derive(Default)]
+struct EdgeOrder(HashSet<(NodeId, NodeId)>);
+
+/// The top node chosen for a span without a recorded parent, stored in its
+/// extensions, see [`Builder::top_nodes`].
+struct SpanTopNode(NodeId);
+
+/// The external node called by a span, stored in its extensions.
+struct ExternalNode(NodeId);
+
+/// The calls of a span and the time it ran for, held back in its extensions
+/// until it closes, see [`Builder::min_duration`].
+struct BufferedCalls {
+    calls: u64,
+    elapsed: Duration,
+}
+
+/// The time spent in the children of a span since it was last exited on each
+/// thread, stored in its extensions while recording folded stacks.
+struct ChildrenBusy(PerThread<Duration>);
+
+impl GraphLayer {
+    fn node<S>(&self, span: &SpanRef<'_, S>) -> NodeId
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        match span.extensions().get::<SpanNode>() {
+            Some(SpanNode(id)) => *id,
+            None => self.graph.names.callsite(span.metadata()),
+        }
+    }
+
+    /// Returns whether spans and events with `metadata` pass the filter set
+    /// with [`Builder::filter`].
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        match &self.filter {
+            Some(filter) if metadata.is_span() => filter.matches(metadata),
+            _ => true,
+        }
+    }
+
+    /// Looks up the span with `id`, counting it as unknown if it is missing,
+    /// see [`unknown_spans`].
+    ///
+    /// [`unknown_spans`]: GraphLayer::unknown_spans
+    fn span<'a, S>(&self, id: &span::Id, ctx: &'a Context<'_, S>) -> Option<SpanRef<'a, S>>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let span = ctx.span(id);
+        if span.is_none() {
+            self.graph.unknown_spans.fetch_add(1, Ordering::Relaxed);
+        }
+        span.filter(|span| !self.is_gated(span))
+    }
+
+    /// Returns whether `span` was left out by the predicate of
+    /// [`Builder::record_when`].
+    fn is_gated<S>(&self, span: &SpanRef<'_, S>) -> bool
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        self.gate.is_some() && span.extensions().get::<Gated>().is_some()
+    }
+
+    /// Returns the nearest ancestor of `span` that counts as its parent, see
+    /// [`is_recorded`].
+    ///
+    /// [`is_recorded`]: GraphLayer::is_recorded
+    fn parent<'a, S>(&self, span: &SpanRef<'a, S>) -> Option<SpanRef<'a, S>>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        span.scope().skip(1).find(|parent| self.is_recorded(parent))
+    }
+
+    fn caller<S>(&self, span: &SpanRef<'_, S>) -> Option<NodeId>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        match self.parent(span) {
+            Some(parent) => Some(self.node(&parent)),
+            None if self.top_nodes.is_none() => self.top_node,
+            None => {
+                if let Some(&SpanTopNode(id)) = span.extensions().get::<SpanTopNode>() {
+                    return Some(id);
+                }
+                let id = self.top_node_of(span.metadata());
+                if let Some(id) = id {
+                    span.extensions_mut().insert(SpanTopNode(id));
+                }
+                id
+            }
+        }
+    }
+
+    /// Returns the top node of spans and events with `metadata` that have no
+    /// recorded parent, see [`Builder::top_nodes`].
+    fn top_node_of(&self, metadata: &Metadata<'_>) -> Option<NodeId> {
+        match self.top_nodes.as_ref().and_then(|top| top.select(metadata)) {
+            Some(name) => Some(self.graph.names.span(name)),
+            None => self.top_node,
+        }
+    }
+
+    /// Returns whether `span` counts as an ancestor of the spans in it, see
+    /// [`Builder::skip_unrecorded_parents`].
+    fn is_recorded<S>(&self, span: &SpanRef<'_, S>) -> bool
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        (!self.skip_unrecorded_parents || span.extensions().get::<SpanNode>().is_some())
+            && !self.is_gated(span)
     }
 }
 
@@ -213,29 +1148,418 @@ impl<S> Layer<S> for GraphLayer
 where
     S: Subscriber + for<'span> LookupSpan<'span>,
 {
-    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
-        let mut locked = self.graph.lock().unwrap();
-
-        // Add node
-        let first = ctx.span(id).expect("expected: span id exists in registry");
-        let node_b = first.name();
-        locked.add_node(node_b);
-
-        // Find parent node
-        let node_a = if let Some(parent) = first.parent() {
-            parent.name()
-        } else if let Some(name) = self.top_node {
-            name
-        } else {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if !self.enabled(metadata) {
+            return Interest::never();
+        }
+        if metadata.is_span() {
+            self.graph.names.register(metadata);
+        }
+        Interest::always()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        GraphLayer::enabled(self, metadata)
+    }
+
+    fn new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let span = match self.span(id, &ctx) {
+            Some(span) => span,
+            None => return,
+        };
+        if let Some(gate) = &self.gate {
+            // Spans in a recorded span are recorded without asking.
+            let recorded = span.parent().is_some_and(|parent| !self.is_gated(&parent));
+            if !recorded {
+                let mut fields = SpanFields::default();
+                attrs.record(&mut fields);
+                if !gate.accepts(&fields) {
+                    span.extensions_mut().insert(Gated);
+                    return;
+                }
+            }
+        }
+        let node = self.graph.names.callsite(span.metadata());
+        span.extensions_mut().insert(SpanNode(node));
+        if let Some(partitions) = &self.graph.partitions {
+            let key = match partitions.key {
+                PartitionKey::Field(field) => {
+                    let mut visitor = KeyVisitor::new(field);
+                    attrs.record(&mut visitor);
+                    visitor.key
+                }
+                PartitionKey::Root if span.parent().is_none() => {
+                    Some(self.graph.names.name(node).into_owned())
+                }
+                PartitionKey::Root => None,
+            };
+            // Spans without a key are recorded into the partition of their
+            // parent, if any.
+            let partition = match key {
+                Some(key) => Some(partitions.get(&key)),
+                None => span.parent().and_then(|parent| {
+                    parent
+                        .extensions()
+                        .get::<SpanPartition>()
+                        .map(|p| p.0.clone())
+                }),
+            };
+            if let Some(partition) = partition {
+                span.extensions_mut().insert(SpanPartition(partition));
+            }
+        }
+        if self.external_calls {
+            let mut visitor = ExternalVisitor::default();
+            attrs.record(&mut visitor);
+            self.set_external(&span, visitor);
+        }
+        if let Some(aggregates) = &self.graph.aggregates {
+            let mut visitor = ValueVisitor::new(aggregates.field());
+            attrs.record(&mut visitor);
+            self.aggregate(aggregates, &span, visitor);
+        }
+        if self.counting == Counting::Spans {
+            match self.min_duration {
+                Some(_) => self.buffer_call(&span),
+                None => self.record_call(&span, node),
+            }
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if !self.external_calls
+            && self.graph.partitions.is_none()
+            && self.graph.aggregates.is_none()
+        {
+            return;
+        }
+        let span = match self.span(id, &ctx) {
+            Some(span) => span,
+            None => return,
+        };
+        if self.external_calls {
+            let mut visitor = ExternalVisitor::default();
+            values.record(&mut visitor);
+            self.set_external(&span, visitor);
+        }
+        if let Some(partitions) = &self.graph.partitions {
+            if let PartitionKey::Field(field) = partitions.key {
+                let mut visitor = KeyVisitor::new(field);
+                values.record(&mut visitor);
+                if let Some(key) = visitor.key {
+                    let partition = SpanPartition(partitions.get(&key));
+                    span.extensions_mut().replace(partition);
+                }
+            }
+        }
+        if let Some(aggregates) = &self.graph.aggregates {
+            let mut visitor = ValueVisitor::new(aggregates.field());
+            values.record(&mut visitor);
+            self.aggregate(aggregates, &span, visitor);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.event_span(event) {
+            if self.is_gated(&span) {
+                return;
+            }
+        }
+        if logs::is_error(event) {
+            let span = ctx.event_span(event);
+            if let Some((caller, span)) = span.and_then(|span| Some((self.caller(&span)?, span))) {
+                self.graph.record_error(caller, self.node(&span));
+            }
+            if let Some(failure) = &self.failure {
+                failure.error(|| self.graph.snapshot());
+            }
+        }
+        if !self.log_events {
             return;
+        }
+        let name = match logs::node_name(event) {
+            Some(name) => name,
+            None => return,
         };
+        let _timer = self
+            .graph
+            .overhead
+            .as_ref()
+            .map(OverheadCounters::time_event);
+        let node = self.graph.names.intern(name);
+        let caller = match ctx.event_span(event) {
+            Some(span) => Some(self.node(&span)),
+            None => self.top_node_of(event.metadata()),
+        };
+        self.record_enter(caller, node);
+    }
 
-        if let Some(weight) = locked.edge_weight_mut(node_a, node_b) {
-            // Increase edge weight
-            *weight += 1;
-        } else {
-            // Add edge
-            locked.add_edge(node_a, node_b, 1);
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let _timer = self
+            .graph
+            .overhead
+            .as_ref()
+            .map(OverheadCounters::time_event);
+        let span = match self.span(id, &ctx) {
+            Some(span) => span,
+            None => return,
+        };
+        let node = self.node(&span);
+        if self.counting == Counting::Enters {
+            match self.min_duration {
+                Some(_) => self.buffer_call(&span),
+                None => {
+                    self.record_call(&span, node);
+                    if let Some(&ExternalNode(external)) = span.extensions().get::<ExternalNode>() {
+                        self.record_enter(Some(node), external);
+                    }
+                }
+            }
         }
+        if let Some(timeline) = &self.graph.timeline {
+            timeline.record_enter(node);
+        }
+        let mut extensions = span.extensions_mut();
+        match extensions.get_mut::<EnteredAt>() {
+            Some(EnteredAt(entered_at)) => entered_at.push(Instant::now()),
+            None => extensions.insert(EnteredAt(PerThread::new(Instant::now()))),
+        }
+        self.graph.open.enter(node);
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let _timer = self
+            .graph
+            .overhead
+            .as_ref()
+            .map(OverheadCounters::time_event);
+        let span = match self.span(id, &ctx) {
+            Some(span) => span,
+            None => return,
+        };
+        let entered_at = match span.extensions_mut().get_mut::<EnteredAt>() {
+            Some(EnteredAt(entered_at)) => entered_at.pop(),
+            None => None,
+        };
+        let entered_at = match entered_at {
+            Some(entered_at) => entered_at,
+            None => return,
+        };
+        let elapsed = entered_at.elapsed();
+        let node = self.node(&span);
+        self.graph.open.exit(node);
+        match self.min_duration {
+            Some(_) => {
+                if let Some(buffered) = span.extensions_mut().get_mut::<BufferedCalls>() {
+                    accumulate(&mut buffered.elapsed, elapsed);
+                }
+            }
+            None => self.record_exit(&span, node, elapsed),
+        }
+        if let Some(stacks) = &self.graph.stacks {
+            self.record_stack(stacks, &span, elapsed);
+        }
+        if let Some(timeline) = &self.graph.timeline {
+            timeline.record_exit(node);
+        }
+        if let Some(slices) = &self.slices {
+            if self.parent(&span).is_none() {
+                slices.root_exited(|| self.graph.snapshot());
+            }
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let min_duration = match self.min_duration {
+            Some(min_duration) => min_duration,
+            None => return,
+        };
+        let span = match self.span(&id, &ctx) {
+            Some(span) => span,
+            None => return,
+        };
+        let buffered = span.extensions_mut().remove::<BufferedCalls>();
+        let BufferedCalls { calls, elapsed } = match buffered {
+            Some(buffered) if buffered.elapsed >= min_duration => buffered,
+            _ => return,
+        };
+        let node = self.node(&span);
+        let external = span.extensions().get::<ExternalNode>().map(|node| node.0);
+        for _ in 0..calls {
+            self.record_call(&span, node);
+            if let Some(external) = external {
+                self.record_enter(Some(node), external);
+            }
+        }
+        self.record_exit(&span, node, elapsed);
+    }
+}
+
+impl GraphLayer {
+    /// Records a call of `span`, whose node is `node`, from its caller.
+    fn record_call<S>(&self, span: &SpanRef<'_, S>, node: NodeId)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let caller = self.caller(span);
+        self.record_enter(caller, node);
+        self.record_orphan(caller, node);
+        if let Some(SpanPartition(partition)) = span.extensions().get::<SpanPartition>() {
+            partition.record_enter(caller, node);
+        }
+        self.record_order(span, caller, node);
+    }
+
+    /// Records `span`, whose node is `node`, being exited after running for
+    /// `elapsed`.
+    fn record_exit<S>(&self, span: &SpanRef<'_, S>, node: NodeId, elapsed: Duration)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let caller = self.caller(span);
+        self.graph.record_exit(caller, node, elapsed);
+        self.observe(caller, node, elapsed);
+        if let Some(slas) = &self.graph.slas {
+            if slas.exceeded(node, elapsed, &self.graph.names) {
+                self.graph.record_sla_violation(caller, node);
+            }
+        }
+        if let Some(SpanPartition(partition)) = span.extensions().get::<SpanPartition>() {
+            partition.record_exit(caller, node, elapsed);
+        }
+        if let Some(&ExternalNode(external)) = span.extensions().get::<ExternalNode>() {
+            self.graph.record_exit(Some(node), external, elapsed);
+            self.observe(Some(node), external, elapsed);
+        }
+    }
+
+    /// Hands a call to the callback set with [`Builder::on_call`], if any.
+    fn observe(&self, caller: Option<NodeId>, callee: NodeId, elapsed: Duration) {
+        if let Some(observer) = &self.observer {
+            let names = &self.graph.names;
+            let caller = caller.map(|caller| names.name(caller));
+            observer.call(caller.as_deref(), &names.name(callee), elapsed);
+        }
+    }
+
+    /// Counts a call of `span` to be recorded when it closes, if it ran for
+    /// long enough, see [`Builder::min_duration`].
+    fn buffer_call<S>(&self, span: &SpanRef<'_, S>)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let mut extensions = span.extensions_mut();
+        match extensions.get_mut::<BufferedCalls>() {
+            Some(buffered) => accumulate(&mut buffered.calls, 1),
+            None => extensions.insert(BufferedCalls {
+                calls: 1,
+                elapsed: Duration::default(),
+            }),
+        }
+    }
+
+    /// Records a call from `caller` to `callee`, and writes it to the stream
+    /// set with [`Builder::edge_stream`], if any.
+    fn record_enter(&self, caller: Option<NodeId>, callee: NodeId) {
+        self.graph.record_enter(caller, callee);
+        if let (Some(stream), Some(caller)) = (&self.stream, caller) {
+            let names = &self.graph.names;
+            stream.record(&names.name(caller), &names.name(callee));
+        }
+    }
+
+    /// Counts the span of `node` as an orphan if it has no `caller`, see
+    /// [`orphans`].
+    ///
+    /// [`orphans`]: GraphLayer::orphans
+    fn record_orphan(&self, caller: Option<NodeId>, node: NodeId) {
+        if let (None, Some(orphans)) = (caller, &self.graph.orphans) {
+            orphans.record(node);
+        }
+    }
+
+    /// Numbers the edge from `caller` to the node of `span` if it is
+    /// followed for the first time within the root span of `span`, see
+    /// [`Builder::edge_order`].
+    fn record_order<S>(&self, span: &SpanRef<'_, S>, caller: Option<NodeId>, node: NodeId)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let (caller, root) = match (caller, span.scope().from_root().next()) {
+            (Some(caller), Some(root)) if self.graph.edge_order => (caller, root),
+            _ => return,
+        };
+        let mut extensions = root.extensions_mut();
+        if extensions.get_mut::<EdgeOrder>().is_none() {
+            extensions.insert(EdgeOrder::default());
+        }
+        let EdgeOrder(seen) = extensions
+            .get_mut::<EdgeOrder>()
+            .expect("expected: the order was just inserted");
+        if seen.insert((caller, node)) {
+            self.graph.record_order(caller, node, seen.len());
+        }
+    }
+
+    /// Combines the value found by `visitor`, if any, onto the edge into
+    /// `span`, see [`Builder::aggregate_edges`].
+    fn aggregate<S>(&self, aggregates: &Aggregates, span: &SpanRef<'_, S>, visitor: ValueVisitor)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        if let (Some(value), Some(caller)) = (visitor.value, self.caller(span)) {
+            aggregates.record(caller, self.node(span), value);
+        }
+    }
+
+    /// Makes the external dependency found by `visitor`, if any, the one
+    /// called by `span`.
+    fn set_external<S>(&self, span: &SpanRef<'_, S>, visitor: ExternalVisitor)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        if let Some(name) = visitor.node_name() {
+            let external = self.graph.names.intern(name);
+            let previous = span.extensions_mut().replace(ExternalNode(external));
+            // Spans are counted once, so only the first dependency is called.
+            if self.counting == Counting::Spans && previous.is_none() && self.min_duration.is_none()
+            {
+                self.record_enter(Some(self.node(span)), external);
+            }
+        }
+    }
+
+    /// Records the time `span` was busy, excluding its children, under the
+    /// stack of spans leading to it.
+    fn record_stack<S>(&self, stacks: &FoldedStacks, span: &SpanRef<'_, S>, elapsed: Duration)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let children = match span.extensions_mut().get_mut::<ChildrenBusy>() {
+            Some(ChildrenBusy(children)) => children.pop(),
+            None => None,
+        };
+        if let Some(parent) = self.parent(span) {
+            let mut extensions = parent.extensions_mut();
+            match extensions.get_mut::<ChildrenBusy>() {
+                Some(ChildrenBusy(busy)) => match busy.last_mut() {
+                    Some(busy) => *busy += elapsed,
+                    None => busy.push(elapsed),
+                },
+                None => extensions.insert(ChildrenBusy(PerThread::new(elapsed))),
+            }
+        }
+
+        let spans: Vec<_> = span
+            .scope()
+            .from_root()
+            .filter(|span| self.is_recorded(span))
+            .collect();
+        let top_node = spans.first().and_then(|root| self.caller(root));
+        let stack = top_node
+            .into_iter()
+            .chain(spans.iter().map(|span| self.node(span)))
+            .collect();
+        stacks.record(stack, elapsed.saturating_sub(children.unwrap_or_default()));
     }
 }
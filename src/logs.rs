@@ -0,0 +1,59 @@
+use std::fmt;
+use tracing::{
+    field::{Field, Visit},
+    Event, Level,
+};
+
+/// The prefix of the names of nodes for `log` records.
+const LOG_PREFIX: &str = "log: ";
+
+/// The field holding the target of a `log` record bridged by `tracing-log`.
+const LOG_TARGET: &str = "log.target";
+
+/// The field holding the error of an event, such as those reported by
+/// `#[instrument(err)]`.
+const ERROR_FIELD: &str = "error";
+
+/// Returns whether `event` reports an error, by its level or an `error`
+/// field, which `#[instrument(err)]` sets at any level.
+pub(crate) fn is_error(event: &Event<'_>) -> bool {
+    let metadata = event.metadata();
+    *metadata.level() == Level::ERROR || metadata.fields().field(ERROR_FIELD).is_some()
+}
+
+/// Returns the name of the node for `event`, if it is a `log` record bridged
+/// by `tracing-log`.
+///
+/// The bridge reports every record from the same callsite, so the node is
+/// named after the original target of the record rather than the metadata.
+pub(crate) fn node_name(event: &Event<'_>) -> Option<String> {
+    let metadata = event.metadata();
+    metadata.fields().field(LOG_TARGET)?;
+    let mut visitor = TargetVisitor::default();
+    event.record(&mut visitor);
+    let target = visitor
+        .target
+        .as_deref()
+        .unwrap_or_else(|| metadata.target());
+    Some(format!("{}{}", LOG_PREFIX, target))
+}
+
+/// Finds the `log.target` field of an event.
+#[derive(Debug, Default)]
+struct TargetVisitor {
+    target: Option<String>,
+}
+
+impl Visit for TargetVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == LOG_TARGET {
+            self.target = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == LOG_TARGET {
+            self.target = Some(format!("{:?}", value));
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{weight::accumulate, CallGraphSnapshot};
+use std::{
+    borrow::Cow,
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+/// The layout of an adjacency matrix, see
+/// [`CallGraphSnapshot::to_adjacency_matrix`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MatrixFormat {
+    /// One comma-separated row of call counts per caller, with a column per
+    /// callee, as read by `numpy.loadtxt(path, delimiter=",")`.
+    #[default]
+    Dense,
+    /// One `caller,callee,count` line per edge, after a `#` header line, as
+    /// read by `numpy.loadtxt(path, delimiter=",", dtype=int)` and turned into
+    /// a `scipy.sparse.coo_matrix`.
+    Sparse,
+}
+
+impl CallGraphSnapshot {
+    /// Writes the call counts between all nodes to `writer` as an adjacency
+    /// matrix in `format`, for analysis in other tooling.
+    ///
+    /// Rows and columns are the indices of [`nodes`], which
+    /// [`write_node_index`] writes alongside.
+    ///
+    /// [`nodes`]: CallGraphSnapshot::nodes
+    /// [`write_node_index`]: CallGraphSnapshot::write_node_index
+    pub fn write_adjacency_matrix<W>(&self, mut writer: W, format: MatrixFormat) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(self.to_adjacency_matrix(format).as_bytes())
+    }
+
+    /// Returns the adjacency matrix of [`write_adjacency_matrix`].
+    ///
+    /// ```rust
+    /// use tracing_callgraph::MatrixFormat;
+    ///
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner();
+    ///     inner();
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let snapshot = tracing_callgraph::with_graph_recording(outer);
+    /// assert_eq!(snapshot.to_node_index(), "index,name\n0,inner\n1,outer\n");
+    /// assert_eq!(snapshot.to_adjacency_matrix(MatrixFormat::Dense), "0,0\n2,0\n");
+    /// assert_eq!(
+    ///     snapshot.to_adjacency_matrix(MatrixFormat::Sparse),
+    ///     "# caller,callee,count\n1,0,2\n",
+    /// );
+    /// ```
+    ///
+    /// [`write_adjacency_matrix`]: CallGraphSnapshot::write_adjacency_matrix
+    pub fn to_adjacency_matrix(&self, format: MatrixFormat) -> String {
+        let mut out = String::new();
+        match format {
+            MatrixFormat::Dense => {
+                let n = self.nodes().len();
+                let mut rows = vec![vec![0; n]; n];
+                for edge in self.edges() {
+                    accumulate(&mut rows[edge.caller][edge.callee], edge.count);
+                }
+                for row in rows {
+                    let row: Vec<_> = row.iter().map(u64::to_string).collect();
+                    let _ = writeln!(out, "{}", row.join(","));
+                }
+            }
+            MatrixFormat::Sparse => {
+                out.push_str("# caller,callee,count\n");
+                let mut edges: Vec<_> = self
+                    .edges()
+                    .iter()
+                    .map(|edge| (edge.caller, edge.callee, edge.count))
+                    .collect();
+                edges.sort_unstable();
+                for (caller, callee, count) in edges {
+                    let _ = writeln!(out, "{},{},{}", caller, callee, count);
+                }
+            }
+        }
+        out
+    }
+
+    /// Writes the name of every node to `writer` as CSV with an
+    /// `index,name` header, mapping the rows and columns of
+    /// [`write_adjacency_matrix`] back to nodes.
+    ///
+    /// [`write_adjacency_matrix`]: CallGraphSnapshot::write_adjacency_matrix
+    pub fn write_node_index<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(self.to_node_index().as_bytes())
+    }
+
+    /// Returns the node index of [`write_node_index`].
+    ///
+    /// [`write_node_index`]: CallGraphSnapshot::write_node_index
+    pub fn to_node_index(&self) -> String {
+        let mut out = String::from("index,name\n");
+        for (index, node) in self.nodes().iter().enumerate() {
+            let _ = writeln!(out, "{},{}", index, quote(&node.name));
+        }
+        out
+    }
+}
+
+/// Quotes `field` for CSV if it contains a separator, quote or line break,
+/// as generic names such as `HashMap<K, V>` do.
+fn quote(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
@@ -0,0 +1,59 @@
+use tracing::Metadata;
+
+/// The targets of spans from the internals of async runtimes and networking
+/// libraries, suppressed by [`Builder::suppress_noise`]. Targets within
+/// these modules are suppressed too.
+///
+/// [`Builder::suppress_noise`]: crate::Builder::suppress_noise
+pub const NOISY_TARGETS: &[&str] = &[
+    "tokio::runtime",
+    "tokio::task",
+    "tokio::sync",
+    "tokio::util",
+    "hyper::proto",
+    "hyper::client::pool",
+    "hyper::client::connect",
+    "hyper_util::client",
+    "h2",
+    "tower::buffer",
+    "tower::limit",
+    "tower::balance",
+    "tower::ready_cache",
+    "mio",
+    "want",
+];
+
+/// How spans with one of the [`NOISY_TARGETS`] are suppressed, see
+/// [`Builder::suppress_noise`].
+///
+/// [`Builder::suppress_noise`]: crate::Builder::suppress_noise
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Noise {
+    /// Record the spans of every library as a single node named after it,
+    /// such as `tokio internals`.
+    Collapse,
+    /// Don't record the spans, connecting the spans in them to the nearest
+    /// recorded ancestor, as with [`Builder::filter`].
+    ///
+    /// [`Builder::filter`]: crate::Builder::filter
+    Drop,
+}
+
+/// Returns whether spans with `metadata` come from one of the
+/// [`NOISY_TARGETS`].
+pub(crate) fn is_noisy(metadata: &Metadata<'_>) -> bool {
+    let target = metadata.target();
+    NOISY_TARGETS.iter().any(|noisy| {
+        target
+            .strip_prefix(noisy)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+    })
+}
+
+/// Returns the name of the node noisy spans with `metadata` are collapsed
+/// into.
+pub(crate) fn collapsed_name(metadata: &Metadata<'_>) -> String {
+    let library = metadata.target().split("::").next().unwrap_or_default();
+    format!("{} internals", library)
+}
@@ -0,0 +1,34 @@
+use std::{fmt, time::Duration};
+
+type Observe = dyn Fn(Option<&str>, &str, Duration) + Send + Sync;
+
+/// Hands every call recorded into the graph to a callback, see
+/// [`Builder::on_call`].
+///
+/// [`Builder::on_call`]: crate::Builder::on_call
+pub(crate) struct Observer {
+    observe: Box<Observe>,
+}
+
+impl Observer {
+    pub(crate) fn new<F>(observe: F) -> Self
+    where
+        F: Fn(Option<&str>, &str, Duration) + Send + Sync + 'static,
+    {
+        Self {
+            observe: Box::new(observe),
+        }
+    }
+
+    /// Hands a call from `caller`, if any, to `callee`, which ran for
+    /// `elapsed`, to the callback.
+    pub(crate) fn call(&self, caller: Option<&str>, callee: &str, elapsed: Duration) {
+        (self.observe)(caller, callee, elapsed)
+    }
+}
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observer").finish_non_exhaustive()
+    }
+}
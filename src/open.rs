@@ -0,0 +1,47 @@
+use crate::{graph::NodeId, sync::RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The number of times the spans of every node were entered but not exited
+/// yet, see [`GraphLayer::open_spans`].
+///
+/// [`GraphLayer::open_spans`]: crate::GraphLayer::open_spans
+#[derive(Debug, Default)]
+pub(crate) struct OpenSpans {
+    counts: RwLock<Vec<AtomicUsize>>,
+}
+
+impl OpenSpans {
+    pub(crate) fn enter(&self, id: NodeId) {
+        if let Some(count) = self.counts.read().get(id.index()) {
+            count.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let mut counts = self.counts.write();
+        if counts.len() <= id.index() {
+            counts.resize_with(id.index() + 1, Default::default);
+        }
+        counts[id.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records an exit matching an earlier [`enter`] of the node.
+    ///
+    /// [`enter`]: OpenSpans::enter
+    pub(crate) fn exit(&self, id: NodeId) {
+        if let Some(count) = self.counts.read().get(id.index()) {
+            count.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns every node with open spans, and their number.
+    pub(crate) fn get(&self) -> Vec<(NodeId, usize)> {
+        self.counts
+            .read()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, count)| match count.load(Ordering::Relaxed) {
+                0 => None,
+                count => Some((NodeId(index as u32), count)),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{
+    graph::NodeId,
+    hash::{BuildHasher, HashMap},
+    sync::RwLock,
+};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::{self, ThreadId},
+};
+
+/// Spans of a node recorded without a caller on a thread, see
+/// [`GraphLayer::orphans`].
+///
+/// [`GraphLayer::orphans`]: crate::GraphLayer::orphans
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Orphan {
+    /// Name of the node of the spans.
+    pub name: String,
+    /// Name of the thread the spans were recorded on, or its id if it has no
+    /// name.
+    pub thread: String,
+    /// Number of times the spans were recorded.
+    pub count: usize,
+}
+
+/// The number of spans of every node recorded without a caller, per thread.
+#[derive(Debug)]
+pub(crate) struct Orphans {
+    counts: RwLock<HashMap<(NodeId, ThreadId), (String, AtomicUsize)>>,
+}
+
+impl Orphans {
+    pub(crate) fn new(hasher: BuildHasher) -> Self {
+        Self {
+            counts: RwLock::new(HashMap::with_hasher(hasher)),
+        }
+    }
+
+    /// Counts a span of `id` recorded without a caller on the current thread.
+    pub(crate) fn record(&self, id: NodeId) {
+        let current = thread::current();
+        let key = (id, current.id());
+        if let Some((_, count)) = self.counts.read().get(&key) {
+            count.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let thread = match current.name() {
+            Some(name) => name.to_string(),
+            None => format!("{:?}", current.id()),
+        };
+        self.counts
+            .write()
+            .entry(key)
+            .or_insert_with(|| (thread, AtomicUsize::new(0)))
+            .1
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns every node and thread with orphans, and their number.
+    pub(crate) fn get(&self) -> Vec<(NodeId, String, usize)> {
+        self.counts
+            .read()
+            .iter()
+            .map(|(&(id, _), (thread, count))| (id, thread.clone(), count.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
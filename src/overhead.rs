@@ -0,0 +1,95 @@
+use crate::{time::Instant, CallGraphSnapshot};
+use std::{
+    convert::TryFrom,
+    ops::AddAssign,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// The cost of recording a graph, measured by a [`GraphLayer`] configured
+/// with [`Builder::measure_overhead`].
+///
+/// [`GraphLayer`]: crate::GraphLayer
+/// [`Builder::measure_overhead`]: crate::Builder::measure_overhead
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Overhead {
+    /// Number of span enters and exits that were recorded.
+    pub events: usize,
+    /// Total time spent recording enters and exits, including lock waits.
+    pub recording: Duration,
+    /// Time spent waiting for locks on the graph, or for room in the channel
+    /// of [`Backend::Channel`].
+    ///
+    /// [`Backend::Channel`]: crate::Backend::Channel
+    pub lock_wait: Duration,
+}
+
+impl AddAssign for Overhead {
+    fn add_assign(&mut self, other: Self) {
+        self.events += other.events;
+        self.recording += other.recording;
+        self.lock_wait += other.lock_wait;
+    }
+}
+
+impl CallGraphSnapshot {
+    /// Returns the overhead of recording the graph, if it was measured.
+    pub fn overhead(&self) -> Option<&Overhead> {
+        self.overhead.as_ref()
+    }
+}
+
+/// Atomic counters behind an [`Overhead`].
+#[derive(Debug, Default)]
+pub(crate) struct OverheadCounters {
+    events: AtomicU64,
+    recording_nanos: AtomicU64,
+    lock_wait_nanos: AtomicU64,
+}
+
+impl OverheadCounters {
+    /// Counts an event, and the time until the returned timer is dropped.
+    pub(crate) fn time_event(&self) -> Timer<'_> {
+        self.events.fetch_add(1, Ordering::Relaxed);
+        Timer::new(&self.recording_nanos)
+    }
+
+    /// Counts the time until the returned timer is dropped as lock wait.
+    pub(crate) fn time_lock_wait(&self) -> Timer<'_> {
+        Timer::new(&self.lock_wait_nanos)
+    }
+
+    pub(crate) fn get(&self) -> Overhead {
+        let duration = |nanos: &AtomicU64| Duration::from_nanos(nanos.load(Ordering::Relaxed));
+        Overhead {
+            events: self.events.load(Ordering::Relaxed) as usize,
+            recording: duration(&self.recording_nanos),
+            lock_wait: duration(&self.lock_wait_nanos),
+        }
+    }
+}
+
+/// Adds the time it was alive to a counter when dropped.
+#[derive(Debug)]
+pub(crate) struct Timer<'a> {
+    nanos: &'a AtomicU64,
+    started: Instant,
+}
+
+impl<'a> Timer<'a> {
+    fn new(nanos: &'a AtomicU64) -> Self {
+        Self {
+            nanos,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for Timer<'_> {
+    fn drop(&mut self) {
+        let elapsed = u64::try_from(self.started.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.nanos.fetch_add(elapsed, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{encoding::varint, CallGraphSnapshot};
+use std::{
+    convert::TryFrom,
+    io::{self, Write},
+};
+
+/// The magic bytes at both ends of a Parquet file.
+const MAGIC: &[u8] = b"PAR1";
+
+// Values of the enums of the Parquet format.
+const INT64: i32 = 2;
+const BYTE_ARRAY: i32 = 6;
+const REQUIRED: i32 = 0;
+const UTF8: i32 = 0;
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+const UNCOMPRESSED: i32 = 0;
+const DATA_PAGE: i32 = 0;
+
+// Types of the Thrift compact protocol.
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+impl CallGraphSnapshot {
+    /// Writes the edges of the snapshot to `writer` as a Parquet table, for
+    /// analysis in DuckDB, Spark or pandas without a conversion step.
+    ///
+    /// The table has a row for every edge, with the `caller` and `callee`
+    /// names, the `count` of calls, their `total_ns` duration in nanoseconds
+    /// and the number of `errors`. It is written as a single uncompressed
+    /// row group.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let snapshot = GraphLayer::builder().record(outer);
+    /// let table = snapshot.to_parquet();
+    /// assert_eq!(table[..4], *b"PAR1");
+    /// assert_eq!(table[table.len() - 4..], *b"PAR1");
+    /// ```
+    ///
+    /// Requires the `parquet` feature.
+    pub fn write_parquet<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(&self.to_parquet())
+    }
+
+    /// Returns the edges of the snapshot as a Parquet table, see
+    /// [`write_parquet`].
+    ///
+    /// [`write_parquet`]: CallGraphSnapshot::write_parquet
+    pub fn to_parquet(&self) -> Vec<u8> {
+        let edges = self.edges();
+        let rows = edges.len() as i64;
+        let name = |node: usize| self.nodes()[node].name.as_str();
+        let int = |value: u128| i64::try_from(value).unwrap_or(i64::MAX);
+        let columns = [
+            (
+                "caller",
+                BYTE_ARRAY,
+                texts(edges.iter().map(|e| name(e.caller))),
+            ),
+            (
+                "callee",
+                BYTE_ARRAY,
+                texts(edges.iter().map(|e| name(e.callee))),
+            ),
+            (
+                "count",
+                INT64,
+                ints(edges.iter().map(|e| int(e.count as u128))),
+            ),
+            (
+                "total_ns",
+                INT64,
+                ints(edges.iter().map(|e| int(e.duration.as_nanos()))),
+            ),
+            (
+                "errors",
+                INT64,
+                ints(edges.iter().map(|e| int(e.errors as u128))),
+            ),
+        ];
+
+        let mut file = MAGIC.to_vec();
+        let mut chunks = Vec::new();
+        let mut total_size = 0;
+        for (name, kind, data) in &columns {
+            let offset = file.len() as i64;
+            let mut data_page = Fields::default();
+            data_page.i32(1, rows as i32);
+            data_page.i32(2, PLAIN);
+            data_page.i32(3, RLE);
+            data_page.i32(4, RLE);
+            let mut page = Fields::default();
+            page.i32(1, DATA_PAGE);
+            page.i32(2, data.len() as i32);
+            page.i32(3, data.len() as i32);
+            page.structure(5, data_page);
+            let page = page.finish();
+            file.extend_from_slice(&page);
+            file.extend_from_slice(data);
+            let size = (page.len() + data.len()) as i64;
+            total_size += size;
+
+            let mut meta = Fields::default();
+            meta.i32(1, *kind);
+            meta.list(2, I32, &[zigzag(PLAIN.into())]);
+            meta.list(3, BINARY, &[binary(name.as_bytes())]);
+            meta.i32(4, UNCOMPRESSED);
+            meta.i64(5, rows);
+            meta.i64(6, size);
+            meta.i64(7, size);
+            meta.i64(9, offset);
+            let mut chunk = Fields::default();
+            chunk.i64(2, offset);
+            chunk.structure(3, meta);
+            chunks.push(chunk.finish());
+        }
+
+        let mut root = Fields::default();
+        root.binary(4, b"schema");
+        root.i32(5, columns.len() as i32);
+        let mut schema = vec![root.finish()];
+        for (name, kind, _) in &columns {
+            let mut element = Fields::default();
+            element.i32(1, *kind);
+            element.i32(3, REQUIRED);
+            element.binary(4, name.as_bytes());
+            if *kind == BYTE_ARRAY {
+                element.i32(6, UTF8);
+            }
+            schema.push(element.finish());
+        }
+        let mut row_group = Fields::default();
+        row_group.list(1, STRUCT, &chunks);
+        row_group.i64(2, total_size);
+        row_group.i64(3, rows);
+        let mut metadata = Fields::default();
+        metadata.i32(1, 1);
+        metadata.list(2, STRUCT, &schema);
+        metadata.i64(3, rows);
+        metadata.list(4, STRUCT, &[row_group.finish()]);
+        metadata.binary(6, b"tracing-callgraph");
+        let metadata = metadata.finish();
+
+        file.extend_from_slice(&metadata);
+        file.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        file.extend_from_slice(MAGIC);
+        file
+    }
+}
+
+/// Returns `values` in the plain encoding of byte arrays.
+fn texts<'a>(values: impl Iterator<Item = &'a str>) -> Vec<u8> {
+    let mut data = Vec::new();
+    for value in values {
+        data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        data.extend_from_slice(value.as_bytes());
+    }
+    data
+}
+
+/// Returns `values` in the plain encoding of 64-bit integers.
+fn ints(values: impl Iterator<Item = i64>) -> Vec<u8> {
+    values.flat_map(i64::to_le_bytes).collect()
+}
+
+/// A struct being encoded in the Thrift compact protocol, as used by the
+/// metadata of Parquet files.
+#[derive(Default)]
+struct Fields {
+    encoded: Vec<u8>,
+    last: i16,
+}
+
+impl Fields {
+    fn header(&mut self, id: i16, kind: u8) {
+        match id - self.last {
+            delta @ 1..=15 => self.encoded.push((delta as u8) << 4 | kind),
+            _ => {
+                self.encoded.push(kind);
+                self.encoded.extend(zigzag(id.into()));
+            }
+        }
+        self.last = id;
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.header(id, I32);
+        self.encoded.extend(zigzag(value.into()));
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.header(id, I64);
+        self.encoded.extend(zigzag(value));
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.header(id, BINARY);
+        self.encoded.extend(binary(value));
+    }
+
+    /// Adds a list of `elements` of type `kind`, each already encoded.
+    fn list(&mut self, id: i16, kind: u8, elements: &[Vec<u8>]) {
+        self.header(id, LIST);
+        match elements.len() {
+            len @ 0..=14 => self.encoded.push((len as u8) << 4 | kind),
+            len => {
+                self.encoded.push(0xf0 | kind);
+                varint(&mut self.encoded, len as u64);
+            }
+        }
+        for element in elements {
+            self.encoded.extend_from_slice(element);
+        }
+    }
+
+    fn structure(&mut self, id: i16, fields: Fields) {
+        self.header(id, STRUCT);
+        self.encoded.extend(fields.finish());
+    }
+
+    /// Returns the encoded struct, ended by a stop field.
+    fn finish(mut self) -> Vec<u8> {
+        self.encoded.push(0);
+        self.encoded
+    }
+}
+
+fn zigzag(value: i64) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    varint(&mut encoded, ((value << 1) ^ (value >> 63)) as u64);
+    encoded
+}
+
+fn binary(value: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    varint(&mut encoded, value.len() as u64);
+    encoded.extend_from_slice(value);
+    encoded
+}
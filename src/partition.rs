@@ -0,0 +1,142 @@
+use crate::{
+    graph::{CallGraph, NodeId},
+    hash::{BuildHasher, HashMap},
+    sync::RwLock,
+    OTHER_NODE,
+};
+use std::{fmt, sync::Arc, time::Duration};
+use tracing::field::{Field, Visit};
+
+/// What the spans are partitioned by.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum PartitionKey {
+    /// The value of a field, see [`Builder::partition_by`].
+    ///
+    /// [`Builder::partition_by`]: crate::Builder::partition_by
+    Field(&'static str),
+    /// The name of the root span, see [`Builder::partition_by_root`].
+    ///
+    /// [`Builder::partition_by_root`]: crate::Builder::partition_by_root
+    Root,
+}
+
+/// The graphs recorded for every key of the partitioning, see
+/// [`Builder::partition_by`] and [`Builder::partition_by_root`].
+///
+/// [`Builder::partition_by`]: crate::Builder::partition_by
+/// [`Builder::partition_by_root`]: crate::Builder::partition_by_root
+#[derive(Debug)]
+pub(crate) struct Partitions {
+    pub(crate) key: PartitionKey,
+    max: usize,
+    hasher: BuildHasher,
+    graphs: RwLock<HashMap<String, Arc<Partition>>>,
+}
+
+impl Partitions {
+    pub(crate) fn new(key: PartitionKey, max: usize, hasher: BuildHasher) -> Self {
+        Self {
+            key,
+            max,
+            graphs: RwLock::new(HashMap::with_hasher(hasher.clone())),
+            hasher,
+        }
+    }
+
+    /// Returns the partition of `key`, or the one shared by all keys beyond
+    /// the maximum, named [`OTHER_NODE`].
+    pub(crate) fn get(&self, key: &str) -> Arc<Partition> {
+        if let Some(partition) = self.graphs.read().get(key) {
+            return partition.clone();
+        }
+        let mut graphs = self.graphs.write();
+        // Only keys other than the overflow count towards the maximum.
+        let keys = graphs.len() - graphs.contains_key(OTHER_NODE) as usize;
+        let key = if graphs.contains_key(key) || keys < self.max {
+            key
+        } else {
+            OTHER_NODE
+        };
+        graphs
+            .entry(key.to_string())
+            .or_insert_with(|| {
+                Arc::new(Partition(RwLock::new(CallGraph::new(self.hasher.clone()))))
+            })
+            .clone()
+    }
+
+    /// Calls `f` with the key and graph of every partition, sorted by key.
+    pub(crate) fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&str, &CallGraph),
+    {
+        let graphs = self.graphs.read();
+        let mut keys: Vec<_> = graphs.keys().collect();
+        keys.sort_unstable();
+        for key in keys {
+            f(key, &graphs[key].0.read());
+        }
+    }
+}
+
+/// The graph of one partition, shared by the spans recorded into it.
+#[derive(Debug)]
+pub(crate) struct Partition(RwLock<CallGraph>);
+
+impl Partition {
+    /// Records `callee` being entered from `caller`, with shared access to
+    /// the graph if the edge was recorded before.
+    pub(crate) fn record_enter(&self, caller: Option<NodeId>, callee: NodeId) {
+        if !self.0.read().try_record_enter(caller, callee) {
+            self.0.write().record_enter(caller, callee);
+        }
+    }
+
+    /// Records `callee` being exited from `caller` after running for
+    /// `elapsed`, like [`record_enter`].
+    ///
+    /// [`record_enter`]: Partition::record_enter
+    pub(crate) fn record_exit(&self, caller: Option<NodeId>, callee: NodeId, elapsed: Duration) {
+        if !self.0.read().try_record_exit(caller, callee, elapsed) {
+            self.0.write().record_exit(caller, callee, elapsed);
+        }
+    }
+}
+
+/// Returns `key` with every character that may not be safe in file names
+/// replaced by `_`.
+pub(crate) fn file_part(key: &str) -> String {
+    key.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Finds the value of the partitioning field among the fields of a span.
+#[derive(Debug)]
+pub(crate) struct KeyVisitor {
+    field: &'static str,
+    pub(crate) key: Option<String>,
+}
+
+impl KeyVisitor {
+    pub(crate) fn new(field: &'static str) -> Self {
+        Self { field, key: None }
+    }
+}
+
+impl Visit for KeyVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == self.field {
+            self.key = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == self.field {
+            self.key = Some(format!("{:?}", value));
+        }
+    }
+}
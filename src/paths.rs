@@ -0,0 +1,270 @@
+use crate::CallGraphSnapshot;
+use petgraph::algo::tarjan_scc;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fmt::Write as _,
+    io::{self, Write},
+    time::Duration,
+};
+
+/// A root-to-leaf path through a [`CallGraphSnapshot`], see
+/// [`CallGraphSnapshot::top_paths`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub struct CallPath {
+    /// The estimated number of times the path was taken.
+    pub count: u64,
+    /// Indices of the nodes along the path, starting at the root.
+    pub nodes: Vec<usize>,
+}
+
+impl CallGraphSnapshot {
+    /// Returns up to `n` of the most frequently taken root-to-leaf paths,
+    /// most frequent first.
+    ///
+    /// Roots are nodes that are never called, and leaves are nodes that call
+    /// nothing, or only nodes already on the path. As the graph only records
+    /// edge counts, the count of a path is estimated as the smallest count of
+    /// any edge along it.
+    ///
+    /// Rather than enumerating every path, of which there can be
+    /// exponentially many, paths are only extended from every node, at every
+    /// position along them, for the `n` most frequent paths reaching it
+    /// there. This is exact for graphs without recursion.
+    ///
+    /// ```rust
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     for _ in 0..3 {
+    ///         hot();
+    ///     }
+    ///     cold();
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn hot() {}
+    ///
+    /// #[tracing::instrument]
+    /// fn cold() {}
+    ///
+    /// let snapshot = tracing_callgraph::with_graph_recording(outer);
+    /// let path = &snapshot.top_paths(1)[0];
+    /// let names: Vec<_> = path.nodes.iter().map(|&i| &snapshot.nodes()[i].name).collect();
+    /// assert_eq!(names, ["outer", "hot"]);
+    /// assert_eq!(path.count, 3);
+    /// ```
+    pub fn top_paths(&self, n: usize) -> Vec<CallPath> {
+        self.best_paths(n, usize::MAX)
+    }
+
+    /// Returns up to `n` of the most frequent paths from a root of at most
+    /// `max_len` nodes that can't be extended, most frequent first, and then
+    /// ordered by the indices of their nodes.
+    ///
+    /// Extending a path can only lower its count, so expanding the most
+    /// frequent partial path first yields complete paths in order. As in a
+    /// k-shortest paths search, paths are only extended from each node at
+    /// each position for the `n` most frequent partial paths reaching it
+    /// there, as any others would be extended no further than those into the
+    /// same suffixes. This keeps the search polynomial, and is exact unless a
+    /// suffix is only simple after a less frequent partial path, in graphs
+    /// with recursion.
+    fn best_paths(&self, n: usize, max_len: usize) -> Vec<CallPath> {
+        let callees = self.callee_lists();
+        let mut called = vec![false; self.nodes().len()];
+        for edge in self.edges() {
+            called[edge.callee] = true;
+        }
+
+        let mut queue: BinaryHeap<_> = (0..self.nodes().len())
+            .filter(|&root| !called[root] && !callees[root].is_empty())
+            .map(|root| CallPath {
+                count: u64::MAX,
+                nodes: vec![root],
+            })
+            .collect();
+
+        let mut extended_from = HashMap::new();
+        let mut paths = Vec::with_capacity(n.min(queue.len()));
+        while paths.len() < n {
+            let path = match queue.pop() {
+                Some(path) => path,
+                None => break,
+            };
+            let last = *path.nodes.last().expect("expected: paths are not empty");
+            let extended_from = extended_from.entry((last, path.nodes.len())).or_insert(0);
+            if *extended_from == n {
+                continue;
+            }
+            *extended_from += 1;
+            let mut extended = false;
+            if path.nodes.len() < max_len {
+                for &(callee, count) in &callees[last] {
+                    if path.nodes.contains(&callee) {
+                        continue;
+                    }
+                    let mut nodes = path.nodes.clone();
+                    nodes.push(callee);
+                    queue.push(CallPath {
+                        count: path.count.min(count),
+                        nodes,
+                    });
+                    extended = true;
+                }
+            }
+            if !extended && path.nodes.len() > 1 {
+                paths.push(path);
+            }
+        }
+        paths.sort_by(|a, b| (Reverse(a.count), &a.nodes).cmp(&(Reverse(b.count), &b.nodes)));
+        paths
+    }
+
+    /// Returns up to `limit` of the most frequent distinct paths from a root
+    /// of at most `max_len` nodes, most frequent first, and then ordered by
+    /// the indices of their nodes.
+    ///
+    /// Paths end at a leaf, before a node already on the path, or once they
+    /// are `max_len` nodes long. Roots and counts are as for [`top_paths`],
+    /// and so is the search, which is bounded by `limit`.
+    ///
+    /// [`top_paths`]: CallGraphSnapshot::top_paths
+    pub fn call_paths(&self, max_len: usize, limit: usize) -> Vec<CallPath> {
+        self.best_paths(limit, max_len)
+    }
+
+    /// Writes up to `limit` of the [`call_paths`] of at most `max_len` nodes
+    /// to `writer`, as sorted `count: root -> ... -> callee` lines.
+    ///
+    /// ```rust
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     for _ in 0..3 {
+    ///         hot();
+    ///     }
+    ///     cold();
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn hot() {
+    ///     leaf()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn cold() {}
+    ///
+    /// #[tracing::instrument]
+    /// fn leaf() {}
+    ///
+    /// let snapshot = tracing_callgraph::with_graph_recording(outer);
+    /// assert_eq!(
+    ///     snapshot.to_paths_report(2, 10),
+    ///     "3: outer -> hot\n1: outer -> cold\n",
+    /// );
+    /// assert_eq!(
+    ///     snapshot.to_paths_report(3, 10),
+    ///     "3: outer -> hot -> leaf\n1: outer -> cold\n",
+    /// );
+    /// assert_eq!(snapshot.to_paths_report(3, 1), "3: outer -> hot -> leaf\n");
+    /// ```
+    ///
+    /// [`call_paths`]: CallGraphSnapshot::call_paths
+    pub fn write_paths_report<W>(
+        &self,
+        mut writer: W,
+        max_len: usize,
+        limit: usize,
+    ) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(self.to_paths_report(max_len, limit).as_bytes())
+    }
+
+    /// Returns the report of [`write_paths_report`].
+    ///
+    /// [`write_paths_report`]: CallGraphSnapshot::write_paths_report
+    pub fn to_paths_report(&self, max_len: usize, limit: usize) -> String {
+        let mut report = String::new();
+        for path in self.call_paths(max_len, limit) {
+            let names: Vec<_> = path
+                .nodes
+                .iter()
+                .map(|&node| self.nodes()[node].name.as_str())
+                .collect();
+            let _ = writeln!(report, "{}: {}", path.count, names.join(" -> "));
+        }
+        report
+    }
+
+    /// Returns the path that accounts for the most time.
+    ///
+    /// This is the longest path through the graph, weighing every call by
+    /// its duration: the path whose calls take the longest in total. Calls
+    /// within a group of mutually recursive nodes, see
+    /// [`recursive_groups`], are not followed, so that the path is simple.
+    /// The result holds node indices, starting at the root, and is empty if
+    /// the graph is.
+    ///
+    /// This can be highlighted in the `dot` output with
+    /// [`DotOptions::with_critical_path`].
+    ///
+    /// [`recursive_groups`]: CallGraphSnapshot::recursive_groups
+    /// [`DotOptions::with_critical_path`]: crate::DotOptions::with_critical_path
+    pub fn critical_path(&self) -> Vec<usize> {
+        // Components come in reverse topological order, callees first.
+        let groups = tarjan_scc(&self.to_graph());
+        let mut group_of = vec![0; self.nodes().len()];
+        for (group, nodes) in groups.iter().enumerate() {
+            for node in nodes {
+                group_of[node.index()] = group;
+            }
+        }
+        let mut callees = vec![Vec::new(); self.nodes().len()];
+        let mut called = vec![false; self.nodes().len()];
+        for edge in self.edges() {
+            if group_of[edge.caller] != group_of[edge.callee] {
+                callees[edge.caller].push((edge.callee, edge.duration));
+                called[edge.callee] = true;
+            }
+        }
+
+        // The longest time along a path from every node, and the next node
+        // on it, preferring lower indices among paths of equal time.
+        let mut longest: Vec<(Duration, Option<usize>)> =
+            vec![(Duration::default(), None); self.nodes().len()];
+        for node in groups.iter().flatten().map(|node| node.index()) {
+            for &(callee, duration) in &callees[node] {
+                let time = duration.saturating_add(longest[callee].0);
+                let (best, next) = longest[node];
+                if time > best || (time == best && next.is_none_or(|next| callee < next)) {
+                    longest[node] = (time, Some(callee));
+                }
+            }
+        }
+
+        let root = (0..self.nodes().len()).max_by_key(|&node| {
+            (
+                longest[node].0,
+                !called[node],
+                self.nodes()[node].duration,
+                Reverse(node),
+            )
+        });
+        let mut path: Vec<_> = root.into_iter().collect();
+        while let Some(next) = path.last().and_then(|&node| longest[node].1) {
+            path.push(next);
+        }
+        path
+    }
+
+    /// Returns the `(callee, count)` pairs of every node.
+    pub(crate) fn callee_lists(&self) -> Vec<Vec<(usize, u64)>> {
+        let mut callees = vec![Vec::new(); self.nodes().len()];
+        for edge in self.edges() {
+            callees[edge.caller].push((edge.callee, edge.count));
+        }
+        callees
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{encoding::varint, weight::accumulate, CallGraphSnapshot};
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    convert::TryFrom,
+    io::{self, Write},
+    time::Duration,
+};
+
+impl CallGraphSnapshot {
+    /// Writes the snapshot to `writer` as a gzipped pprof `profile.proto`,
+    /// for `go tool pprof`, Speedscope and other pprof tooling.
+    ///
+    /// Samples hold a `calls` count and a `time` in nanoseconds. As only
+    /// edges are recorded, stacks are at most two frames deep: the self time
+    /// of every node is split across its callers in proportion to the time
+    /// spent in it from each. Flat times are exact, while cumulative times
+    /// only include direct callees.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// #[tracing::instrument]
+    /// fn work() {}
+    ///
+    /// let snapshot = GraphLayer::builder().record(work);
+    /// let mut profile = Vec::new();
+    /// snapshot.write_pprof(&mut profile).unwrap();
+    /// // Gzip magic bytes, as expected by `go tool pprof`.
+    /// assert_eq!(profile[..2], [0x1f, 0x8b]);
+    /// ```
+    ///
+    /// Requires the `pprof` feature.
+    pub fn write_pprof<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        encoder.write_all(&self.profile())?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    /// Returns the snapshot as a gzipped pprof `profile.proto`, see
+    /// [`write_pprof`].
+    ///
+    /// [`write_pprof`]: CallGraphSnapshot::write_pprof
+    pub fn to_pprof(&self) -> Vec<u8> {
+        let mut pprof = Vec::new();
+        self.write_pprof(&mut pprof)
+            .expect("expected: writing to a Vec never fails");
+        pprof
+    }
+
+    /// Encodes the uncompressed `Profile` message.
+    fn profile(&self) -> Vec<u8> {
+        // Strings 1 to 4 are the sample types, node names follow.
+        const STRINGS: [&str; 5] = ["", "calls", "count", "time", "nanoseconds"];
+        let name = |node: usize| (STRINGS.len() + node) as u64;
+        let id = |node: usize| node as u64 + 1;
+
+        let mut profile = Vec::new();
+        for (kind, unit) in [(1, 2), (3, 4)] {
+            let mut value_type = Vec::new();
+            varint_field(&mut value_type, 1, kind);
+            varint_field(&mut value_type, 2, unit);
+            bytes_field(&mut profile, 1, &value_type);
+        }
+
+        let mut sample = |stack: &[usize], calls: u64, time: Duration| {
+            let locations: Vec<_> = stack.iter().map(|&node| id(node)).collect();
+            let time = u64::try_from(time.as_nanos()).unwrap_or(u64::MAX);
+            let mut encoded = Vec::new();
+            packed_field(&mut encoded, 1, &locations);
+            packed_field(&mut encoded, 2, &[calls, time]);
+            bytes_field(&mut profile, 2, &encoded);
+        };
+        for (node, info) in self.nodes().iter().enumerate() {
+            let mut calls = Duration::default();
+            for edge in self.edges() {
+                if edge.caller == node && edge.callee != node {
+                    accumulate(&mut calls, edge.duration);
+                }
+            }
+            let busy = info.duration.saturating_sub(calls);
+            let incoming: Vec<_> = self
+                .edges()
+                .iter()
+                .filter(|edge| edge.callee == node && edge.caller != node)
+                .collect();
+            let mut incoming_time = Duration::default();
+            let mut incoming_calls = 0;
+            for edge in &incoming {
+                accumulate(&mut incoming_time, edge.duration);
+                accumulate(&mut incoming_calls, edge.count);
+            }
+
+            let mut attributed = Duration::default();
+            for edge in &incoming {
+                let share = if incoming_time.is_zero() {
+                    Duration::default()
+                } else {
+                    let fraction = edge.duration.as_secs_f64() / incoming_time.as_secs_f64();
+                    // Rounding may take the share of a saturated time past
+                    // the largest duration.
+                    Duration::try_from_secs_f64(busy.as_secs_f64() * fraction)
+                        .map_or(busy, |share| share.min(busy))
+                };
+                accumulate(&mut attributed, share);
+                sample(&[node, edge.caller], edge.count, share);
+            }
+            let calls = info.calls.saturating_sub(incoming_calls);
+            let time = busy.saturating_sub(attributed);
+            if calls > 0 || !time.is_zero() || incoming.is_empty() {
+                sample(&[node], calls, time);
+            }
+        }
+
+        for node in 0..self.nodes().len() {
+            let mut line = Vec::new();
+            varint_field(&mut line, 1, id(node));
+            let mut location = Vec::new();
+            varint_field(&mut location, 1, id(node));
+            bytes_field(&mut location, 4, &line);
+            bytes_field(&mut profile, 4, &location);
+        }
+        for node in 0..self.nodes().len() {
+            let mut function = Vec::new();
+            varint_field(&mut function, 1, id(node));
+            varint_field(&mut function, 2, name(node));
+            varint_field(&mut function, 3, name(node));
+            bytes_field(&mut profile, 5, &function);
+        }
+
+        let names = self.nodes().iter().map(|node| node.name.as_str());
+        for string in STRINGS.iter().copied().chain(names) {
+            bytes_field(&mut profile, 6, string.as_bytes());
+        }
+        profile
+    }
+}
+
+fn varint_field(out: &mut Vec<u8>, field: u64, value: u64) {
+    varint(out, field << 3);
+    varint(out, value);
+}
+
+fn bytes_field(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    varint(out, field << 3 | 2);
+    varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn packed_field(out: &mut Vec<u8>, field: u64, values: &[u64]) {
+    let mut packed = Vec::new();
+    for &value in values {
+        varint(&mut packed, value);
+    }
+    bytes_field(out, field, &packed);
+}
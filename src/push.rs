@@ -0,0 +1,206 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, TcpStream, ToSocketAddrs},
+    thread,
+    time::Duration,
+};
+
+/// How long connecting, sending and waiting for a response may take.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A writer POSTing what was written since the previous flush to an HTTP
+/// endpoint on every flush, so that fleets of instrumented binaries can
+/// report their graphs to a central service without collecting files.
+///
+/// Hand it to [`Builder::with_http_push`] to push the snapshot serialized
+/// as JSON with the `serde` feature, or in `dot` representation otherwise.
+/// As any other writer, it is sent the graph in `dot` representation.
+///
+/// Only plain `http://` URLs are supported, as there is no TLS, so push to
+/// a collector or proxy on the local network. Failed requests, and
+/// responses other than `2xx`, are retried with exponential backoff, and
+/// the error of the last attempt is returned. Data still buffered when the
+/// writer is dropped is pushed, ignoring errors.
+///
+/// ```rust
+/// use std::{io::{Read, Write}, net::TcpListener, thread};
+/// use tracing_callgraph::{GraphLayer, HttpPush};
+/// use tracing_subscriber::{prelude::*, Registry};
+///
+/// #[tracing::instrument]
+/// fn handle() {}
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let listener_address = listener.local_addr().unwrap();
+/// let url = format!("http://{}/graphs", listener_address);
+/// let server = thread::spawn(move || {
+///     let (mut connection, _) = listener.accept().unwrap();
+///     let mut request = String::new();
+///     connection.read_to_string(&mut request).unwrap();
+///     connection.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+///     request
+/// });
+///
+/// let push = HttpPush::new(&url).unwrap();
+/// let (layer, guard) = GraphLayer::builder().with_http_push(push);
+/// tracing::subscriber::with_default(Registry::default().with(layer), handle);
+/// drop(guard);
+///
+/// let request = server.join().unwrap();
+/// assert!(request.starts_with("POST /graphs HTTP/1.1\r\n"));
+/// assert!(request.contains(&format!("Host: {}\r\n", listener_address)));
+/// assert!(request.contains("handle"));
+/// ```
+///
+/// [`Builder::with_http_push`]: crate::Builder::with_http_push
+#[derive(Debug)]
+pub struct HttpPush {
+    /// The host and port as written in the URL, sent as the `Host` header.
+    authority: String,
+    host: String,
+    port: u16,
+    path: String,
+    content_type: &'static str,
+    retries: usize,
+    backoff: Duration,
+    buffer: Vec<u8>,
+}
+
+impl HttpPush {
+    /// Returns a writer pushing to `url`, such as
+    /// `http://collector:8080/graphs` or `http://[::1]:8080/graphs`,
+    /// retrying failed requests 3 times after waiting for 500ms, then twice
+    /// as long for every further retry.
+    ///
+    /// Returns an error of kind `InvalidInput` if `url` isn't a plain
+    /// `http://` URL.
+    pub fn new(url: &str) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("not an http:// URL: {}", url),
+            )
+        };
+        let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (authority, path) = match rest.find('/') {
+            Some(start) => (&rest[..start], &rest[start..]),
+            None => (rest, "/"),
+        };
+        // IPv6 addresses are enclosed in brackets, as they contain colons.
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, rest) = bracketed.split_once(']').ok_or_else(invalid)?;
+                (host, rest.strip_prefix(':'))
+            }
+            None => match authority.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        let port = match port {
+            Some(port) => port.parse().map_err(|_| invalid())?,
+            None => 80,
+        };
+        if host.is_empty() || (!authority.starts_with('[') && host.contains(':')) {
+            return Err(invalid());
+        }
+        Ok(Self {
+            authority: authority.to_string(),
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+            content_type: "text/vnd.graphviz",
+            retries: 3,
+            backoff: Duration::from_millis(500),
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Retry a failed request up to `retries` times.
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Wait for `backoff` before the first retry, doubling the wait for
+    /// every further retry.
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sends the pushed data as JSON, as written by [`Builder::with_http_push`]
+    /// with the `serde` feature.
+    ///
+    /// [`Builder::with_http_push`]: crate::Builder::with_http_push
+    #[cfg(feature = "serde")]
+    pub(crate) fn json(mut self) -> Self {
+        self.content_type = "application/json";
+        self
+    }
+
+    /// Sends `body` once, returning an error unless the response is `2xx`.
+    fn post(&self, body: &[u8]) -> io::Result<()> {
+        let address = (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "host not found"))?;
+        let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.path,
+            self.authority,
+            self.content_type,
+            body.len()
+        );
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+        // Let servers reading until the end of the request respond.
+        stream.shutdown(Shutdown::Write)?;
+
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status)?;
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(io::Error::other(format!(
+                "unexpected response: {}",
+                status.trim_end()
+            ))),
+        }
+    }
+}
+
+impl Write for HttpPush {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let body = std::mem::take(&mut self.buffer);
+        let mut backoff = self.backoff;
+        let mut result = self.post(&body);
+        for _ in 0..self.retries {
+            if result.is_ok() {
+                break;
+            }
+            thread::sleep(backoff);
+            backoff = backoff.saturating_mul(2);
+            result = self.post(&body);
+        }
+        result
+    }
+}
+
+impl Drop for HttpPush {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
@@ -0,0 +1,77 @@
+use crate::CallGraphSnapshot;
+
+impl CallGraphSnapshot {
+    /// Returns `true` if `callee` can be reached by a chain of calls starting
+    /// at `caller`.
+    ///
+    /// ```rust
+    /// #[tracing::instrument]
+    /// fn handler() {
+    ///     service()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn service() {
+    ///     database()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn database() {}
+    ///
+    /// let snapshot = tracing_callgraph::with_graph_recording(handler);
+    /// assert!(snapshot.reaches("handler", "database"));
+    /// assert!(!snapshot.reaches("database", "handler"));
+    /// ```
+    pub fn reaches(&self, caller: &str, callee: &str) -> bool {
+        match (self.node_index(caller), self.node_index(callee)) {
+            (Some(caller), Some(callee)) => self.reachable(caller, false)[callee],
+            _ => false,
+        }
+    }
+
+    /// Returns the names of all nodes that transitively call `name`.
+    pub fn callers_of(&self, name: &str) -> Vec<&str> {
+        self.reachable_names(name, true)
+    }
+
+    /// Returns the names of all nodes transitively called by `name`.
+    pub fn callees_of(&self, name: &str) -> Vec<&str> {
+        self.reachable_names(name, false)
+    }
+
+    fn reachable_names(&self, name: &str, reverse: bool) -> Vec<&str> {
+        let start = match self.node_index(name) {
+            Some(start) => start,
+            None => return Vec::new(),
+        };
+        self.reachable(start, reverse)
+            .into_iter()
+            .zip(self.nodes())
+            .filter(|(reachable, _)| *reachable)
+            .map(|(_, node)| node.name.as_str())
+            .collect()
+    }
+
+    /// Marks the nodes reachable from `start` by at least one call, following
+    /// edges backwards if `reverse` is set.
+    pub(crate) fn reachable(&self, start: usize, reverse: bool) -> Vec<bool> {
+        let mut adjacent = vec![Vec::new(); self.nodes().len()];
+        for edge in self.edges() {
+            if reverse {
+                adjacent[edge.callee].push(edge.caller);
+            } else {
+                adjacent[edge.caller].push(edge.callee);
+            }
+        }
+
+        let mut reached = vec![false; self.nodes().len()];
+        let mut stack = adjacent[start].clone();
+        while let Some(node) = stack.pop() {
+            if !reached[node] {
+                reached[node] = true;
+                stack.extend(&adjacent[node]);
+            }
+        }
+        reached
+    }
+}
@@ -0,0 +1,70 @@
+use crate::CallGraphSnapshot;
+
+impl CallGraphSnapshot {
+    /// Returns a copy of the graph without the edges implied by longer chains
+    /// of calls, for a minimal diagram in documentation. The snapshot itself
+    /// keeps every edge.
+    ///
+    /// An edge is dropped if its callee stays reachable from its caller
+    /// without it. Edges are considered in order, so that within cycles,
+    /// where the reduction isn't unique, every node stays reachable from the
+    /// same nodes as before. Nodes and calls of a node to itself are kept.
+    ///
+    /// ```rust
+    /// #[tracing::instrument]
+    /// fn handler() {
+    ///     service();
+    ///     database();
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn service() {
+    ///     database()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn database() {}
+    ///
+    /// let snapshot = tracing_callgraph::with_graph_recording(handler);
+    /// let reduced = snapshot.transitive_reduction();
+    /// assert!(reduced.edge("handler", "database").is_none());
+    /// assert!(reduced.edge("service", "database").is_some());
+    /// assert!(snapshot.edge("handler", "database").is_some());
+    /// ```
+    pub fn transitive_reduction(&self) -> CallGraphSnapshot {
+        let mut outgoing = vec![Vec::new(); self.nodes().len()];
+        for (index, edge) in self.edges().iter().enumerate() {
+            if edge.caller != edge.callee {
+                outgoing[edge.caller].push(index);
+            }
+        }
+
+        let mut dropped = vec![false; self.edges().len()];
+        for (index, edge) in self.edges().iter().enumerate() {
+            if edge.caller == edge.callee {
+                continue;
+            }
+            let mut reached = vec![false; self.nodes().len()];
+            let mut stack = vec![edge.caller];
+            while let Some(node) = stack.pop() {
+                for &next in &outgoing[node] {
+                    let callee = self.edges()[next].callee;
+                    if next != index && !dropped[next] && !reached[callee] {
+                        reached[callee] = true;
+                        stack.push(callee);
+                    }
+                }
+            }
+            dropped[index] = reached[edge.callee];
+        }
+
+        let edges = self
+            .edges()
+            .iter()
+            .zip(dropped)
+            .filter(|(_, dropped)| !dropped)
+            .map(|(edge, _)| edge.clone())
+            .collect();
+        CallGraphSnapshot::from_parts(self.nodes().to_vec(), edges)
+    }
+}
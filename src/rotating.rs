@@ -0,0 +1,134 @@
+use crate::{
+    time::{self, SystemTime},
+    AtomicFile,
+};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// When a [`RotatingFile`] moves on to the next file of its series.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Rotation {
+    /// Write every flush to a new numbered file, such as `output.1.dot`,
+    /// `output.2.dot` and so on.
+    EveryFlush,
+    /// Write to one file per hour, in UTC, such as `output.2024-05-01T13.dot`.
+    /// Later flushes within the hour replace the file.
+    Hourly,
+    /// Append every flush to a numbered file, such as `output.1.dot`, moving
+    /// on to the next one once appending would grow the file beyond the
+    /// given number of bytes.
+    MaxSize(u64),
+}
+
+/// A writer producing a series of files from the output of successive
+/// flushes, as configured by a [`Rotation`].
+///
+/// This suits long-running services flushing their graph periodically, with
+/// [`FlushGuard::flush`], keeping the graph of every period. Files are
+/// replaced atomically, see [`AtomicFile`], except when appending for
+/// [`Rotation::MaxSize`].
+///
+/// ```rust
+/// use std::io::Write;
+/// use tracing_callgraph::{RotatingFile, Rotation};
+///
+/// let dir = std::env::temp_dir();
+/// let mut file = RotatingFile::new(dir.join("rotating-example.dot"), Rotation::EveryFlush);
+/// for _ in 0..2 {
+///     file.write_all(b"digraph {\n}\n").unwrap();
+///     file.flush().unwrap();
+/// }
+/// assert!(dir.join("rotating-example.1.dot").exists());
+/// assert!(dir.join("rotating-example.2.dot").exists());
+/// ```
+///
+/// [`FlushGuard::flush`]: crate::FlushGuard::flush
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    index: usize,
+    buffer: Vec<u8>,
+}
+
+impl RotatingFile {
+    /// Returns a writer producing files named after `path`, with a number or
+    /// timestamp inserted before the extension.
+    pub fn new(path: impl Into<PathBuf>, rotation: Rotation) -> Self {
+        Self {
+            path: path.into(),
+            rotation,
+            index: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Returns the path of the file written by `flush` when it moves on to
+    /// the next file for [`Rotation::MaxSize`].
+    fn next(&mut self) -> PathBuf {
+        self.index += 1;
+        rotated(&self.path, &self.index.to_string())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let output = std::mem::take(&mut self.buffer);
+        let path = match self.rotation {
+            Rotation::EveryFlush => self.next(),
+            Rotation::Hourly => rotated(&self.path, &utc_hour(SystemTime::now())),
+            Rotation::MaxSize(max) => {
+                let mut path = rotated(&self.path, &self.index.max(1).to_string());
+                let size = fs::metadata(&path).map_or(0, |metadata| metadata.len());
+                if self.index == 0 || (size > 0 && size + output.len() as u64 > max) {
+                    path = self.next();
+                }
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                return file.write_all(&output);
+            }
+        };
+        let mut file = AtomicFile::create(path)?;
+        file.write_all(&output)?;
+        file.flush()
+    }
+}
+
+impl Drop for RotatingFile {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Returns `path` with `part` inserted before its extension.
+pub(crate) fn rotated(path: &Path, part: &str) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(part);
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// Formats the hour of `time` in UTC, as `YYYY-MM-DDTHH`.
+fn utc_hour(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let mut hour = time::utc(seconds);
+    hour.truncate("YYYY-MM-DDTHH".len());
+    hour
+}
@@ -0,0 +1,252 @@
+use crate::{
+    error::{Error, Kind},
+    CallGraphSnapshot, NodeInfo,
+};
+use std::{fmt, fs, path::Path};
+
+/// A specification of the edges a graph may contain, for checking the
+/// architecture of an application against the calls it actually makes.
+///
+/// Rules match the caller and callee of an edge by patterns, where `*`
+/// matches any run of characters. A pattern matches a node if it matches its
+/// name, or its target and span name, such as `app::db::query`, if the node
+/// was recorded from spans. An edge violates the rules if it matches a `deny`
+/// rule, or if there are `allow` rules and it matches none of them.
+///
+/// ```rust
+/// use tracing_callgraph::EdgeRules;
+///
+/// mod api {
+///     #[tracing::instrument]
+///     pub fn list_users() {
+///         crate::db::query()
+///     }
+/// }
+///
+/// mod db {
+///     #[tracing::instrument]
+///     pub fn query() {}
+/// }
+///
+/// fn main() {
+///     let rules = EdgeRules::new().deny("*::api::*", "*::db::*");
+///     let snapshot = tracing_callgraph::with_graph_recording(api::list_users);
+///     let violations = snapshot.violations(&rules);
+///     assert_eq!(violations[0].caller, "list_users");
+///     assert_eq!(violations[0].callee, "query");
+///     assert!(snapshot.check_rules(&rules).is_err());
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EdgeRules {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    allow: bool,
+    caller: String,
+    callee: String,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = if self.allow { "allow" } else { "deny" };
+        write!(f, "{} {} -> {}", action, self.caller, self.callee)
+    }
+}
+
+/// An edge violating [`EdgeRules`], see [`CallGraphSnapshot::violations`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Violation {
+    /// Name of the calling node.
+    pub caller: String,
+    /// Name of the called node.
+    pub callee: String,
+    /// Number of times the callee was entered from the caller.
+    pub count: u64,
+    /// The `deny` rule matching the edge, such as `deny api::* -> db::*`, or
+    /// `None` if it matched no `allow` rule.
+    pub rule: Option<String>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}: {}", self.caller, self.callee, self.count)?;
+        match &self.rule {
+            Some(rule) => write!(f, " ({})", rule),
+            None => write!(f, " (not allowed)"),
+        }
+    }
+}
+
+impl EdgeRules {
+    /// Returns rules allowing every edge.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Allow calls from nodes matching `caller` to nodes matching `callee`.
+    pub fn allow(mut self, caller: impl Into<String>, callee: impl Into<String>) -> Self {
+        self.rules.push(Rule {
+            allow: true,
+            caller: caller.into(),
+            callee: callee.into(),
+        });
+        self
+    }
+
+    /// Deny calls from nodes matching `caller` to nodes matching `callee`,
+    /// even if they are allowed by another rule.
+    pub fn deny(mut self, caller: impl Into<String>, callee: impl Into<String>) -> Self {
+        self.rules.push(Rule {
+            allow: false,
+            caller: caller.into(),
+            callee: callee.into(),
+        });
+        self
+    }
+
+    /// Parses rules from `spec`, which holds one `allow <caller> -> <callee>`
+    /// or `deny <caller> -> <callee>` rule per line. Empty lines and lines
+    /// starting with `#` are ignored.
+    ///
+    /// ```rust
+    /// let rules = tracing_callgraph::EdgeRules::parse(
+    ///     "# The API only talks to services.
+    ///     allow *::api::* -> *::service::*
+    ///     allow *::service::* -> *",
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn parse(spec: &str) -> Result<Self, Error> {
+        let mut rules = Self::new();
+        for (index, line) in spec.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = || Error(Kind::ParseRules { line: index + 1 });
+
+            let (action, edge) = line.split_once(char::is_whitespace).ok_or_else(error)?;
+            let (caller, callee) = edge.split_once("->").ok_or_else(error)?;
+            let (caller, callee) = (caller.trim(), callee.trim());
+            if caller.is_empty() || callee.is_empty() {
+                return Err(error());
+            }
+            rules = match action {
+                "allow" => rules.allow(caller, callee),
+                "deny" => rules.deny(caller, callee),
+                _ => return Err(error()),
+            };
+        }
+        Ok(rules)
+    }
+
+    /// Reads and parses the rules in the file at `path`, see [`parse`].
+    ///
+    /// [`parse`]: EdgeRules::parse
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let spec = fs::read_to_string(path)
+            .map_err(|source| Kind::ReadFile {
+                source,
+                path: path.into(),
+            })
+            .map_err(Error)?;
+        Self::parse(&spec)
+    }
+
+    /// Returns `None` if the edge from `caller` to `callee` is allowed, and
+    /// the `deny` rule it matches otherwise, if any.
+    fn check(&self, caller: &NodeInfo, callee: &NodeInfo) -> Option<Option<&Rule>> {
+        let matching =
+            |rule: &&Rule| matches(&rule.caller, caller) && matches(&rule.callee, callee);
+        if let Some(rule) = self.rules.iter().filter(|rule| !rule.allow).find(matching) {
+            return Some(Some(rule));
+        }
+        let mut allows = self.rules.iter().filter(|rule| rule.allow).peekable();
+        if allows.peek().is_some() && !allows.any(|rule| matching(&rule)) {
+            return Some(None);
+        }
+        None
+    }
+}
+
+impl CallGraphSnapshot {
+    /// Returns every edge violating `rules`, in the order of [`edges`].
+    ///
+    /// [`edges`]: CallGraphSnapshot::edges
+    pub fn violations(&self, rules: &EdgeRules) -> Vec<Violation> {
+        self.indexed_violations(rules)
+            .into_iter()
+            .map(|(_, violation)| violation)
+            .collect()
+    }
+
+    /// Returns every edge violating `rules`, with its index into [`edges`].
+    ///
+    /// [`edges`]: CallGraphSnapshot::edges
+    pub(crate) fn indexed_violations(&self, rules: &EdgeRules) -> Vec<(usize, Violation)> {
+        self.edges()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, edge)| {
+                let caller = &self.nodes()[edge.caller];
+                let callee = &self.nodes()[edge.callee];
+                let rule = rules.check(caller, callee)?;
+                let violation = Violation {
+                    caller: caller.name.clone(),
+                    callee: callee.name.clone(),
+                    count: edge.count,
+                    rule: rule.map(Rule::to_string),
+                };
+                Some((index, violation))
+            })
+            .collect()
+    }
+
+    /// Returns an error listing the edges violating `rules`, if any.
+    pub fn check_rules(&self, rules: &EdgeRules) -> Result<(), Error> {
+        let violations = self.violations(rules);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error(Kind::Violations(violations)))
+        }
+    }
+}
+
+/// Returns whether `pattern` matches the name of `node`, or its target and
+/// span name.
+fn matches(pattern: &str, node: &NodeInfo) -> bool {
+    glob(pattern, &node.name)
+        || node.callsite.as_ref().is_some_and(|callsite| {
+            glob(pattern, &format!("{}::{}", callsite.target, callsite.name))
+        })
+}
+
+/// Returns whether `text` matches `pattern`, in which `*` matches any run of
+/// characters.
+fn glob(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match text.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let mut parts: Vec<_> = parts.collect();
+    let last = match parts.pop() {
+        Some(last) => last,
+        // Without a wildcard, the whole text must match.
+        None => return rest.is_empty(),
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(start) => rest = &rest[start + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
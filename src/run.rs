@@ -0,0 +1,131 @@
+use crate::{
+    time::{self, SystemTime},
+    CallGraphSnapshot,
+};
+use std::{env, fs, path::Path};
+
+/// Where and how a graph was recorded, embedded in the output of a
+/// [`GraphLayer`] configured with [`Builder::run_metadata`], so that archived
+/// graphs remain interpretable long after the run.
+///
+/// [`GraphLayer`]: crate::GraphLayer
+/// [`Builder::run_metadata`]: crate::Builder::run_metadata
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct RunInfo {
+    /// File name of the running binary.
+    pub binary: String,
+    /// Version of the binary, as given to [`Builder::run_metadata`].
+    ///
+    /// [`Builder::run_metadata`]: crate::Builder::run_metadata
+    pub version: String,
+    /// Arguments the binary was started with, separated by spaces.
+    pub command_line: String,
+    /// Name of the host, if it could be found.
+    pub hostname: Option<String>,
+    /// Seconds since the Unix epoch at which the layer was built.
+    pub started: u64,
+    /// Seconds since the Unix epoch at which the graph was taken.
+    pub ended: u64,
+    /// Configuration of the layer, in its `Debug` representation.
+    pub configuration: String,
+}
+
+impl RunInfo {
+    /// Describes the current process, started now.
+    pub(crate) fn start(version: String, configuration: String) -> Self {
+        let mut args = env::args();
+        let binary = args
+            .next()
+            .map(|path| match Path::new(&path).file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => path.clone(),
+            })
+            .unwrap_or_default();
+        let started = now();
+        Self {
+            binary,
+            version,
+            command_line: args.collect::<Vec<_>>().join(" "),
+            hostname: hostname(),
+            started,
+            ended: started,
+            configuration,
+        }
+    }
+
+    /// Returns the run information as it is now.
+    pub(crate) fn ended(&self) -> Self {
+        Self {
+            ended: now(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the fields in the order written after `dot` output, with
+    /// their names.
+    pub(crate) fn fields(&self) -> [(&'static str, String); 7] {
+        [
+            ("binary", self.binary.clone()),
+            ("version", self.version.clone()),
+            ("command line", self.command_line.clone()),
+            ("hostname", self.hostname.clone().unwrap_or_default()),
+            ("started", timestamp(self.started)),
+            ("ended", timestamp(self.ended)),
+            ("configuration", self.configuration.clone()),
+        ]
+    }
+
+    /// Sets the field called `name` from its representation in `dot`
+    /// output, returning whether it is one.
+    pub(crate) fn set(&mut self, name: &str, value: &str) -> bool {
+        let seconds = || {
+            value
+                .split_whitespace()
+                .next()
+                .and_then(|seconds| seconds.parse().ok())
+                .unwrap_or(0)
+        };
+        match name {
+            "binary" => self.binary = value.to_string(),
+            "version" => self.version = value.to_string(),
+            "command line" => self.command_line = value.to_string(),
+            "hostname" if value.is_empty() => self.hostname = None,
+            "hostname" => self.hostname = Some(value.to_string()),
+            "started" => self.started = seconds(),
+            "ended" => self.ended = seconds(),
+            "configuration" => self.configuration = value.to_string(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl CallGraphSnapshot {
+    /// Returns where and how the graph was recorded, if the layer was
+    /// configured to tell.
+    pub fn run(&self) -> Option<&RunInfo> {
+        self.run.as_ref()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Formats `seconds` since the epoch both as is and as a UTC date.
+fn timestamp(seconds: u64) -> String {
+    format!("{} ({})", seconds, time::utc(seconds))
+}
+
+fn hostname() -> Option<String> {
+    env::var("HOSTNAME")
+        .or_else(|_| env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
@@ -0,0 +1,103 @@
+use crate::{snapshot::min_order, weight::accumulate, CallGraphSnapshot, EdgeInfo, NodeInfo};
+use petgraph::algo::tarjan_scc;
+use std::{collections::HashMap, time::Duration};
+
+impl CallGraphSnapshot {
+    /// Returns the groups of mutually recursive nodes.
+    ///
+    /// Each group is a strongly connected component of the graph that either
+    /// holds several nodes or a single node calling itself. Groups hold node
+    /// indices in ascending order, and are sorted by their first node.
+    pub fn recursive_groups(&self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<_> = tarjan_scc(&self.to_graph())
+            .into_iter()
+            .map(|group| {
+                let mut group: Vec<_> = group.into_iter().map(|node| node.index()).collect();
+                group.sort_unstable();
+                group
+            })
+            .filter(|group| match group.as_slice() {
+                [node] => self
+                    .edges()
+                    .iter()
+                    .any(|e| e.caller == *node && e.callee == *node),
+                _ => true,
+            })
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    /// Returns a copy of the graph where every group of mutually recursive
+    /// nodes is collapsed into a single node.
+    ///
+    /// A collapsed node is named after its members, joined by `" | "`, and
+    /// sums their calls and durations. Calls within a group are kept as an
+    /// edge from the collapsed node to itself, and calls between groups are
+    /// summed into a single edge.
+    pub fn condensed(&self) -> CallGraphSnapshot {
+        let mut group_of: Vec<_> = (0..self.nodes().len()).collect();
+        let mut nodes: Vec<_> = self.nodes().iter().cloned().map(Some).collect();
+        for group in self.recursive_groups() {
+            let name = group
+                .iter()
+                .map(|&node| self.nodes()[node].name.as_str())
+                .collect::<Vec<_>>()
+                .join(" | ");
+            let mut collapsed = NodeInfo {
+                name,
+                calls: 0,
+                duration: Duration::default(),
+                callsite: None,
+                sla_violations: 0,
+            };
+            for &node in &group {
+                accumulate(&mut collapsed.calls, self.nodes()[node].calls);
+                accumulate(&mut collapsed.duration, self.nodes()[node].duration);
+                accumulate(
+                    &mut collapsed.sla_violations,
+                    self.nodes()[node].sla_violations,
+                );
+                group_of[node] = group[0];
+                nodes[node] = None;
+            }
+            nodes[group[0]] = Some(collapsed);
+        }
+
+        let mut index_of = vec![0; nodes.len()];
+        let mut next = 0;
+        for (node, info) in nodes.iter().enumerate() {
+            if info.is_some() {
+                index_of[node] = next;
+                next += 1;
+            }
+        }
+
+        let mut edges: HashMap<(usize, usize), EdgeInfo> = HashMap::new();
+        for edge in self.edges() {
+            let caller = index_of[group_of[edge.caller]];
+            let callee = index_of[group_of[edge.callee]];
+            let merged = edges.entry((caller, callee)).or_insert(EdgeInfo {
+                caller,
+                callee,
+                count: 0,
+                duration: Duration::default(),
+                order: None,
+                errors: 0,
+                sla_violations: 0,
+                value: None,
+            });
+            accumulate(&mut merged.count, edge.count);
+            accumulate(&mut merged.duration, edge.duration);
+            merged.order = min_order(merged.order, edge.order);
+            accumulate(&mut merged.errors, edge.errors);
+            accumulate(&mut merged.sla_violations, edge.sla_violations);
+            merged.value = merged.value.max(edge.value);
+        }
+
+        CallGraphSnapshot::from_parts(
+            nodes.into_iter().flatten().collect(),
+            edges.into_values().collect(),
+        )
+    }
+}
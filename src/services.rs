@@ -0,0 +1,79 @@
+use crate::{external::EXTERNAL_PREFIX, CallGraphSnapshot, EdgeInfo, SERVICE_SEPARATOR};
+
+impl CallGraphSnapshot {
+    /// Merges the snapshots of several services into one cross-service
+    /// graph, showing the end-to-end call structure.
+    ///
+    /// Spans are kept apart by the service they were tagged with, see
+    /// [`Builder::service_name`]. Calls between services are found through
+    /// the external nodes recorded with [`Builder::external_calls`]: the
+    /// node `external: orders` gains an edge to every root of the service
+    /// `orders`, that is every one of its nodes which nothing calls.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::{CallGraphSnapshot, GraphLayer};
+    ///
+    /// #[tracing::instrument(fields(peer.service = "orders"))]
+    /// fn place_order() {}
+    ///
+    /// #[tracing::instrument]
+    /// fn create_order() {}
+    ///
+    /// let shop = GraphLayer::builder()
+    ///     .service_name("shop")
+    ///     .external_calls(true)
+    ///     .record(place_order);
+    /// let orders = GraphLayer::builder()
+    ///     .service_name("orders")
+    ///     .record(create_order);
+    ///
+    /// let graph = CallGraphSnapshot::merge_services(&[shop, orders]);
+    /// assert!(graph.edge("shop/place_order", "external: orders").is_some());
+    /// assert!(graph.edge("external: orders", "orders/create_order").is_some());
+    /// ```
+    ///
+    /// [`Builder::service_name`]: crate::Builder::service_name
+    /// [`Builder::external_calls`]: crate::Builder::external_calls
+    pub fn merge_services<'a, I>(snapshots: I) -> CallGraphSnapshot
+    where
+        I: IntoIterator<Item = &'a CallGraphSnapshot>,
+    {
+        let mut merged = CallGraphSnapshot::default();
+        for snapshot in snapshots {
+            merged.merge(snapshot);
+        }
+
+        let mut called = vec![false; merged.nodes().len()];
+        for edge in merged.edges() {
+            called[edge.callee] = true;
+        }
+        let mut edges = merged.edges().to_vec();
+        for (caller, node) in merged.nodes().iter().enumerate() {
+            let service = match node.name.strip_prefix(EXTERNAL_PREFIX) {
+                Some(service) => service,
+                None => continue,
+            };
+            let roots = merged.nodes().iter().enumerate().filter(|(index, root)| {
+                !called[*index]
+                    && root
+                        .name
+                        .strip_prefix(service)
+                        .is_some_and(|name| name.starts_with(SERVICE_SEPARATOR))
+            });
+            edges.extend(roots.map(|(callee, root)| EdgeInfo {
+                caller,
+                callee,
+                count: root.calls,
+                duration: root.duration,
+                order: None,
+                errors: 0,
+                sla_violations: 0,
+                value: None,
+            }));
+        }
+
+        let mut linked = CallGraphSnapshot::from_parts(merged.nodes().to_vec(), edges);
+        linked.overhead = merged.overhead;
+        linked
+    }
+}
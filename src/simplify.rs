@@ -0,0 +1,56 @@
+use std::borrow::Cow;
+
+/// How generic parameters and closures in span names are simplified, see
+/// [`Builder::simplify_names`].
+///
+/// [`Builder::simplify_names`]: crate::Builder::simplify_names
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Simplification {
+    /// Keep span names as they are.
+    #[default]
+    Keep,
+    /// Strip generic parameters and closure segments, numbering names that
+    /// only differed in them, such as `decode #2`.
+    Strip,
+    /// Strip generic parameters and closure segments, merging monomorphized
+    /// variants of a span into one node.
+    Merge,
+}
+
+/// Strips generic parameters, such as `<T>` and `::<u8>`, and closure
+/// segments, such as `::{{closure}}` and `::{closure#0}`, from `name`.
+///
+/// Names that would be left empty are kept as they are.
+pub(crate) fn simplify(name: &str) -> Cow<'_, str> {
+    if !name.contains(['<', '{']) {
+        return Cow::Borrowed(name);
+    }
+
+    let mut stripped = String::with_capacity(name.len());
+    let mut depth = 0usize;
+    let mut previous = None;
+    for c in name.chars() {
+        match c {
+            '<' => depth += 1,
+            // The arrow of a function type doesn't close a parameter list.
+            '>' if depth > 0 && previous != Some('-') => depth -= 1,
+            _ if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+        previous = Some(c);
+    }
+
+    let segments: Vec<_> = stripped
+        .split("::")
+        .filter(|segment| !segment.is_empty() && !is_closure(segment))
+        .collect();
+    if segments.is_empty() {
+        return Cow::Borrowed(name);
+    }
+    Cow::Owned(segments.join("::"))
+}
+
+fn is_closure(segment: &str) -> bool {
+    segment == "{{closure}}" || segment.starts_with("{closure#") && segment.ends_with('}')
+}
@@ -0,0 +1,42 @@
+use crate::{
+    graph::NodeId,
+    hash::{BuildHasher, HashMap},
+    interner::Interner,
+    sync::RwLock,
+};
+use std::{collections::HashMap as StdHashMap, time::Duration};
+
+/// The duration thresholds of nodes, see [`Builder::sla`].
+///
+/// [`Builder::sla`]: crate::Builder::sla
+#[derive(Debug)]
+pub(crate) struct Slas {
+    thresholds: StdHashMap<String, Duration>,
+    /// The threshold of every node looked up so far, so that exits don't
+    /// need its name.
+    nodes: RwLock<HashMap<NodeId, Option<Duration>>>,
+}
+
+impl Slas {
+    pub(crate) fn new(thresholds: &[(String, Duration)], hasher: BuildHasher) -> Self {
+        Self {
+            thresholds: thresholds.iter().cloned().collect(),
+            nodes: RwLock::new(HashMap::with_hasher(hasher)),
+        }
+    }
+
+    /// Returns whether `node`, named by `names`, running for `elapsed`
+    /// exceeded its threshold.
+    pub(crate) fn exceeded(&self, node: NodeId, elapsed: Duration, names: &Interner) -> bool {
+        let cached = self.nodes.read().get(&node).copied();
+        let threshold = match cached {
+            Some(threshold) => threshold,
+            None => {
+                let threshold = self.thresholds.get(names.name(node).as_ref()).copied();
+                self.nodes.write().insert(node, threshold);
+                threshold
+            }
+        };
+        threshold.is_some_and(|threshold| elapsed > threshold)
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{
+    error::{Error, Kind},
+    sync::Mutex,
+    time::Instant,
+    CallGraphSnapshot, DotOptions, EdgeInfo, NodeInfo, RotatingFile,
+};
+use std::{io::Write, time::Duration};
+
+/// When a [`GraphLayer`] ends a time slice of its graph, see
+/// [`Builder::time_slices`].
+///
+/// Slices other than [`Slicing::Frames`] end when a root span exits, so that
+/// no call is split between two of them.
+///
+/// [`GraphLayer`]: crate::GraphLayer
+/// [`Builder::time_slices`]: crate::Builder::time_slices
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Slicing {
+    /// End a slice when the first root span exits after the given time has
+    /// passed since the slice started.
+    Interval(Duration),
+    /// End a slice once the given number of root spans exited.
+    RootSpans(usize),
+    /// End a slice once the given number of frames ended, as marked by
+    /// [`GraphLayer::end_frame`], such as `Frames(1)` for a graph per frame.
+    ///
+    /// [`GraphLayer::end_frame`]: crate::GraphLayer::end_frame
+    Frames(usize),
+}
+
+/// The time slices of a graph, written to a numbered series of files.
+#[derive(Debug)]
+pub(crate) struct Slices {
+    slicing: Slicing,
+    dot_options: DotOptions,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    started: Instant,
+    roots: usize,
+    frames: usize,
+    /// The graph when the current slice started.
+    previous: CallGraphSnapshot,
+    writer: RotatingFile,
+}
+
+impl Slices {
+    pub(crate) fn new(slicing: Slicing, writer: RotatingFile, dot_options: DotOptions) -> Self {
+        Self {
+            slicing,
+            dot_options,
+            state: Mutex::new(State {
+                started: Instant::now(),
+                roots: 0,
+                frames: 0,
+                previous: CallGraphSnapshot::default(),
+                writer,
+            }),
+        }
+    }
+
+    /// Counts a root span exiting, and writes the current slice if that ends
+    /// it, taking the `snapshot` of the graph only then.
+    pub(crate) fn root_exited<F>(&self, snapshot: F)
+    where
+        F: FnOnce() -> CallGraphSnapshot,
+    {
+        let mut state = self.state.lock();
+        state.roots += 1;
+        let ended = match self.slicing {
+            Slicing::Interval(interval) => state.started.elapsed() >= interval,
+            Slicing::RootSpans(roots) => state.roots >= roots.max(1),
+            Slicing::Frames(_) => false,
+        };
+        if ended {
+            self.end(&mut state, snapshot());
+        }
+    }
+
+    /// Counts a frame ending, and writes the current slice if that ends it,
+    /// like [`root_exited`].
+    ///
+    /// [`root_exited`]: Slices::root_exited
+    pub(crate) fn frame_ended<F>(&self, snapshot: F)
+    where
+        F: FnOnce() -> CallGraphSnapshot,
+    {
+        let mut state = self.state.lock();
+        state.frames += 1;
+        if let Slicing::Frames(frames) = self.slicing {
+            if state.frames >= frames.max(1) {
+                self.end(&mut state, snapshot());
+            }
+        }
+    }
+
+    /// Writes the current slice, if anything was recorded in it.
+    pub(crate) fn finish(&self, snapshot: CallGraphSnapshot) {
+        let mut state = self.state.lock();
+        self.end(&mut state, snapshot);
+    }
+
+    fn end(&self, state: &mut State, snapshot: CallGraphSnapshot) {
+        let slice = snapshot.since(&state.previous);
+        state.started = Instant::now();
+        state.roots = 0;
+        state.frames = 0;
+        state.previous = snapshot;
+        if slice.nodes().is_empty() {
+            return;
+        }
+        let result = slice
+            .write_dot_with(&mut state.writer, &self.dot_options)
+            .and_then(|()| state.writer.flush());
+        if let Err(error) = result {
+            Error(Kind::FlushFile(error)).report();
+        }
+    }
+}
+
+impl CallGraphSnapshot {
+    /// Returns the calls recorded since `previous`, an earlier snapshot of
+    /// the same graph, keeping only the nodes and edges called since.
+    pub(crate) fn since(&self, previous: &CallGraphSnapshot) -> CallGraphSnapshot {
+        let mut used = vec![false; self.nodes().len()];
+        let mut edges = Vec::new();
+        for edge in self.edges() {
+            let caller = &self.nodes()[edge.caller].name;
+            let callee = &self.nodes()[edge.callee].name;
+            let (count, duration, errors, sla_violations) = match previous.edge(caller, callee) {
+                Some(before) => (
+                    edge.count.saturating_sub(before.count),
+                    edge.duration.saturating_sub(before.duration),
+                    edge.errors.saturating_sub(before.errors),
+                    edge.sla_violations.saturating_sub(before.sla_violations),
+                ),
+                None => (edge.count, edge.duration, edge.errors, edge.sla_violations),
+            };
+            if count > 0 || errors > 0 {
+                used[edge.caller] = true;
+                used[edge.callee] = true;
+                edges.push(EdgeInfo {
+                    count,
+                    duration,
+                    errors,
+                    sla_violations,
+                    ..edge.clone()
+                });
+            }
+        }
+
+        let mut indices = vec![0; self.nodes().len()];
+        let mut nodes = Vec::new();
+        for (index, node) in self.nodes().iter().enumerate() {
+            let (calls, duration, sla_violations) = match previous.node_index(&node.name) {
+                Some(before) => {
+                    let before = &previous.nodes()[before];
+                    (
+                        node.calls.saturating_sub(before.calls),
+                        node.duration.saturating_sub(before.duration),
+                        node.sla_violations.saturating_sub(before.sla_violations),
+                    )
+                }
+                None => (node.calls, node.duration, node.sla_violations),
+            };
+            if calls > 0 || used[index] {
+                indices[index] = nodes.len();
+                nodes.push(NodeInfo {
+                    calls,
+                    duration,
+                    sla_violations,
+                    ..node.clone()
+                });
+            }
+        }
+        for edge in &mut edges {
+            edge.caller = indices[edge.caller];
+            edge.callee = indices[edge.callee];
+        }
+        CallGraphSnapshot::from_parts(nodes, edges)
+    }
+}
@@ -0,0 +1,504 @@
+use crate::{aggregation::Aggregates, graph::CallGraph, weight::accumulate, Overhead, RunInfo};
+use petgraph::graph::{DiGraph, NodeIndex};
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+use std::{collections::HashMap, time::Duration};
+
+/// A node in a [`CallGraphSnapshot`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct NodeInfo {
+    /// The span name identifying the node.
+    pub name: String,
+    /// Number of times the node was entered.
+    pub calls: u64,
+    /// Total time spent inside the node, from entering to exiting it.
+    pub duration: Duration,
+    /// Number of times the node ran for longer than its threshold, if one
+    /// was set with [`Builder::sla`].
+    ///
+    /// [`Builder::sla`]: crate::Builder::sla
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sla_violations: u64,
+    /// Where the first span recorded as the node was created, if the node
+    /// was recorded from spans.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub callsite: Option<CallsiteInfo>,
+}
+
+/// Where the spans of a node were created, see [`NodeInfo::callsite`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct CallsiteInfo {
+    /// The target of the spans, usually the module path they were created in.
+    pub target: String,
+    /// The name of the spans, simplified as configured by
+    /// [`Builder::simplify_names`] but not disambiguated.
+    ///
+    /// [`Builder::simplify_names`]: crate::Builder::simplify_names
+    pub name: String,
+    /// The source file the spans were created in, if known.
+    pub file: Option<String>,
+    /// The line the spans were created at, if known.
+    pub line: Option<u32>,
+}
+
+/// A directed edge in a [`CallGraphSnapshot`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct EdgeInfo {
+    /// Index of the calling node in [`CallGraphSnapshot::nodes`].
+    pub caller: usize,
+    /// Index of the called node in [`CallGraphSnapshot::nodes`].
+    pub callee: usize,
+    /// Number of times the callee was entered from the caller.
+    ///
+    /// Counts and durations saturate at their largest value rather than
+    /// wrapping around, both while recording and when snapshots are merged.
+    pub count: u64,
+    /// Total time spent inside the callee when entered from the caller.
+    pub duration: Duration,
+    /// The position of the edge among the edges first followed within a
+    /// root span, counting from `1`, or the lowest position over several
+    /// root spans, if recorded with [`Builder::edge_order`].
+    ///
+    /// [`Builder::edge_order`]: crate::Builder::edge_order
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub order: Option<usize>,
+    /// Number of error events reported inside the callee when entered from
+    /// the caller, at `ERROR` level or with an `error` field like those of
+    /// `#[instrument(err)]`.
+    ///
+    /// ```rust
+    /// #[tracing::instrument]
+    /// fn handler() {
+    ///     let _ = load("a");
+    ///     let _ = load("b");
+    /// }
+    ///
+    /// #[tracing::instrument(err)]
+    /// fn load(key: &str) -> Result<(), String> {
+    ///     match key {
+    ///         "a" => Ok(()),
+    ///         _ => Err("not found".to_string()),
+    ///     }
+    /// }
+    ///
+    /// let snapshot = tracing_callgraph::with_graph_recording(handler);
+    /// assert_eq!(snapshot.edge("handler", "load").unwrap().errors, 1);
+    /// assert!(snapshot.to_dot().contains(r#"label = "2 (1 error)""#));
+    /// ```
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub errors: u64,
+    /// Number of times the callee ran for longer than its threshold when
+    /// entered from the caller, if one was set with [`Builder::sla`].
+    ///
+    /// [`Builder::sla`]: crate::Builder::sla
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sla_violations: u64,
+    /// The values of a span field combined onto the edge, if configured with
+    /// [`Builder::aggregate_edges`]. Merged snapshots keep the larger value,
+    /// as they don't know how it was combined.
+    ///
+    /// [`Builder::aggregate_edges`]: crate::Builder::aggregate_edges
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub value: Option<u64>,
+}
+
+/// A change to an edge between two snapshots, see [`CallGraphSnapshot::diff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EdgeChange {
+    /// Name of the calling node.
+    pub caller: String,
+    /// Name of the called node.
+    pub callee: String,
+    /// Count in the baseline snapshot, `0` if the edge is new.
+    pub before: u64,
+    /// Count in the compared snapshot, `0` if the edge was removed.
+    pub after: u64,
+}
+
+/// A point-in-time copy of the call graph recorded by a [`GraphLayer`].
+///
+/// Nodes are sorted by name and edges refer to them by index into [`nodes`].
+/// Edges are sorted by caller, then callee, so exported output is stable
+/// across runs regardless of the order spans were entered in.
+///
+/// With the `serde` feature enabled, snapshots implement `Serialize` and
+/// `Deserialize`, so they can be stored and processed later by offline tools.
+/// Deserializing fails if an edge refers to a missing node, and sorts the
+/// nodes and edges as if the snapshot was taken.
+///
+/// [`GraphLayer`]: crate::GraphLayer
+/// [`nodes`]: CallGraphSnapshot::nodes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RawSnapshot")
+)]
+pub struct CallGraphSnapshot {
+    nodes: Vec<NodeInfo>,
+    edges: Vec<EdgeInfo>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub(crate) overhead: Option<Overhead>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub(crate) run: Option<RunInfo>,
+}
+
+/// A [`CallGraphSnapshot`] as deserialized, before it is checked and sorted.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawSnapshot {
+    nodes: Vec<NodeInfo>,
+    edges: Vec<EdgeInfo>,
+    #[serde(default)]
+    overhead: Option<Overhead>,
+    #[serde(default)]
+    run: Option<RunInfo>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawSnapshot> for CallGraphSnapshot {
+    type Error = String;
+
+    fn try_from(raw: RawSnapshot) -> Result<Self, Self::Error> {
+        for edge in &raw.edges {
+            if let Some(&index) = [edge.caller, edge.callee]
+                .iter()
+                .find(|&&index| index >= raw.nodes.len())
+            {
+                return Err(format!(
+                    "edge refers to node {}, but there are only {} nodes",
+                    index,
+                    raw.nodes.len()
+                ));
+            }
+        }
+        let mut snapshot = Self::from_parts(raw.nodes, raw.edges);
+        snapshot.overhead = raw.overhead;
+        snapshot.run = raw.run;
+        Ok(snapshot)
+    }
+}
+
+impl CallGraphSnapshot {
+    /// Builds a snapshot of `graph`, whose nodes are named by their index
+    /// into `names` and `callsites`, with the values of its edges in
+    /// `aggregates`, if any.
+    pub(crate) fn from_graph(
+        graph: &CallGraph,
+        names: &[String],
+        callsites: &[Option<CallsiteInfo>],
+        aggregates: Option<&Aggregates>,
+    ) -> Self {
+        let nodes: Vec<_> = graph
+            .nodes()
+            .map(|(id, counters)| NodeInfo {
+                name: names[id.index()].clone(),
+                calls: counters.count(),
+                duration: counters.duration(),
+                sla_violations: counters.sla_violations(),
+                callsite: callsites[id.index()].clone(),
+            })
+            .collect();
+        let indices: HashMap<_, _> = graph
+            .nodes()
+            .enumerate()
+            .map(|(index, (id, _))| (id, index))
+            .collect();
+        let edges = graph
+            .edges()
+            .map(|(caller, callee, counters)| EdgeInfo {
+                caller: indices[&caller],
+                callee: indices[&callee],
+                count: counters.count(),
+                duration: counters.duration(),
+                order: counters.order(),
+                errors: counters.errors(),
+                sla_violations: counters.sla_violations(),
+                value: aggregates.and_then(|aggregates| aggregates.value(caller, callee)),
+            })
+            .collect();
+        Self::from_parts(nodes, edges)
+    }
+
+    pub(crate) fn from_parts(nodes: Vec<NodeInfo>, edges: Vec<EdgeInfo>) -> Self {
+        let mut snapshot = Self {
+            nodes,
+            edges,
+            overhead: None,
+            run: None,
+        };
+        snapshot.sort();
+        snapshot
+    }
+
+    /// Restores the ordering of nodes and edges after they were modified.
+    fn sort(&mut self) {
+        let mut order: Vec<_> = (0..self.nodes.len()).collect();
+        order.sort_by(|&a, &b| self.nodes[a].name.cmp(&self.nodes[b].name));
+        let mut rank = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            rank[old] = new;
+        }
+
+        self.nodes.sort_by(|a, b| a.name.cmp(&b.name));
+        for edge in &mut self.edges {
+            edge.caller = rank[edge.caller];
+            edge.callee = rank[edge.callee];
+        }
+        self.edges.sort_by_key(|edge| (edge.caller, edge.callee));
+    }
+
+    /// Returns the nodes of the graph.
+    pub fn nodes(&self) -> &[NodeInfo] {
+        &self.nodes
+    }
+
+    /// Returns the edges of the graph.
+    pub fn edges(&self) -> &[EdgeInfo] {
+        &self.edges
+    }
+
+    /// Returns the index of the node with the given name, if any.
+    pub fn node_index(&self, name: &str) -> Option<usize> {
+        self.nodes
+            .binary_search_by(|node| node.name.as_str().cmp(name))
+            .ok()
+    }
+
+    /// Returns the edge from `caller` to `callee`, if any.
+    pub fn edge(&self, caller: &str, callee: &str) -> Option<&EdgeInfo> {
+        let caller = self.node_index(caller)?;
+        let callee = self.node_index(callee)?;
+        self.edges
+            .binary_search_by_key(&(caller, callee), |edge| (edge.caller, edge.callee))
+            .ok()
+            .map(|index| &self.edges[index])
+    }
+
+    /// Converts the snapshot into a [`petgraph::Graph`].
+    ///
+    /// The `NodeIndex` of each node matches its position in [`nodes`], so
+    /// indices can be used interchangeably between the snapshot and the graph.
+    ///
+    /// ```rust
+    /// use tracing_callgraph::GraphLayer;
+    /// use tracing_subscriber::{registry::Registry, prelude::*};
+    ///
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let layer = GraphLayer::new();
+    /// let subscriber = Registry::default().with(layer.clone());
+    /// tracing::subscriber::with_default(subscriber, outer);
+    ///
+    /// let graph = layer.snapshot().to_graph();
+    /// let order = petgraph::algo::toposort(&graph, None).unwrap();
+    /// assert_eq!(graph[order[0]].name, "outer");
+    /// ```
+    ///
+    /// [`nodes`]: CallGraphSnapshot::nodes
+    pub fn to_graph(&self) -> DiGraph<NodeInfo, EdgeInfo> {
+        let mut graph = DiGraph::with_capacity(self.nodes.len(), self.edges.len());
+        for node in &self.nodes {
+            graph.add_node(node.clone());
+        }
+        for edge in &self.edges {
+            graph.add_edge(
+                NodeIndex::new(edge.caller),
+                NodeIndex::new(edge.callee),
+                edge.clone(),
+            );
+        }
+        graph
+    }
+
+    /// Adds the nodes and edges of `other` to this snapshot.
+    ///
+    /// Nodes are matched by name, and the counts and durations of common
+    /// nodes and edges are summed, as are measured [`Overhead`]s. The
+    /// [`RunInfo`] of this snapshot is kept, or that of `other` taken if it
+    /// has none.
+    pub fn merge(&mut self, other: &CallGraphSnapshot) {
+        if self.run.is_none() {
+            self.run = other.run.clone();
+        }
+        if let Some(overhead) = other.overhead {
+            *self.overhead.get_or_insert_with(Overhead::default) += overhead;
+        }
+        let mut indices: HashMap<_, _> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node.name.clone(), index))
+            .collect();
+        let mapping: Vec<_> = other
+            .nodes
+            .iter()
+            .map(|node| match indices.get(&node.name) {
+                Some(&index) => {
+                    accumulate(&mut self.nodes[index].calls, node.calls);
+                    accumulate(&mut self.nodes[index].duration, node.duration);
+                    accumulate(&mut self.nodes[index].sla_violations, node.sla_violations);
+                    index
+                }
+                None => {
+                    indices.insert(node.name.clone(), self.nodes.len());
+                    self.nodes.push(node.clone());
+                    self.nodes.len() - 1
+                }
+            })
+            .collect();
+
+        let mut edges: HashMap<_, _> = self
+            .edges
+            .iter()
+            .enumerate()
+            .map(|(index, edge)| ((edge.caller, edge.callee), index))
+            .collect();
+        for edge in &other.edges {
+            let key = (mapping[edge.caller], mapping[edge.callee]);
+            match edges.get(&key) {
+                Some(&index) => {
+                    let merged = &mut self.edges[index];
+                    accumulate(&mut merged.count, edge.count);
+                    accumulate(&mut merged.duration, edge.duration);
+                    merged.order = min_order(merged.order, edge.order);
+                    accumulate(&mut merged.errors, edge.errors);
+                    accumulate(&mut merged.sla_violations, edge.sla_violations);
+                    merged.value = merged.value.max(edge.value);
+                }
+                None => {
+                    edges.insert(key, self.edges.len());
+                    self.edges.push(EdgeInfo {
+                        caller: key.0,
+                        callee: key.1,
+                        ..edge.clone()
+                    });
+                }
+            }
+        }
+        self.sort();
+    }
+
+    /// Retains only the nodes for which `keep` returns `true`, along with the
+    /// edges between them.
+    pub fn retain_nodes<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&NodeInfo) -> bool,
+    {
+        let mut mapping = Vec::with_capacity(self.nodes.len());
+        let mut next = 0;
+        for node in &self.nodes {
+            if keep(node) {
+                mapping.push(Some(next));
+                next += 1;
+            } else {
+                mapping.push(None);
+            }
+        }
+
+        let mut index = 0;
+        self.nodes.retain(|_| {
+            index += 1;
+            mapping[index - 1].is_some()
+        });
+        self.edges
+            .retain_mut(|edge| match (mapping[edge.caller], mapping[edge.callee]) {
+                (Some(caller), Some(callee)) => {
+                    edge.caller = caller;
+                    edge.callee = callee;
+                    true
+                }
+                _ => false,
+            });
+    }
+
+    /// Removes edges called fewer than `min_count` times, then any nodes
+    /// left without edges.
+    pub fn prune(&mut self, min_count: u64) {
+        self.edges.retain(|edge| edge.count >= min_count);
+        let mut connected = vec![false; self.nodes.len()];
+        for edge in &self.edges {
+            connected[edge.caller] = true;
+            connected[edge.callee] = true;
+        }
+        let mut index = 0;
+        self.retain_nodes(|_| {
+            index += 1;
+            connected[index - 1]
+        });
+    }
+
+    /// Compares this snapshot against a `baseline`, returning every edge
+    /// whose count differs between the two.
+    pub fn diff(&self, baseline: &CallGraphSnapshot) -> Vec<EdgeChange> {
+        let counts = |snapshot: &CallGraphSnapshot| -> HashMap<(String, String), u64> {
+            snapshot
+                .edges
+                .iter()
+                .map(|edge| {
+                    let caller = snapshot.nodes[edge.caller].name.clone();
+                    let callee = snapshot.nodes[edge.callee].name.clone();
+                    ((caller, callee), edge.count)
+                })
+                .collect()
+        };
+        let before = counts(baseline);
+        let after = counts(self);
+
+        let mut changes: Vec<_> = before
+            .keys()
+            .chain(after.keys().filter(|key| !before.contains_key(*key)))
+            .filter_map(|key| {
+                let old = before.get(key).copied().unwrap_or(0);
+                let new = after.get(key).copied().unwrap_or(0);
+                if old == new {
+                    return None;
+                }
+                Some(EdgeChange {
+                    caller: key.0.clone(),
+                    callee: key.1.clone(),
+                    before: old,
+                    after: new,
+                })
+            })
+            .collect();
+        changes.sort_by(|a, b| (&a.caller, &a.callee).cmp(&(&b.caller, &b.callee)));
+        changes
+    }
+}
+
+/// Returns the lower of two [`EdgeInfo::order`]s, ignoring missing ones.
+pub(crate) fn min_order(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
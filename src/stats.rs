@@ -0,0 +1,82 @@
+use crate::{CallGraphSnapshot, EdgeInfo};
+use petgraph::{algo::tarjan_scc, graph::NodeIndex};
+
+/// The number of edges kept in [`GraphStats::heaviest_edges`].
+const HEAVIEST_EDGES: usize = 5;
+
+/// Summary statistics of a [`CallGraphSnapshot`], see [`CallGraphSnapshot::stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GraphStats {
+    /// Number of nodes.
+    pub nodes: usize,
+    /// Number of edges.
+    pub edges: usize,
+    /// Sum of all edge counts.
+    pub total_calls: u64,
+    /// Largest number of distinct callers of a single node.
+    pub max_fan_in: usize,
+    /// Largest number of distinct callees of a single node.
+    pub max_fan_out: usize,
+    /// Number of nodes on the longest call chain, counting each group of
+    /// mutually recursive nodes once.
+    pub max_depth: usize,
+    /// The edges with the highest counts, heaviest first.
+    pub heaviest_edges: Vec<EdgeInfo>,
+}
+
+impl CallGraphSnapshot {
+    /// Returns summary statistics of the graph.
+    pub fn stats(&self) -> GraphStats {
+        let mut fan_in = vec![0; self.nodes().len()];
+        let mut fan_out = vec![0; self.nodes().len()];
+        for edge in self.edges() {
+            fan_in[edge.callee] += 1;
+            fan_out[edge.caller] += 1;
+        }
+
+        let mut heaviest_edges = self.edges().to_vec();
+        heaviest_edges.sort_by_key(|edge| std::cmp::Reverse(edge.count));
+        heaviest_edges.truncate(HEAVIEST_EDGES);
+
+        GraphStats {
+            nodes: self.nodes().len(),
+            edges: self.edges().len(),
+            total_calls: self
+                .edges()
+                .iter()
+                .fold(0, |total, edge| total.saturating_add(edge.count)),
+            max_fan_in: fan_in.into_iter().max().unwrap_or(0),
+            max_fan_out: fan_out.into_iter().max().unwrap_or(0),
+            max_depth: self.max_depth(),
+            heaviest_edges,
+        }
+    }
+
+    fn max_depth(&self) -> usize {
+        let graph = self.to_graph();
+        // Components are yielded callees first, so every successor component
+        // has its depth computed before it is needed.
+        let components = tarjan_scc(&graph);
+        let mut component_of = vec![0; graph.node_count()];
+        for (component, nodes) in components.iter().enumerate() {
+            for node in nodes {
+                component_of[node.index()] = component;
+            }
+        }
+
+        let mut depths = vec![0; components.len()];
+        for (component, nodes) in components.iter().enumerate() {
+            let deepest_callee = nodes
+                .iter()
+                .flat_map(|&node| graph.neighbors(node))
+                .map(|callee: NodeIndex| component_of[callee.index()])
+                .filter(|&callee| callee != component)
+                .map(|callee| depths[callee])
+                .max()
+                .unwrap_or(0);
+            depths[component] = deepest_callee + 1;
+        }
+        depths.into_iter().max().unwrap_or(0)
+    }
+}
@@ -0,0 +1,583 @@
+use crate::{
+    aggregation::Aggregates,
+    decay::{Call, Decay},
+    filter::NodeKeys,
+    folded::FoldedStacks,
+    graph::{CallGraph, NodeId},
+    hash::{BuildHasher, HashFunction},
+    interner::{Disambiguation, Interner},
+    open::OpenSpans,
+    orphans::Orphans,
+    overhead::OverheadCounters,
+    partition::{PartitionKey, Partitions},
+    run::RunInfo,
+    simplify::Simplification,
+    sla::Slas,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+    timeline::Timeline,
+    window::Window,
+    Aggregation, CallGraphSnapshot, CallsiteInfo, Orphan,
+};
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+    sync::{
+        atomic::AtomicU64,
+        mpsc::{self, Receiver, SyncSender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use thread_local::ThreadLocal;
+
+/// How a [`GraphLayer`] stores the graph while recording.
+///
+/// [`GraphLayer`]: crate::GraphLayer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Backend {
+    /// Each thread records into its own graph, and the graphs are merged
+    /// when a snapshot is taken. Recording never contends across threads.
+    #[default]
+    ThreadLocal,
+    /// Spans are recorded into a fixed number of shared graphs, chosen by
+    /// the node of the entered span. Contention is reduced while keeping
+    /// memory independent of the number of threads.
+    Sharded(usize),
+    /// Entering and exiting a span, like reporting an error, only pushes a
+    /// small record onto a channel holding up to the given number of
+    /// records, and a background thread aggregates them into a single graph.
+    /// This keeps the latency added to instrumented threads to a minimum,
+    /// but they block while the channel is full. Where threads can't be
+    /// spawned, such as on `wasm32`, this falls back to
+    /// [`Backend::ThreadLocal`].
+    Channel(usize),
+}
+
+/// What a [`GraphLayer`] records, and how, as configured by its [`Builder`].
+///
+/// [`GraphLayer`]: crate::GraphLayer
+/// [`Builder`]: crate::Builder
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Config {
+    pub(crate) backend: Backend,
+    pub(crate) max_nodes: Option<usize>,
+    pub(crate) hash_function: HashFunction,
+    pub(crate) measure_overhead: bool,
+    pub(crate) folded_stacks: bool,
+    pub(crate) timeline: bool,
+    pub(crate) service: Option<String>,
+    pub(crate) disambiguation: Disambiguation,
+    pub(crate) simplification: Simplification,
+    pub(crate) report_unexecuted: bool,
+    pub(crate) partition: Option<(PartitionKey, usize)>,
+    pub(crate) edge_order: bool,
+    pub(crate) report_orphans: bool,
+    pub(crate) aggregation: Option<Aggregation>,
+    pub(crate) node_keys: Option<NodeKeys>,
+    pub(crate) run_version: Option<String>,
+    pub(crate) slas: Vec<(String, Duration)>,
+    pub(crate) half_life: Option<Duration>,
+    pub(crate) window: Option<Duration>,
+}
+
+/// The graphs of a [`GraphLayer`], and the names of their nodes.
+///
+/// [`GraphLayer`]: crate::GraphLayer
+#[derive(Debug, Default)]
+pub(crate) struct Graphs {
+    layout: Layout,
+    hasher: BuildHasher,
+    pub(crate) names: Interner,
+    pub(crate) overhead: Option<OverheadCounters>,
+    pub(crate) stacks: Option<FoldedStacks>,
+    pub(crate) timeline: Option<Timeline>,
+    /// The number of span events for ids missing from the registry.
+    pub(crate) unknown_spans: AtomicU64,
+    /// The number of frames ended with `GraphLayer::end_frame`.
+    pub(crate) frames: AtomicU64,
+    pub(crate) open: OpenSpans,
+    pub(crate) report_unexecuted: bool,
+    pub(crate) partitions: Option<Partitions>,
+    pub(crate) edge_order: bool,
+    pub(crate) orphans: Option<Orphans>,
+    pub(crate) aggregates: Option<Aggregates>,
+    pub(crate) run: Option<RunInfo>,
+    pub(crate) slas: Option<Slas>,
+    decay: Option<Decay>,
+    window: Option<Window>,
+}
+
+/// The graphs laid out as configured by a [`Backend`].
+// Always kept behind an `Arc`, so the size difference doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+enum Layout {
+    ThreadLocal(ThreadLocal<RwLock<CallGraph>>),
+    Sharded(Box<[RwLock<CallGraph>]>),
+    Channel(Aggregator),
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::ThreadLocal(ThreadLocal::new())
+    }
+}
+
+impl Graphs {
+    pub(crate) fn new(config: &Config) -> Self {
+        let hasher = BuildHasher::new(config.hash_function);
+        let layout = match config.backend {
+            Backend::ThreadLocal => Layout::ThreadLocal(ThreadLocal::new()),
+            Backend::Sharded(shards) => Layout::Sharded(
+                (0..shards.max(1))
+                    .map(|_| RwLock::new(CallGraph::new(hasher.clone())))
+                    .collect(),
+            ),
+            // Fall back to recording in place where threads are unavailable.
+            Backend::Channel(capacity) => {
+                match Aggregator::spawn(capacity, CallGraph::new(hasher.clone())) {
+                    Ok(aggregator) => Layout::Channel(aggregator),
+                    Err(_) => Layout::ThreadLocal(ThreadLocal::new()),
+                }
+            }
+        };
+        Self {
+            layout,
+            names: Interner::new(
+                config.max_nodes,
+                hasher.clone(),
+                config.service.clone(),
+                config.disambiguation,
+                config.simplification,
+                config.node_keys.clone(),
+            ),
+            overhead: if config.measure_overhead {
+                Some(OverheadCounters::default())
+            } else {
+                None
+            },
+            stacks: if config.folded_stacks {
+                Some(FoldedStacks::new(hasher.clone()))
+            } else {
+                None
+            },
+            timeline: if config.timeline {
+                Some(Timeline::new())
+            } else {
+                None
+            },
+            unknown_spans: AtomicU64::new(0),
+            frames: AtomicU64::new(0),
+            open: OpenSpans::default(),
+            report_unexecuted: config.report_unexecuted,
+            partitions: config
+                .partition
+                .map(|(key, max)| Partitions::new(key, max, hasher.clone())),
+            edge_order: config.edge_order,
+            orphans: if config.report_orphans {
+                Some(Orphans::new(hasher.clone()))
+            } else {
+                None
+            },
+            aggregates: config
+                .aggregation
+                .clone()
+                .map(|aggregation| Aggregates::new(aggregation, hasher.clone())),
+            run: config
+                .run_version
+                .clone()
+                .map(|version| RunInfo::start(version, format!("{:?}", config))),
+            slas: if config.slas.is_empty() {
+                None
+            } else {
+                Some(Slas::new(&config.slas, hasher.clone()))
+            },
+            decay: config
+                .half_life
+                .map(|half_life| Decay::new(half_life, hasher.clone())),
+            window: config
+                .window
+                .map(|length| Window::new(length, hasher.clone())),
+            hasher,
+        }
+    }
+
+    /// Returns the graph that records entering `callee` on the current thread.
+    fn graph(&self, callee: NodeId) -> &RwLock<CallGraph> {
+        match &self.layout {
+            Layout::ThreadLocal(graphs) => {
+                graphs.get_or(|| RwLock::new(CallGraph::new(self.hasher.clone())))
+            }
+            Layout::Sharded(shards) => &shards[callee.index() % shards.len()],
+            Layout::Channel(aggregator) => &aggregator.graph,
+        }
+    }
+
+    /// Locks `graph` for shared access, timing the wait if overhead is measured.
+    fn read<'a>(&self, graph: &'a RwLock<CallGraph>) -> RwLockReadGuard<'a, CallGraph> {
+        let _wait = self.overhead.as_ref().map(OverheadCounters::time_lock_wait);
+        graph.read()
+    }
+
+    /// Locks `graph` for exclusive access, timing the wait if overhead is
+    /// measured.
+    fn write<'a>(&self, graph: &'a RwLock<CallGraph>) -> RwLockWriteGuard<'a, CallGraph> {
+        let _wait = self.overhead.as_ref().map(OverheadCounters::time_lock_wait);
+        graph.write()
+    }
+
+    /// Records into the graph that records entering `callee` on the current
+    /// thread, or into the bucket of the window recording the calls made
+    /// now, with shared access if `try_record` succeeds and exclusive access
+    /// to `record` otherwise. With a half-life, `call` is decayed instead.
+    fn record<T, R>(
+        &self,
+        caller: Option<NodeId>,
+        callee: NodeId,
+        call: Call,
+        try_record: T,
+        record: R,
+    ) where
+        T: FnOnce(&CallGraph) -> bool,
+        R: FnOnce(&mut CallGraph),
+    {
+        let record = |graph: &RwLock<CallGraph>| {
+            if !try_record(&self.read(graph)) {
+                record(&mut self.write(graph));
+            }
+        };
+        match (&self.window, &self.decay) {
+            (Some(window), _) => window.with_bucket(record),
+            (None, Some(decay)) => decay.record(caller, callee, call),
+            (None, None) => record(self.graph(callee)),
+        }
+    }
+
+    /// Returns the aggregator of [`Backend::Channel`], unless calls are
+    /// recorded into the buckets of a window or decayed.
+    fn aggregator(&self) -> Option<&Aggregator> {
+        match (&self.layout, &self.window, &self.decay) {
+            (Layout::Channel(aggregator), None, None) => Some(aggregator),
+            _ => None,
+        }
+    }
+
+    /// Runs `f` with exclusive access to the graph that records entering
+    /// `callee` on the current thread.
+    pub(crate) fn with_graph<F, R>(&self, callee: NodeId, f: F) -> R
+    where
+        F: FnOnce(&mut CallGraph) -> R,
+    {
+        f(&mut self.graph(callee).write())
+    }
+
+    /// Records `callee` being entered from `caller`.
+    ///
+    /// Entering takes shared access to the graph if the edge was recorded
+    /// before, and only locks it exclusively to add new nodes and edges. As
+    /// the counters are atomic, shared access never blocks other threads
+    /// recording into the same graph.
+    pub(crate) fn record_enter(&self, caller: Option<NodeId>, callee: NodeId) {
+        if let Some(aggregator) = self.aggregator() {
+            let _wait = self.overhead.as_ref().map(OverheadCounters::time_lock_wait);
+            return aggregator.send(Event::Enter(caller, callee));
+        }
+        self.record(
+            caller,
+            callee,
+            Call::Enter,
+            |graph| graph.try_record_enter(caller, callee),
+            |graph| graph.record_enter(caller, callee),
+        );
+    }
+
+    /// Records `callee` being exited from `caller` after running for
+    /// `elapsed`, with shared access to the graph like [`record_enter`].
+    ///
+    /// [`record_enter`]: Graphs::record_enter
+    pub(crate) fn record_exit(&self, caller: Option<NodeId>, callee: NodeId, elapsed: Duration) {
+        if let Some(aggregator) = self.aggregator() {
+            let _wait = self.overhead.as_ref().map(OverheadCounters::time_lock_wait);
+            return aggregator.send(Event::Exit(caller, callee, elapsed));
+        }
+        self.record(
+            caller,
+            callee,
+            Call::Exit(elapsed),
+            |graph| graph.try_record_exit(caller, callee, elapsed),
+            |graph| graph.record_exit(caller, callee, elapsed),
+        );
+    }
+
+    /// Records the position of the edge from `caller` to `callee` among the
+    /// edges first followed within a root span, see
+    /// [`CallGraph::record_order`].
+    ///
+    pub(crate) fn record_order(&self, caller: NodeId, callee: NodeId, order: usize) {
+        if let Some(aggregator) = self.aggregator() {
+            let _wait = self.overhead.as_ref().map(OverheadCounters::time_lock_wait);
+            return aggregator.send(Event::Order(caller, callee, order));
+        }
+        self.record(
+            Some(caller),
+            callee,
+            Call::Order(order),
+            |graph| graph.try_record_order(caller, callee, order),
+            |graph| graph.record_order(caller, callee, order),
+        );
+    }
+
+    /// Records an error reported by `callee` while called from `caller`,
+    /// with shared access to the graph if the edge was recorded before.
+    pub(crate) fn record_error(&self, caller: NodeId, callee: NodeId) {
+        if let Some(aggregator) = self.aggregator() {
+            let _wait = self.overhead.as_ref().map(OverheadCounters::time_lock_wait);
+            return aggregator.send(Event::Error(caller, callee));
+        }
+        self.record(
+            Some(caller),
+            callee,
+            Call::Error,
+            |graph| graph.try_record_error(caller, callee),
+            |graph| graph.record_error(caller, callee),
+        );
+    }
+
+    /// Records `callee` running for longer than its threshold while called
+    /// from `caller`, with shared access to the graph if its node and edge
+    /// were recorded before.
+    pub(crate) fn record_sla_violation(&self, caller: Option<NodeId>, callee: NodeId) {
+        if let Some(aggregator) = self.aggregator() {
+            let _wait = self.overhead.as_ref().map(OverheadCounters::time_lock_wait);
+            return aggregator.send(Event::SlaViolation(caller, callee));
+        }
+        self.record(
+            caller,
+            callee,
+            Call::SlaViolation,
+            |graph| graph.try_record_sla_violation(caller, callee),
+            |graph| graph.record_sla_violation(caller, callee),
+        );
+    }
+
+    /// Removes a node along with all of its edges from every graph.
+    pub(crate) fn remove_node(&self, id: NodeId) {
+        self.for_each(|graph| graph.remove_node(id));
+        if let Some(decay) = &self.decay {
+            decay.remove_node(id);
+        }
+    }
+
+    /// Runs `f` with every graph, including the buckets of a window.
+    fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&mut CallGraph),
+    {
+        let mut apply = |graph: &RwLock<CallGraph>| f(&mut graph.write());
+        if let Some(window) = &self.window {
+            window.for_each(&mut apply);
+        }
+        match &self.layout {
+            Layout::ThreadLocal(graphs) => graphs.iter().for_each(&mut apply),
+            Layout::Sharded(shards) => shards.iter().for_each(&mut apply),
+            Layout::Channel(aggregator) => {
+                aggregator.sync();
+                apply(&aggregator.graph)
+            }
+        }
+    }
+
+    /// Returns a snapshot of all graphs merged into one.
+    pub(crate) fn snapshot(&self) -> CallGraphSnapshot {
+        let mut snapshot = CallGraphSnapshot::from_graph(
+            &self.merged(),
+            &self.names.names(),
+            &self.names.callsites(),
+            self.aggregates.as_ref(),
+        );
+        snapshot.overhead = self.overhead.as_ref().map(OverheadCounters::get);
+        snapshot.run = self.run.as_ref().map(RunInfo::ended);
+        snapshot
+    }
+
+    /// Returns a snapshot of the graph of every partition with its key,
+    /// sorted by key.
+    pub(crate) fn partition_snapshots(&self) -> Vec<(String, CallGraphSnapshot)> {
+        let mut snapshots = Vec::new();
+        if let Some(partitions) = &self.partitions {
+            let (names, callsites) = (self.names.names(), self.names.callsites());
+            partitions.for_each(|key, graph| {
+                let snapshot = CallGraphSnapshot::from_graph(graph, &names, &callsites, None);
+                snapshots.push((key.to_string(), snapshot));
+            });
+        }
+        snapshots
+    }
+
+    /// Writes the recorded stacks in folded format, if they were recorded.
+    pub(crate) fn write_folded<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        match &self.stacks {
+            Some(stacks) => stacks.write(writer, &self.names.names()),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the recorded timeline in Speedscope format, if it was recorded.
+    pub(crate) fn write_speedscope<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        match &self.timeline {
+            Some(timeline) => timeline.write(writer, &self.names.names()),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the names of the nodes with open spans, and their number,
+    /// sorted by name.
+    pub(crate) fn open_spans(&self) -> Vec<(String, usize)> {
+        let names = self.names.names();
+        let mut open: Vec<_> = self
+            .open
+            .get()
+            .into_iter()
+            .map(|(id, count)| (names[id.index()].clone(), count))
+            .collect();
+        open.sort_unstable();
+        open
+    }
+
+    /// Returns the spans recorded without a caller, sorted by name and
+    /// thread, if they were counted.
+    pub(crate) fn orphans(&self) -> Vec<Orphan> {
+        let orphans = match &self.orphans {
+            Some(orphans) => orphans.get(),
+            None => return Vec::new(),
+        };
+        let names = self.names.names();
+        let mut orphans: Vec<_> = orphans
+            .into_iter()
+            .map(|(id, thread, count)| Orphan {
+                name: names[id.index()].clone(),
+                thread,
+                count,
+            })
+            .collect();
+        orphans.sort_by(|a, b| (&a.name, &a.thread).cmp(&(&b.name, &b.thread)));
+        orphans
+    }
+
+    /// Returns the registered span callsites whose nodes were never
+    /// recorded, sorted by target, file and line.
+    pub(crate) fn unexecuted_callsites(&self) -> Vec<CallsiteInfo> {
+        let merged = self.merged();
+        let recorded: HashSet<_> = merged.nodes().map(|(id, _)| id).collect();
+        let mut unexecuted: Vec<_> = self
+            .names
+            .registered()
+            .into_iter()
+            .filter(|(_, id)| !recorded.contains(id))
+            .map(|(callsite, _)| callsite)
+            .collect();
+        unexecuted.sort_by(|a, b| {
+            (&a.target, &a.file, a.line, &a.name).cmp(&(&b.target, &b.file, b.line, &b.name))
+        });
+        unexecuted
+    }
+
+    fn merged(&self) -> CallGraph {
+        let mut merged = CallGraph::new(self.hasher.clone());
+        let mut merge = |graph: &RwLock<CallGraph>| merged.merge(&graph.read());
+        match &self.layout {
+            Layout::ThreadLocal(graphs) => graphs.iter().for_each(&mut merge),
+            Layout::Sharded(shards) => shards.iter().for_each(&mut merge),
+            Layout::Channel(aggregator) => {
+                aggregator.sync();
+                merge(&aggregator.graph)
+            }
+        }
+        if let Some(window) = &self.window {
+            window.merge_into(&mut merged);
+        }
+        if let Some(decay) = &self.decay {
+            decay.merge_into(&mut merged);
+        }
+        merged
+    }
+}
+
+/// A record sent to the background thread of [`Backend::Channel`].
+#[derive(Debug)]
+enum Event {
+    Enter(Option<NodeId>, NodeId),
+    Exit(Option<NodeId>, NodeId, Duration),
+    Order(NodeId, NodeId, usize),
+    Error(NodeId, NodeId),
+    SlaViolation(Option<NodeId>, NodeId),
+    /// Acknowledged once all earlier records were aggregated.
+    Sync(SyncSender<()>),
+}
+
+/// The channel and graph of [`Backend::Channel`].
+///
+/// The background thread exits once the channel is dropped along with the
+/// [`Graphs`].
+#[derive(Debug)]
+struct Aggregator {
+    sender: SyncSender<Event>,
+    graph: Arc<RwLock<CallGraph>>,
+}
+
+impl Aggregator {
+    fn spawn(capacity: usize, graph: CallGraph) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let graph = Arc::new(RwLock::new(graph));
+        let aggregated = graph.clone();
+        thread::Builder::new()
+            .name("tracing-callgraph".into())
+            .spawn(move || Self::aggregate(&receiver, &aggregated))?;
+        Ok(Self { sender, graph })
+    }
+
+    fn aggregate(receiver: &Receiver<Event>, graph: &RwLock<CallGraph>) {
+        // Hold the lock for everything that is queued, rather than per record.
+        while let Ok(first) = receiver.recv() {
+            let mut graph = graph.write();
+            for event in std::iter::once(first).chain(receiver.try_iter()) {
+                match event {
+                    Event::Enter(caller, callee) => graph.record_enter(caller, callee),
+                    Event::Exit(caller, callee, elapsed) => {
+                        graph.record_exit(caller, callee, elapsed)
+                    }
+                    Event::Order(caller, callee, order) => {
+                        graph.record_order(caller, callee, order)
+                    }
+                    Event::Error(caller, callee) => graph.record_error(caller, callee),
+                    Event::SlaViolation(caller, callee) => {
+                        graph.record_sla_violation(caller, callee)
+                    }
+                    Event::Sync(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        }
+    }
+
+    fn send(&self, event: Event) {
+        // The thread only exits once the sender is dropped.
+        let _ = self.sender.send(event);
+    }
+
+    /// Waits until every record sent so far was aggregated.
+    fn sync(&self) {
+        let (done, synced) = mpsc::sync_channel(1);
+        self.send(Event::Sync(done));
+        let _ = synced.recv();
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{
+    error::{Error, Kind},
+    sync::Mutex,
+    time::SystemTime,
+    timeline::json_string,
+};
+use std::{
+    convert::TryFrom,
+    fmt,
+    io::{self, BufWriter, Write},
+    thread,
+};
+
+/// Writes a line of JSON for every call along an edge, see
+/// [`Builder::edge_stream`].
+///
+/// Lines are buffered, so that recording a call doesn't wait on the writer
+/// while holding the lock, and written out whole.
+///
+/// [`Builder::edge_stream`]: crate::Builder::edge_stream
+pub(crate) struct EdgeStream {
+    writer: Mutex<Writer>,
+}
+
+struct Writer {
+    inner: BufWriter<Box<dyn Write + Send>>,
+    /// Whether writing failed, after which nothing more is written.
+    failed: bool,
+}
+
+impl EdgeStream {
+    pub(crate) fn new<W>(writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        Self {
+            writer: Mutex::new(Writer {
+                inner: BufWriter::new(Box::new(writer)),
+                failed: false,
+            }),
+        }
+    }
+
+    /// Writes a call from `caller` to `callee` on the current thread.
+    pub(crate) fn record(&self, caller: &str, callee: &str) {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| {
+                u64::try_from(since.as_micros()).unwrap_or(u64::MAX)
+            });
+        let current = thread::current();
+        let thread = match current.name() {
+            Some(name) => name.to_string(),
+            None => format!("{:?}", current.id()),
+        };
+        // Built up front, so that the lock is only held to buffer the line.
+        let line = format!(
+            "{{\"caller\":{},\"callee\":{},\"timestamp\":{},\"thread\":{}}}\n",
+            json_string(caller),
+            json_string(callee),
+            timestamp,
+            json_string(&thread)
+        );
+        self.write(|writer| writer.write_all(line.as_bytes()));
+    }
+
+    pub(crate) fn flush(&self) {
+        self.write(|writer| writer.flush());
+    }
+
+    /// Runs `write` on the writer unless it failed before, reporting the
+    /// first failure only.
+    fn write<F>(&self, write: F)
+    where
+        F: FnOnce(&mut dyn Write) -> io::Result<()>,
+    {
+        let mut writer = self.writer.lock();
+        if writer.failed {
+            return;
+        }
+        if let Err(error) = write(&mut writer.inner) {
+            writer.failed = true;
+            Error(Kind::FlushFile(error)).report();
+        }
+    }
+}
+
+impl fmt::Debug for EdgeStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EdgeStream").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{
+    layout::{LAYER_GAP, MARGIN, NODE_HEIGHT},
+    CallGraphSnapshot,
+};
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+impl CallGraphSnapshot {
+    /// Writes the snapshot to `writer` as an SVG image.
+    ///
+    /// Unlike the `dot` output this needs no Graphviz installation. Nodes are
+    /// laid out in layers, with callers above their callees, and edges are
+    /// labelled with their counts. Calls that close a cycle point upwards.
+    ///
+    /// Requires the `svg` feature.
+    pub fn write_svg<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(self.to_svg().as_bytes())
+    }
+
+    /// Returns the snapshot as an SVG image, see [`write_svg`].
+    ///
+    /// ```rust
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let svg = tracing_callgraph::with_graph_recording(outer).to_svg();
+    /// assert!(svg.starts_with("<svg"));
+    /// assert!(svg.contains(">outer</text>"));
+    /// ```
+    ///
+    /// [`write_svg`]: CallGraphSnapshot::write_svg
+    pub fn to_svg(&self) -> String {
+        let placed = self.layout();
+        let width = placed
+            .iter()
+            .map(|node| node.x + node.width / 2.0)
+            .fold(0.0, f64::max)
+            + MARGIN;
+        let height = placed
+            .iter()
+            .map(|node| node.y + NODE_HEIGHT / 2.0)
+            .fold(0.0, f64::max)
+            + MARGIN;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}" height="{h:.0}" viewBox="0 0 {w:.0} {h:.0}" font-family="sans-serif" font-size="12">"#,
+            w = width,
+            h = height
+        );
+        svg.push_str(concat!(
+            r#"<defs><marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" "#,
+            r#"markerWidth="8" markerHeight="8" orient="auto-start-reverse">"#,
+            r#"<path d="M 0 0 L 10 5 L 0 10 z"/></marker></defs>"#,
+            "\n"
+        ));
+
+        for edge in self.edges() {
+            let from = placed[edge.caller];
+            let to = placed[edge.callee];
+            let (path, label_x, label_y) = if edge.caller == edge.callee {
+                let x = from.x + from.width / 2.0;
+                let path = format!(
+                    "M {:.1} {:.1} C {:.1} {:.1}, {:.1} {:.1}, {:.1} {:.1}",
+                    x,
+                    from.y - 5.0,
+                    x + 30.0,
+                    from.y - 20.0,
+                    x + 30.0,
+                    from.y + 20.0,
+                    x,
+                    from.y + 5.0
+                );
+                (path, x + 28.0, from.y)
+            } else {
+                let down = to.layer > from.layer;
+                let direction = if down { 1.0 } else { -1.0 };
+                let (x1, y1) = (from.x, from.y + direction * NODE_HEIGHT / 2.0);
+                let (x2, y2) = (to.x, to.y - direction * NODE_HEIGHT / 2.0);
+                let bend = direction * LAYER_GAP / 2.0;
+                let path = format!(
+                    "M {:.1} {:.1} C {:.1} {:.1}, {:.1} {:.1}, {:.1} {:.1}",
+                    x1,
+                    y1,
+                    x1,
+                    y1 + bend,
+                    x2,
+                    y2 - bend,
+                    x2,
+                    y2
+                );
+                (path, (x1 + x2) / 2.0 + 4.0, (y1 + y2) / 2.0)
+            };
+            let _ = writeln!(
+                svg,
+                r#"<path d="{}" fill="none" stroke="black" marker-end="url(#arrow)"/>"#,
+                path
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}">{}</text>"#,
+                label_x, label_y, edge.count
+            );
+        }
+
+        for (node, place) in self.nodes().iter().zip(&placed) {
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="4" fill="white" stroke="black"/>"#,
+                place.x - place.width / 2.0,
+                place.y - NODE_HEIGHT / 2.0,
+                place.width,
+                NODE_HEIGHT
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+                place.x,
+                place.y,
+                escape(&node.name)
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Escapes `text` for use in SVG text content.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
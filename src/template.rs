@@ -0,0 +1,49 @@
+use crate::time::SystemTime;
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// The number of the next path expanded with a `{seq}` placeholder.
+static SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Expands the placeholders of an output path, see [`Builder::with_file`].
+///
+/// `{pid}` is replaced with the id of the process, `{timestamp}` with the
+/// seconds since the Unix epoch, and `{seq}` with the number of paths with a
+/// `{seq}` expanded before in the process. Paths that are not valid UTF-8
+/// are left as they are.
+///
+/// [`Builder::with_file`]: crate::Builder::with_file
+pub(crate) fn expand(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(template) if template.contains('{') => expand_str(template).into(),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Expands the placeholders of `template` like [`expand`].
+pub(crate) fn expand_str(template: &str) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let mut expanded = template
+        .replace("{pid}", &pid().to_string())
+        .replace("{timestamp}", &timestamp.to_string());
+    if expanded.contains("{seq}") {
+        let seq = SEQ.fetch_add(1, Ordering::Relaxed);
+        expanded = expanded.replace("{seq}", &seq.to_string());
+    }
+    expanded
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn pid() -> u32 {
+    std::process::id()
+}
+
+/// Browsers have no processes, so every page shares the id `0`.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn pid() -> u32 {
+    0
+}
@@ -0,0 +1,231 @@
+use crate::{
+    error::{Error, Kind},
+    AtomicFile, CallGraphSnapshot, GraphLayer,
+};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::subscriber::DefaultGuard;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// The environment variable naming the directory [`TestGraph`]s are written
+/// to, `target/callgraph-tests` by default.
+pub const TEST_OUTPUT_ENV: &str = "TRACING_CALLGRAPH_TEST_DIR";
+
+/// Records the call graph of `f` and returns it.
+///
+/// A [`GraphLayer`] is installed as the default subscriber for the current
+/// thread only, while `f` runs, so this can be used in unit tests without
+/// touching the global subscriber.
+///
+/// ```rust
+/// #[tracing::instrument]
+/// fn outer() {
+///     inner()
+/// }
+///
+/// #[tracing::instrument]
+/// fn inner() {}
+///
+/// let snapshot = tracing_callgraph::with_graph_recording(outer);
+/// assert_eq!(snapshot.edge("outer", "inner").unwrap().count, 1);
+/// ```
+pub fn with_graph_recording<F>(f: F) -> CallGraphSnapshot
+where
+    F: FnOnce(),
+{
+    GraphLayer::builder().record(f)
+}
+
+/// Asserts that a [`CallGraphSnapshot`] contains an edge, optionally with a
+/// constraint on its count.
+///
+/// ```rust
+/// use tracing_callgraph::{assert_edge, with_graph_recording};
+///
+/// #[tracing::instrument]
+/// fn outer() {
+///     inner();
+///     inner();
+/// }
+///
+/// #[tracing::instrument]
+/// fn inner() {}
+///
+/// let snapshot = with_graph_recording(outer);
+/// assert_edge!(snapshot, "outer" -> "inner");
+/// assert_edge!(snapshot, "outer" -> "inner", count == 2);
+/// ```
+///
+/// [`CallGraphSnapshot`]: crate::CallGraphSnapshot
+#[macro_export]
+macro_rules! assert_edge {
+    ($snapshot:expr, $caller:tt -> $callee:tt) => {
+        $crate::assert_edge!($snapshot, $caller -> $callee, count >= 1)
+    };
+    ($snapshot:expr, $caller:tt -> $callee:tt, count $op:tt $expected:expr) => {{
+        let snapshot: &$crate::CallGraphSnapshot = &$snapshot;
+        let (caller, callee): (&str, &str) = (&$caller, &$callee);
+        let expected = $expected;
+        match snapshot.edge(caller, callee) {
+            Some(edge) if edge.count $op expected => {}
+            Some(edge) => panic!(
+                "assertion failed: edge `{} -> {}` has count {}, expected count {} {}",
+                caller,
+                callee,
+                edge.count,
+                stringify!($op),
+                expected,
+            ),
+            None => panic!(
+                "assertion failed: expected edge `{} -> {}`, but it was never recorded",
+                caller, callee,
+            ),
+        }
+    }};
+}
+
+/// Asserts that a [`CallGraphSnapshot`] does not contain an edge.
+///
+/// ```rust
+/// use tracing_callgraph::{assert_no_edge, with_graph_recording};
+///
+/// #[tracing::instrument]
+/// fn handler() {}
+///
+/// #[tracing::instrument]
+/// fn query() {}
+///
+/// let snapshot = with_graph_recording(handler);
+/// assert_no_edge!(snapshot, "handler" -> "query");
+/// ```
+///
+/// [`CallGraphSnapshot`]: crate::CallGraphSnapshot
+#[macro_export]
+macro_rules! assert_no_edge {
+    ($snapshot:expr, $caller:tt -> $callee:tt) => {{
+        let snapshot: &$crate::CallGraphSnapshot = &$snapshot;
+        let (caller, callee): (&str, &str) = (&$caller, &$callee);
+        if let Some(edge) = snapshot.edge(caller, callee) {
+            panic!(
+                "assertion failed: unexpected edge `{} -> {}` with count {}",
+                caller, callee, edge.count,
+            );
+        }
+    }};
+}
+
+/// Records the call graph of the current test into a [`TestGraph`] named
+/// after it, see [`TestGraph::new`].
+///
+/// ```rust
+/// #[tracing::instrument]
+/// fn login(password: &str) -> bool {
+///     check(password)
+/// }
+///
+/// #[tracing::instrument]
+/// fn check(password: &str) -> bool {
+///     password == "hunter2"
+/// }
+///
+/// // #[test]
+/// fn login_fails() {
+///     let graph = tracing_callgraph::test_graph!();
+///     assert!(!login("password"));
+///     assert!(graph.path().ends_with("login_fails.dot"));
+/// }
+///
+/// fn main() {
+///     login_fails();
+/// }
+/// ```
+#[macro_export]
+macro_rules! test_graph {
+    () => {{
+        fn here() {}
+        $crate::TestGraph::new(&$crate::__test_name(here))
+    }};
+}
+
+/// Returns the path of the function containing `f`, without the crate name,
+/// the name of `f`, or the closures and async blocks around `f`.
+#[doc(hidden)]
+pub fn __test_name<F>(_: F) -> String {
+    let path = std::any::type_name::<F>();
+    let segments: Vec<_> = path
+        .split("::")
+        .skip(1)
+        .filter(|segment| !segment.starts_with('{'))
+        .collect();
+    segments[..segments.len().saturating_sub(1)].join("::")
+}
+
+/// A guard recording the call graph of a test into a file named after it
+/// while it is alive, so that failing tests come with a picture of what
+/// they actually executed.
+///
+/// The graph is written when the guard is dropped, which also happens when
+/// the test panics. Spans are recorded on the current thread only, which
+/// includes `#[tokio::test]`s on the default current-thread runtime.
+#[must_use]
+#[derive(Debug)]
+pub struct TestGraph {
+    layer: GraphLayer,
+    path: PathBuf,
+    _default: DefaultGuard,
+}
+
+impl TestGraph {
+    /// Starts recording the call graph of the current thread, to be written
+    /// to `name.dot`, with `::` in `name` replaced by `.`, in the directory
+    /// named by [`TEST_OUTPUT_ENV`].
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::var_os(TEST_OUTPUT_ENV)
+            .map_or_else(|| PathBuf::from("target/callgraph-tests"), PathBuf::from);
+        let layer = GraphLayer::new();
+        let default = tracing::subscriber::set_default(Registry::default().with(layer.clone()));
+        Self {
+            layer,
+            path: dir.join(format!("{}.dot", name.replace("::", "."))),
+            _default: default,
+        }
+    }
+
+    /// Returns the path the graph is written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns a snapshot of the graph recorded so far.
+    pub fn snapshot(&self) -> CallGraphSnapshot {
+        self.layer.snapshot()
+    }
+}
+
+impl Drop for TestGraph {
+    fn drop(&mut self) {
+        let create = |path: &Path| {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            AtomicFile::create(path)
+        };
+        let mut writer = match create(&self.path) {
+            Ok(writer) => writer,
+            Err(source) => {
+                let path = self.path.clone();
+                return Error(Kind::CreateFile { path, source }).report();
+            }
+        };
+        let result = self
+            .snapshot()
+            .write_dot(&mut writer)
+            .and_then(|()| writer.flush());
+        if let Err(error) = result {
+            Error(Kind::FlushFile(error)).report();
+        }
+    }
+}
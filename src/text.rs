@@ -0,0 +1,64 @@
+use crate::CallGraphSnapshot;
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+impl CallGraphSnapshot {
+    /// Writes the snapshot to `writer` in a compact canonical text format.
+    ///
+    /// Each edge is written as a sorted `caller -> callee: count` line, and
+    /// nodes without any edges as a line holding just their name. Unlike the
+    /// `dot` output there are no indices or layout attributes, which makes the
+    /// format suited to golden-file and snapshot tests.
+    ///
+    /// ```rust
+    /// #[tracing::instrument]
+    /// fn outer() {
+    ///     inner()
+    /// }
+    ///
+    /// #[tracing::instrument]
+    /// fn inner() {}
+    ///
+    /// let snapshot = tracing_callgraph::with_graph_recording(outer);
+    /// assert_eq!(snapshot.to_text(), "outer -> inner: 1\n");
+    /// ```
+    pub fn write_text<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(self.to_text().as_bytes())
+    }
+
+    /// Returns the snapshot in the canonical text format, see [`write_text`].
+    ///
+    /// [`write_text`]: CallGraphSnapshot::write_text
+    pub fn to_text(&self) -> String {
+        let mut connected = vec![false; self.nodes().len()];
+        for edge in self.edges() {
+            connected[edge.caller] = true;
+            connected[edge.callee] = true;
+        }
+
+        let mut text = String::new();
+        for (node, _) in self
+            .nodes()
+            .iter()
+            .zip(&connected)
+            .filter(|(_, connected)| !**connected)
+        {
+            let _ = writeln!(text, "{}", node.name);
+        }
+        for edge in self.edges() {
+            let _ = writeln!(
+                text,
+                "{} -> {}: {}",
+                self.nodes()[edge.caller].name,
+                self.nodes()[edge.callee].name,
+                edge.count
+            );
+        }
+        text
+    }
+}
@@ -0,0 +1,59 @@
+/// A preset of `dot` styles, see [`DotOptions::with_theme`].
+///
+/// [`DotOptions::with_theme`]: crate::DotOptions::with_theme
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Theme {
+    /// Dark text on pale, rounded boxes over a white background.
+    Light,
+    /// Pale text on dark, rounded boxes over a dark background.
+    Dark,
+    /// Black outlines and serif text without fills, for printing in
+    /// greyscale.
+    Print,
+    /// White and yellow on black with thick lines and large text.
+    HighContrast,
+}
+
+/// The attributes of a [`Theme`].
+pub(crate) struct Style {
+    pub(crate) graph: &'static str,
+    pub(crate) node: &'static str,
+    pub(crate) edge: &'static str,
+    /// The color of highlighted nodes and edges.
+    pub(crate) highlight: &'static str,
+}
+
+/// The highlight color without a theme.
+pub(crate) const HIGHLIGHT_COLOR: &str = "red";
+
+impl Theme {
+    pub(crate) fn style(self) -> Style {
+        match self {
+            Self::Light => Style {
+                graph: r##"bgcolor = "white" fontname = "Helvetica""##,
+                node: r##"shape = "box" style = "rounded,filled" fillcolor = "#f5f7fa" color = "#4a5568" fontcolor = "#1a202c" fontname = "Helvetica""##,
+                edge: r##"color = "#718096" fontcolor = "#4a5568" fontname = "Helvetica" arrowhead = "vee" arrowsize = 0.7"##,
+                highlight: "#e53e3e",
+            },
+            Self::Dark => Style {
+                graph: r##"bgcolor = "#1e1e2e" fontname = "Helvetica""##,
+                node: r##"shape = "box" style = "rounded,filled" fillcolor = "#313244" color = "#89b4fa" fontcolor = "#cdd6f4" fontname = "Helvetica""##,
+                edge: r##"color = "#a6adc8" fontcolor = "#bac2de" fontname = "Helvetica" arrowhead = "vee" arrowsize = 0.7"##,
+                highlight: "#f38ba8",
+            },
+            Self::Print => Style {
+                graph: r##"bgcolor = "white" fontname = "Times-Roman""##,
+                node: r##"shape = "box" style = "rounded" color = "black" fontcolor = "black" fontname = "Times-Roman""##,
+                edge: r##"color = "black" fontcolor = "black" fontname = "Times-Roman" arrowhead = "normal""##,
+                highlight: "black",
+            },
+            Self::HighContrast => Style {
+                graph: r##"bgcolor = "black" fontname = "Helvetica-Bold" fontsize = 16"##,
+                node: r##"shape = "box" style = "filled" fillcolor = "black" color = "yellow" penwidth = 2 fontcolor = "white" fontname = "Helvetica-Bold" fontsize = 16"##,
+                edge: r##"color = "white" penwidth = 2 fontcolor = "yellow" fontname = "Helvetica-Bold" fontsize = 16 arrowhead = "normal" arrowsize = 1.2"##,
+                highlight: "cyan",
+            },
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{graph::NodeId, sync::RwLock, time::Instant};
+use std::{
+    convert::TryFrom,
+    io::{self, Write},
+    thread,
+};
+use thread_local::ThreadLocal;
+
+/// The time every span was entered and exited, by thread, for output in the
+/// evented format of [Speedscope](https://www.speedscope.app).
+#[derive(Debug)]
+pub(crate) struct Timeline {
+    start: Instant,
+    threads: ThreadLocal<RwLock<Thread>>,
+}
+
+/// The events recorded on one thread.
+#[derive(Debug)]
+struct Thread {
+    name: String,
+    events: Vec<Event>,
+}
+
+/// A span being entered or exited, `at` nanoseconds after recording started.
+#[derive(Clone, Copy, Debug)]
+struct Event {
+    open: bool,
+    node: NodeId,
+    at: u64,
+}
+
+impl Timeline {
+    pub(crate) fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            threads: ThreadLocal::new(),
+        }
+    }
+
+    pub(crate) fn record_enter(&self, node: NodeId) {
+        self.record(true, node);
+    }
+
+    pub(crate) fn record_exit(&self, node: NodeId) {
+        self.record(false, node);
+    }
+
+    fn record(&self, open: bool, node: NodeId) {
+        let at = u64::try_from(self.start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        let thread = self.threads.get_or(|| {
+            let current = thread::current();
+            let name = match current.name() {
+                Some(name) => name.to_string(),
+                None => format!("{:?}", current.id()),
+            };
+            RwLock::new(Thread {
+                name,
+                events: Vec::new(),
+            })
+        });
+        thread.write().events.push(Event { open, node, at });
+    }
+
+    /// Writes a Speedscope file with one profile per thread, naming nodes by
+    /// their index into `names`.
+    pub(crate) fn write<W>(&self, mut writer: W, names: &[String]) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mut threads: Vec<_> = self
+            .threads
+            .iter()
+            .map(|thread| {
+                let thread = thread.read();
+                (thread.name.clone(), nested(&thread.events))
+            })
+            .collect();
+        threads.sort_by(|a, b| a.0.cmp(&b.0));
+
+        write!(
+            writer,
+            "{{\"$schema\":\"https://www.speedscope.app/file-format-schema.json\",\
+             \"exporter\":\"tracing-callgraph\",\"shared\":{{\"frames\":["
+        )?;
+        for (index, name) in names.iter().enumerate() {
+            if index > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "{{\"name\":{}}}", json_string(name))?;
+        }
+        write!(writer, "]}},\"profiles\":[")?;
+        for (index, (name, events)) in threads.iter().enumerate() {
+            if index > 0 {
+                write!(writer, ",")?;
+            }
+            let start = events.first().map_or(0, |event| event.at);
+            let end = events.last().map_or(0, |event| event.at);
+            write!(
+                writer,
+                "{{\"type\":\"evented\",\"name\":{},\"unit\":\"nanoseconds\",\
+                 \"startValue\":{},\"endValue\":{},\"events\":[",
+                json_string(name),
+                start,
+                end
+            )?;
+            for (index, event) in events.iter().enumerate() {
+                if index > 0 {
+                    write!(writer, ",")?;
+                }
+                write!(
+                    writer,
+                    "{{\"type\":\"{}\",\"frame\":{},\"at\":{}}}",
+                    if event.open { "O" } else { "C" },
+                    event.node.index(),
+                    event.at
+                )?;
+            }
+            write!(writer, "]}}")?;
+        }
+        writeln!(writer, "]}}")
+    }
+}
+
+/// Returns `events` strictly nested, as Speedscope requires.
+///
+/// Spans entered out of order are closed and reopened around the exit of the
+/// span below them, exits of spans that were never entered are dropped, and
+/// spans still entered are closed at the last event.
+fn nested(events: &[Event]) -> Vec<Event> {
+    let mut nested = Vec::with_capacity(events.len());
+    let mut stack: Vec<NodeId> = Vec::new();
+    for &event in events {
+        if event.open {
+            stack.push(event.node);
+            nested.push(event);
+            continue;
+        }
+        let position = match stack.iter().rposition(|&node| node == event.node) {
+            Some(position) => position,
+            None => continue,
+        };
+        let above = stack.split_off(position + 1);
+        let close = |node| Event {
+            open: false,
+            node,
+            at: event.at,
+        };
+        nested.extend(above.iter().rev().map(|&node| close(node)));
+        nested.push(close(stack.pop().expect("expected: position is in stack")));
+        nested.extend(above.iter().map(|&node| Event {
+            open: true,
+            ..close(node)
+        }));
+        stack.extend(above);
+    }
+    let end = nested.last().map_or(0, |event| event.at);
+    nested.extend(stack.iter().rev().map(|&node| Event {
+        open: false,
+        node,
+        at: end,
+    }));
+    nested
+}
+
+/// Returns `text` as a JSON string.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c < ' ' => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,145 @@
+use crate::{CallGraphSnapshot, GraphLayer};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    widgets::{Block, Row, Table},
+    DefaultTerminal, Frame,
+};
+use std::{
+    cmp::Reverse,
+    io,
+    time::{Duration, Instant},
+};
+
+/// The statistic the live view is sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SortBy {
+    Count,
+    Duration,
+}
+
+impl GraphLayer {
+    /// Takes over the terminal to show the nodes and edges recorded so far,
+    /// refreshed every `refresh`, until `q` or `Esc` is pressed.
+    ///
+    /// The most called nodes and edges are shown first. Press `d` to sort by
+    /// duration instead, and `c` to sort by count again. This blocks, so it
+    /// is usually run on its own thread while the application is under load:
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    /// use tracing_callgraph::GraphLayer;
+    ///
+    /// let layer = GraphLayer::new();
+    /// let viewer = layer.clone();
+    /// std::thread::spawn(move || viewer.run_tui(Duration::from_millis(500)));
+    /// ```
+    ///
+    /// Requires the `tui` feature.
+    pub fn run_tui(&self, refresh: Duration) -> io::Result<()> {
+        let mut terminal = ratatui::init();
+        let result = self.show_live(&mut terminal, refresh);
+        ratatui::restore();
+        result
+    }
+
+    fn show_live(&self, terminal: &mut DefaultTerminal, refresh: Duration) -> io::Result<()> {
+        let mut sort_by = SortBy::Count;
+        loop {
+            let snapshot = self.snapshot();
+            terminal.draw(|frame| draw(frame, &snapshot, sort_by))?;
+
+            let deadline = Instant::now() + refresh;
+            while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+                if !event::poll(timeout)? {
+                    break;
+                }
+                let key = match event::read()? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                    _ => continue,
+                };
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') => sort_by = SortBy::Count,
+                    KeyCode::Char('d') => sort_by = SortBy::Duration,
+                    _ => continue,
+                }
+                break;
+            }
+        }
+    }
+}
+
+fn draw(frame: &mut Frame<'_>, snapshot: &CallGraphSnapshot, sort_by: SortBy) {
+    let [nodes, edges] = Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .areas(frame.area());
+    draw_nodes(frame, nodes, snapshot, sort_by);
+    draw_edges(frame, edges, snapshot, sort_by);
+}
+
+fn draw_nodes(frame: &mut Frame<'_>, area: Rect, snapshot: &CallGraphSnapshot, sort_by: SortBy) {
+    let mut nodes: Vec<_> = snapshot.nodes().iter().collect();
+    match sort_by {
+        SortBy::Count => nodes.sort_by_key(|node| Reverse(node.calls)),
+        SortBy::Duration => nodes.sort_by_key(|node| Reverse(node.duration)),
+    }
+    let rows = nodes.into_iter().map(|node| {
+        Row::new(vec![
+            node.name.clone(),
+            node.calls.to_string(),
+            format!("{:?}", node.duration),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Fill(1),
+            Constraint::Length(12),
+            Constraint::Length(16),
+        ],
+    )
+    .header(header(["node", "calls", "duration"]))
+    .block(Block::bordered().title(title("Nodes", sort_by)));
+    frame.render_widget(table, area);
+}
+
+fn draw_edges(frame: &mut Frame<'_>, area: Rect, snapshot: &CallGraphSnapshot, sort_by: SortBy) {
+    let mut edges: Vec<_> = snapshot.edges().iter().collect();
+    match sort_by {
+        SortBy::Count => edges.sort_by_key(|edge| Reverse(edge.count)),
+        SortBy::Duration => edges.sort_by_key(|edge| Reverse(edge.duration)),
+    }
+    let rows = edges.into_iter().map(|edge| {
+        Row::new(vec![
+            snapshot.nodes()[edge.caller].name.clone(),
+            snapshot.nodes()[edge.callee].name.clone(),
+            edge.count.to_string(),
+            format!("{:?}", edge.duration),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Length(12),
+            Constraint::Length(16),
+        ],
+    )
+    .header(header(["caller", "callee", "count", "duration"]))
+    .block(Block::bordered().title(title("Edges", sort_by)));
+    frame.render_widget(table, area);
+}
+
+fn header<const N: usize>(cells: [&'static str; N]) -> Row<'static> {
+    Row::new(cells).style(Style::new().add_modifier(Modifier::BOLD))
+}
+
+fn title(name: &str, sort_by: SortBy) -> String {
+    let sort_by = match sort_by {
+        SortBy::Count => "count",
+        SortBy::Duration => "duration",
+    };
+    format!(" {} by {} ([c]ount, [d]uration, [q]uit) ", name, sort_by)
+}
@@ -0,0 +1,108 @@
+use crate::{gzip, template};
+use std::{
+    fmt,
+    io::{self, Write},
+    path::Path,
+};
+
+/// A writer handing what was written since the previous flush to a function
+/// on every flush, along with a key expanded from a template, such as for
+/// batch jobs in containers without a persistent disk.
+///
+/// This doesn't talk to any store itself: the function is given the key and
+/// the contents, and stores them however the application does, such as by
+/// putting them as an object with the application's own object storage
+/// client. An async client can be driven from it, such as with
+/// `tokio::runtime::Handle::block_on`, or the upload spawned onto a runtime
+/// to return right away.
+///
+/// In the key template, `{flush}` is replaced with the number of the flush,
+/// counting from 1, `{pid}` with the id of the process and `{timestamp}`
+/// with the seconds since the Unix epoch at the flush. With the `flate2`
+/// feature, contents whose key ends with `.gz` are gzip-compressed. Data
+/// still buffered when the writer is dropped is handed over, ignoring
+/// errors.
+///
+/// ```rust
+/// use std::sync::{Arc, Mutex};
+/// use tracing_callgraph::{GraphLayer, KeyedUpload};
+/// use tracing_subscriber::{prelude::*, Registry};
+///
+/// #[tracing::instrument]
+/// fn handle() {}
+///
+/// let uploaded = Arc::new(Mutex::new(Vec::new()));
+/// let objects = uploaded.clone();
+/// let upload = KeyedUpload::new("graphs/job-{pid}/{flush}.dot", move |key, object| {
+///     // `client.put_object().bucket("traces").key(key).body(object.into())`
+///     objects.lock().unwrap().push((key.to_string(), object));
+///     Ok(())
+/// });
+/// let (layer, guard) = GraphLayer::builder().with_writer(upload);
+/// tracing::subscriber::with_default(Registry::default().with(layer), handle);
+/// drop(guard);
+///
+/// let uploaded = uploaded.lock().unwrap();
+/// assert_eq!(uploaded[0].0, format!("graphs/job-{}/1.dot", std::process::id()));
+/// assert!(uploaded[0].1.starts_with(b"digraph {"));
+/// ```
+pub struct KeyedUpload {
+    key: String,
+    upload: Box<Upload>,
+    flushes: u64,
+    buffer: Vec<u8>,
+}
+
+/// Stores the contents of a flush under a key.
+type Upload = dyn FnMut(&str, Vec<u8>) -> io::Result<()> + Send;
+
+impl KeyedUpload {
+    /// Returns a writer handing every flush to `upload`, with a key
+    /// expanded from the template `key`.
+    pub fn new<F>(key: impl Into<String>, upload: F) -> Self
+    where
+        F: FnMut(&str, Vec<u8>) -> io::Result<()> + Send + 'static,
+    {
+        Self {
+            key: key.into(),
+            upload: Box::new(upload),
+            flushes: 0,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Write for KeyedUpload {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.flushes += 1;
+        let key = template::expand_str(&self.key.replace("{flush}", &self.flushes.to_string()));
+        let mut object = std::mem::take(&mut self.buffer);
+        if gzip::is_gzip(Path::new(&key)) {
+            object = gzip::compress(&object)?;
+        }
+        (self.upload)(&key, object)
+    }
+}
+
+impl Drop for KeyedUpload {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl fmt::Debug for KeyedUpload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyedUpload")
+            .field("key", &self.key)
+            .field("flushes", &self.flushes)
+            .finish_non_exhaustive()
+    }
+}
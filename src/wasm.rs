@@ -0,0 +1,64 @@
+use crate::{Builder, FlushGuard, GraphLayer};
+use js_sys::Function;
+use std::io::{self, Write};
+use wasm_bindgen::JsValue;
+
+/// A writer handing everything written to it to a JavaScript callback, as a
+/// single string per flush.
+///
+/// Paired with a [`FlushGuard`], the callback receives the `dot` output of
+/// the graph, so applications running in the browser can produce call
+/// graphs without a filesystem. Requires the `wasm` feature.
+#[derive(Debug)]
+pub struct JsWriter {
+    callback: Function,
+    buffer: Vec<u8>,
+}
+
+impl JsWriter {
+    /// Returns a writer calling `callback` with the written text on flush.
+    pub fn new(callback: Function) -> Self {
+        Self {
+            callback,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Write for JsWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&self.buffer);
+        self.callback
+            .call1(&JsValue::NULL, &JsValue::from_str(&text))
+            .map_err(|e| io::Error::other(format!("{:?}", e)))?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl Builder {
+    /// Builds the configured [`GraphLayer`] and a [`FlushGuard`] which hands
+    /// the graph to `callback` when dropped, see [`JsWriter`].
+    pub fn with_js_callback(self, callback: Function) -> (GraphLayer, FlushGuard<JsWriter>) {
+        let layer = self.build();
+        let guard = layer.flush_on_drop(JsWriter::new(callback));
+        (layer, guard)
+    }
+}
+
+impl GraphLayer {
+    /// Constructs a `GraphLayer` that constructs the call graph, and a
+    /// `FlushGuard` which hands the graph to `callback` when dropped, see
+    /// [`JsWriter`].
+    pub fn with_js_callback(callback: Function) -> (Self, FlushGuard<JsWriter>) {
+        Builder::new().with_js_callback(callback)
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    convert::TryFrom,
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// A quantity accumulated on nodes and edges, such as a number of calls,
+/// errors or bytes, or a duration.
+pub(crate) trait Weight: Copy {
+    /// Returns `self + other`, or the largest weight if that overflows.
+    fn saturating_add(self, other: Self) -> Self;
+
+    /// Returns the weight as the 64 bits it is recorded in, saturating.
+    fn to_bits(self) -> u64;
+
+    /// Returns the weight recorded in `bits`.
+    fn from_bits(bits: u64) -> Self;
+}
+
+impl Weight for u64 {
+    fn saturating_add(self, other: Self) -> Self {
+        u64::saturating_add(self, other)
+    }
+
+    fn to_bits(self) -> u64 {
+        self
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        bits
+    }
+}
+
+/// Durations are recorded in whole nanoseconds, saturating after more than
+/// 584 years.
+impl Weight for Duration {
+    fn saturating_add(self, other: Self) -> Self {
+        Duration::saturating_add(self, other)
+    }
+
+    fn to_bits(self) -> u64 {
+        u64::try_from(self.as_nanos()).unwrap_or(u64::MAX)
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        Duration::from_nanos(bits)
+    }
+}
+
+/// Adds `weight` to `total`, saturating.
+pub(crate) fn accumulate<W: Weight>(total: &mut W, weight: W) {
+    *total = total.saturating_add(weight);
+}
+
+/// A [`Weight`] accumulated atomically, so that it can be added to with
+/// shared access.
+///
+/// The weight saturates at its largest value rather than wrapping around,
+/// so that the counts of week-long runs stay meaningful.
+#[derive(Debug, Default)]
+pub(crate) struct AtomicWeight<W> {
+    bits: AtomicU64,
+    weight: PhantomData<W>,
+}
+
+impl<W: Weight> AtomicWeight<W> {
+    pub(crate) fn get(&self) -> W {
+        W::from_bits(self.bits.load(Ordering::Relaxed))
+    }
+
+    /// Adds `weight`, saturating.
+    pub(crate) fn add(&self, weight: W) {
+        let bits = weight.to_bits();
+        // Fixing up an overflow after the fact is cheaper than a
+        // compare-and-swap loop on every addition.
+        if self.bits.fetch_add(bits, Ordering::Relaxed) > u64::MAX - bits {
+            self.bits.store(u64::MAX, Ordering::Relaxed);
+        }
+    }
+}
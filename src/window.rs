@@ -0,0 +1,95 @@
+use crate::{graph::CallGraph, hash::BuildHasher, sync::RwLock, time::Instant};
+use std::{collections::VecDeque, time::Duration};
+
+/// The number of buckets spanning a window.
+const BUCKETS: u64 = 60;
+
+/// The calls of a graph within a sliding time window, see
+/// [`Builder::window`].
+///
+/// Calls are recorded into buckets by the time they are made, each spanning
+/// a sixtieth of the window, and buckets are dropped from snapshots once
+/// they start before the window. Expired buckets are freed when the next
+/// one is added, so taking a snapshot changes nothing.
+///
+/// [`Builder::window`]: crate::Builder::window
+#[derive(Debug)]
+pub(crate) struct Window {
+    start: Instant,
+    /// The time spanned by a bucket, in nanoseconds.
+    width: u128,
+    hasher: BuildHasher,
+    /// The buckets that may still be within the window, oldest first, with
+    /// the number of widths between the start and their own start.
+    buckets: RwLock<VecDeque<(u64, RwLock<CallGraph>)>>,
+}
+
+impl Window {
+    pub(crate) fn new(length: Duration, hasher: BuildHasher) -> Self {
+        Self {
+            start: Instant::now(),
+            width: (length.as_nanos() / u128::from(BUCKETS)).max(1),
+            hasher,
+            buckets: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    /// Returns the number of the bucket recording the calls made now.
+    fn now(&self) -> u64 {
+        let elapsed = Instant::now().duration_since(self.start).as_nanos();
+        (elapsed / self.width).min(u128::from(u64::MAX)) as u64
+    }
+
+    /// Runs `f` with the bucket recording the calls made now, adding it and
+    /// freeing the expired buckets first if it is the first call made in
+    /// its bucket.
+    pub(crate) fn with_bucket<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&RwLock<CallGraph>) -> R,
+    {
+        let now = self.now();
+        {
+            let buckets = self.buckets.read();
+            // Another thread may have added a later bucket in the meantime.
+            if let Some((_, graph)) = buckets.back().filter(|(start, _)| *start >= now) {
+                return f(graph);
+            }
+        }
+        let mut buckets = self.buckets.write();
+        if buckets.back().is_none_or(|(start, _)| *start < now) {
+            while buckets
+                .front()
+                .is_some_and(|(start, _)| start.saturating_add(BUCKETS) <= now)
+            {
+                buckets.pop_front();
+            }
+            buckets.push_back((now, RwLock::new(CallGraph::new(self.hasher.clone()))));
+        }
+        let (_, graph) = buckets
+            .back()
+            .expect("expected: a bucket was just pushed if missing");
+        f(graph)
+    }
+
+    /// Runs `f` with every bucket, including expired ones.
+    pub(crate) fn for_each<F>(&self, f: F)
+    where
+        F: FnMut(&RwLock<CallGraph>),
+    {
+        self.buckets
+            .read()
+            .iter()
+            .map(|(_, graph)| graph)
+            .for_each(f)
+    }
+
+    /// Merges the calls made within the window into `merged`.
+    pub(crate) fn merge_into(&self, merged: &mut CallGraph) {
+        let now = self.now();
+        for (start, graph) in self.buckets.read().iter() {
+            if start.saturating_add(BUCKETS) > now {
+                merged.merge(&graph.read());
+            }
+        }
+    }
+}
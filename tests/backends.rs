@@ -0,0 +1,20 @@
+mod common;
+
+use tracing_callgraph::{Backend, GraphLayer};
+
+#[test]
+fn channel_records_like_thread_local() {
+    let expected = common::record(GraphLayer::builder());
+    assert!(expected.edge("handle", "query").unwrap().errors > 0);
+    let snapshot = common::record(GraphLayer::builder().backend(Backend::Channel(16)));
+    assert_eq!(common::counts(&snapshot), common::counts(&expected));
+}
+
+#[test]
+fn sharded_records_like_thread_local() {
+    let expected = common::record(GraphLayer::builder());
+    for shards in [1, 4] {
+        let snapshot = common::record(GraphLayer::builder().backend(Backend::Sharded(shards)));
+        assert_eq!(common::counts(&snapshot), common::counts(&expected));
+    }
+}
@@ -0,0 +1,90 @@
+// Not every test uses every helper.
+#![allow(dead_code)]
+
+use std::{thread, time::Duration};
+use tracing_callgraph::{Builder, CallGraphSnapshot};
+
+#[tracing::instrument]
+fn handle(request: u64) {
+    if request.is_multiple_of(3) {
+        cache();
+    }
+    query(request);
+}
+
+#[tracing::instrument]
+fn cache() {}
+
+#[tracing::instrument]
+fn query(request: u64) {
+    thread::sleep(Duration::from_millis(1));
+    if request.is_multiple_of(2) {
+        tracing::error!("query failed");
+    }
+}
+
+/// Records a few requests, some of them failing or over their threshold,
+/// with `builder`.
+pub fn record(builder: Builder) -> CallGraphSnapshot {
+    builder
+        .edge_order(true)
+        .sla("query", Duration::ZERO)
+        .record(|| (0..10).for_each(handle))
+}
+
+/// The names and counts of the nodes and edges of `snapshot`, sorted, which
+/// unlike durations don't change between runs.
+pub fn counts(snapshot: &CallGraphSnapshot) -> Vec<String> {
+    let nodes = snapshot.nodes();
+    let mut counts: Vec<_> = nodes
+        .iter()
+        .map(|node| format!("{} {} {}", node.name, node.calls, node.sla_violations))
+        .chain(snapshot.edges().iter().map(|edge| {
+            format!(
+                "{} -> {} {} {:?} {} {}",
+                nodes[edge.caller].name,
+                nodes[edge.callee].name,
+                edge.count,
+                edge.order,
+                edge.errors,
+                edge.sla_violations
+            )
+        }))
+        .collect();
+    counts.sort();
+    counts
+}
+
+/// A graph whose counts and durations are all saturated, where `outer`
+/// calls both `first` and `second`, and `first` calls `second`.
+#[cfg(feature = "serde_json")]
+pub fn saturated() -> CallGraphSnapshot {
+    let count = u64::MAX;
+    let duration = format!(
+        r#"{{"secs": {}, "nanos": {}}}"#,
+        Duration::MAX.as_secs(),
+        Duration::MAX.subsec_nanos()
+    );
+    let node = |name: &str| {
+        format!(
+            r#"{{"name": "{}", "calls": {}, "duration": {}}}"#,
+            name, count, duration
+        )
+    };
+    let edge = |caller: usize, callee: usize| {
+        format!(
+            r#"{{"caller": {}, "callee": {}, "count": {}, "duration": {}}}"#,
+            caller, callee, count, duration
+        )
+    };
+    let json = format!(
+        r#"{{"nodes": [{}, {}, {}], "edges": [{}, {}, {}]}}"#,
+        node("outer"),
+        node("first"),
+        node("second"),
+        edge(0, 1),
+        edge(0, 2),
+        edge(1, 2)
+    );
+    serde_json::from_str(&json).expect("expected: a valid snapshot")
+}
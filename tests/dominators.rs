@@ -0,0 +1,89 @@
+use tracing_callgraph::{CallGraphSnapshot, GraphLayer};
+
+/// The `(dominator, node)` edges of the dominator tree rooted at `root`.
+fn tree(snapshot: &CallGraphSnapshot, root: &str) -> Vec<(String, String)> {
+    let tree = snapshot.dominator_tree(root).unwrap();
+    let name = |node: usize| tree.nodes()[node].name.clone();
+    tree.edges()
+        .iter()
+        .map(|edge| (name(edge.caller), name(edge.callee)))
+        .collect()
+}
+
+fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(a, b)| (a.to_string(), b.to_string()))
+        .collect()
+}
+
+#[tracing::instrument]
+fn top() {
+    left();
+    right();
+}
+
+#[tracing::instrument]
+fn left() {
+    bottom();
+}
+
+#[tracing::instrument]
+fn right() {
+    bottom();
+}
+
+#[tracing::instrument]
+fn bottom() {}
+
+#[test]
+fn diamond() {
+    let snapshot = GraphLayer::builder().record(top);
+    // Neither side dominates `bottom`, which is reachable through both.
+    assert_eq!(
+        tree(&snapshot, "top"),
+        pairs(&[("top", "bottom"), ("top", "left"), ("top", "right")])
+    );
+    // From `left`, only `bottom` is reachable.
+    let from_left = snapshot.dominator_tree("left").unwrap();
+    assert_eq!(from_left.nodes().len(), 2);
+}
+
+#[tracing::instrument]
+fn start() {
+    step(2);
+}
+
+#[tracing::instrument]
+fn step(remaining: u32) {
+    if remaining > 0 {
+        check(remaining);
+    }
+}
+
+#[tracing::instrument]
+fn check(remaining: u32) {
+    step(remaining - 1);
+}
+
+#[test]
+fn loop_through_recursion() {
+    let snapshot = GraphLayer::builder().record(start);
+    assert_eq!(
+        tree(&snapshot, "start"),
+        pairs(&[("start", "step"), ("step", "check")])
+    );
+}
+
+#[tracing::instrument]
+fn alone() {}
+
+#[test]
+fn lone_root() {
+    let snapshot = GraphLayer::builder().record(alone);
+    let tree = snapshot.dominator_tree("alone").unwrap();
+    assert_eq!(tree.nodes().len(), 1);
+    assert_eq!(tree.nodes()[0].name, "alone");
+    assert!(tree.edges().is_empty());
+    assert!(snapshot.dominator_tree("missing").is_none());
+}
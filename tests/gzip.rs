@@ -0,0 +1,56 @@
+#![cfg(feature = "flate2")]
+
+use flate2::read::GzDecoder;
+use std::{fs, io::Read};
+use tracing_callgraph::GraphLayer;
+use tracing_subscriber::{prelude::*, Registry};
+
+#[tracing::instrument]
+fn outer() {
+    inner()
+}
+
+#[tracing::instrument]
+fn inner() {}
+
+fn gunzip(path: &std::path::Path) -> String {
+    let mut text = String::new();
+    GzDecoder::new(fs::File::open(path).unwrap())
+        .read_to_string(&mut text)
+        .unwrap();
+    text
+}
+
+#[test]
+fn gunzipped_output_equals_plain_output() {
+    let dir = std::env::temp_dir();
+    let plain = dir.join(format!("gzip-test-{}.dot", std::process::id()));
+    let compressed = dir.join(format!("gzip-test-{}.dot.gz", std::process::id()));
+    for path in [&plain, &compressed] {
+        let (layer, guard) = GraphLayer::builder().with_file(path).unwrap();
+        tracing::subscriber::with_default(Registry::default().with(layer), outer);
+        drop(guard);
+    }
+    let dot = fs::read_to_string(&plain).unwrap();
+    assert!(dot.contains("outer"));
+    assert_eq!(gunzip(&compressed), dot);
+    fs::remove_file(&plain).unwrap();
+    fs::remove_file(&compressed).unwrap();
+}
+
+#[test]
+fn compressed_output_accumulates() {
+    let path = std::env::temp_dir().join(format!("gzip-accumulate-{}.dot.gz", std::process::id()));
+    let _ = fs::remove_file(&path);
+    for _ in 0..3 {
+        let (layer, guard) = GraphLayer::builder()
+            .accumulate(true)
+            .with_file(&path)
+            .unwrap();
+        tracing::subscriber::with_default(Registry::default().with(layer), outer);
+        drop(guard);
+    }
+    let snapshot = tracing_callgraph::CallGraphSnapshot::from_dot(&gunzip(&path)).unwrap();
+    assert_eq!(snapshot.edge("outer", "inner").unwrap().count, 3);
+    fs::remove_file(&path).unwrap();
+}
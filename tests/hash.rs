@@ -0,0 +1,15 @@
+mod common;
+
+use tracing_callgraph::{GraphLayer, HashFunction};
+
+#[test]
+fn fx_records_like_sip() {
+    let expected = common::record(GraphLayer::builder().hash_function(HashFunction::Sip));
+    let snapshot = common::record(GraphLayer::builder().hash_function(HashFunction::Fx));
+    assert_eq!(common::counts(&snapshot), common::counts(&expected));
+    // Nodes are sorted, so the output doesn't depend on the hash function.
+    assert_eq!(snapshot.to_dot(), expected.to_dot());
+
+    let again = common::record(GraphLayer::builder().hash_function(HashFunction::Fx));
+    assert_eq!(again.to_dot(), snapshot.to_dot());
+}
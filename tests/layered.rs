@@ -0,0 +1,125 @@
+use tracing::{span, Event, Metadata, Subscriber};
+use tracing_callgraph::{CallGraphSnapshot, GraphLayer};
+use tracing_subscriber::{
+    filter::filter_fn,
+    layer::{Context, Layer},
+    prelude::*,
+    registry::LookupSpan,
+    Registry,
+};
+
+#[tracing::instrument]
+fn outer() {
+    unrecorded()
+}
+
+#[tracing::instrument]
+fn unrecorded() {
+    inner()
+}
+
+#[tracing::instrument]
+fn inner() {}
+
+fn is_unrecorded(metadata: Option<&Metadata<'_>>) -> bool {
+    metadata.is_some_and(|metadata| metadata.name() == "unrecorded")
+}
+
+/// Hides the `unrecorded` span from a layer, as a layer combinator
+/// forwarding only some spans would.
+struct Unseen(GraphLayer);
+
+impl<S> Layer<S> for Unseen
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if !is_unrecorded(Some(attrs.metadata())) {
+            self.0.new_span(attrs, id, ctx)
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        self.0.on_event(event, ctx)
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if !is_unrecorded(ctx.metadata(id)) {
+            self.0.on_enter(id, ctx)
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if !is_unrecorded(ctx.metadata(id)) {
+            self.0.on_exit(id, ctx)
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        if !is_unrecorded(ctx.metadata(&id)) {
+            self.0.on_close(id, ctx)
+        }
+    }
+}
+
+/// A layer seeing every span.
+struct Everything;
+
+impl<S: Subscriber> Layer<S> for Everything {}
+
+/// Records `outer` with `layer`, which doesn't see the `unrecorded` span.
+fn record_unseen(layer: GraphLayer) -> CallGraphSnapshot {
+    let subscriber = Registry::default().with(Unseen(layer.clone()));
+    tracing::subscriber::with_default(subscriber, outer);
+    layer.snapshot()
+}
+
+#[test]
+fn unrecorded_parents_are_callers_by_default() {
+    let snapshot = record_unseen(GraphLayer::builder().build());
+    assert_eq!(snapshot.edge("unrecorded", "inner").unwrap().count, 1);
+    assert!(snapshot.edge("outer", "unrecorded").is_none());
+    assert!(snapshot.edge("outer", "inner").is_none());
+}
+
+#[test]
+fn unrecorded_parents_are_skipped() {
+    let snapshot = record_unseen(GraphLayer::builder().skip_unrecorded_parents(true).build());
+    assert_eq!(snapshot.edge("outer", "inner").unwrap().count, 1);
+    assert!(snapshot
+        .nodes()
+        .iter()
+        .all(|node| node.name != "unrecorded"));
+}
+
+#[test]
+fn filtered_parents_are_skipped() {
+    let layer = GraphLayer::builder().build();
+    let filtered = layer
+        .clone()
+        .with_filter(filter_fn(|metadata| metadata.name() != "unrecorded"));
+    // Another layer sees every span, so that the filtered span is created.
+    let subscriber = Registry::default().with(Everything).with(filtered);
+    tracing::subscriber::with_default(subscriber, outer);
+    let snapshot = layer.snapshot();
+    assert_eq!(snapshot.edge("outer", "inner").unwrap().count, 1);
+    assert!(snapshot
+        .nodes()
+        .iter()
+        .all(|node| node.name != "unrecorded"));
+}
+
+#[test]
+fn unknown_spans_are_counted() {
+    let layer = GraphLayer::builder().build();
+    let subscriber = Registry::default().with(layer.clone());
+    // Exiting a span is forwarded to the layer whether or not it exists.
+    subscriber.exit(&span::Id::from_u64(42));
+    assert_eq!(layer.unknown_spans(), 1);
+    tracing::subscriber::with_default(subscriber, outer);
+    assert_eq!(layer.unknown_spans(), 1);
+    assert_eq!(
+        layer.snapshot().edge("outer", "unrecorded").unwrap().count,
+        1
+    );
+}
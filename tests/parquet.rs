@@ -0,0 +1,183 @@
+#![cfg(feature = "parquet")]
+
+mod common;
+
+use std::{collections::HashMap, convert::TryInto};
+use tracing_callgraph::GraphLayer;
+
+/// A value decoded from the Thrift compact protocol.
+#[derive(Debug)]
+enum Value {
+    Int(i64),
+    Binary(Vec<u8>),
+    List(Vec<Value>),
+    Struct(HashMap<i16, Value>),
+}
+
+impl Value {
+    fn int(&self) -> i64 {
+        match self {
+            Value::Int(value) => *value,
+            value => panic!("not an integer: {:?}", value),
+        }
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            Value::Binary(bytes) => std::str::from_utf8(bytes).unwrap(),
+            value => panic!("not binary: {:?}", value),
+        }
+    }
+
+    fn list(&self) -> &[Value] {
+        match self {
+            Value::List(elements) => elements,
+            value => panic!("not a list: {:?}", value),
+        }
+    }
+
+    fn field(&self, id: i16) -> &Value {
+        match self {
+            Value::Struct(fields) => &fields[&id],
+            value => panic!("not a struct: {:?}", value),
+        }
+    }
+}
+
+/// Decodes the Thrift compact protocol, as written in Parquet metadata.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> u8 {
+        self.position += 1;
+        self.bytes[self.position - 1]
+    }
+
+    fn varint(&mut self) -> u64 {
+        let mut value = 0;
+        for shift in (0..).step_by(7) {
+            let byte = self.byte();
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                break;
+            }
+        }
+        value
+    }
+
+    fn zigzag(&mut self) -> i64 {
+        let value = self.varint();
+        (value >> 1) as i64 ^ -((value & 1) as i64)
+    }
+
+    fn value(&mut self, kind: u8) -> Value {
+        match kind {
+            5 | 6 => Value::Int(self.zigzag()),
+            8 => {
+                let len = self.varint() as usize;
+                self.position += len;
+                Value::Binary(self.bytes[self.position - len..self.position].to_vec())
+            }
+            9 => {
+                let header = self.byte();
+                let len = match header >> 4 {
+                    15 => self.varint() as usize,
+                    len => len as usize,
+                };
+                Value::List((0..len).map(|_| self.value(header & 0x0f)).collect())
+            }
+            12 => self.structure(),
+            kind => panic!("unexpected type {}", kind),
+        }
+    }
+
+    fn structure(&mut self) -> Value {
+        let mut fields = HashMap::new();
+        let mut last = 0;
+        loop {
+            let header = self.byte();
+            if header == 0 {
+                return Value::Struct(fields);
+            }
+            let id = match header >> 4 {
+                0 => self.zigzag() as i16,
+                delta => last + i16::from(delta),
+            };
+            last = id;
+            fields.insert(id, self.value(header & 0x0f));
+        }
+    }
+}
+
+#[test]
+fn footer_describes_edges() {
+    let snapshot = common::record(GraphLayer::builder());
+    let table = snapshot.to_parquet();
+
+    let len = u32::from_le_bytes(table[table.len() - 8..table.len() - 4].try_into().unwrap());
+    let start = table.len() - 8 - len as usize;
+    let metadata = Reader {
+        bytes: &table[..table.len() - 8],
+        position: start,
+    }
+    .structure();
+    let edges = snapshot.edges();
+    assert_eq!(metadata.field(3).int(), edges.len() as i64);
+
+    let schema = metadata.field(2).list();
+    let names: Vec<_> = schema[1..]
+        .iter()
+        .map(|column| column.field(4).text())
+        .collect();
+    assert_eq!(names, ["caller", "callee", "count", "total_ns", "errors"]);
+
+    let row_groups = metadata.field(4).list();
+    assert_eq!(row_groups.len(), 1);
+    let mut columns = Vec::new();
+    for chunk in row_groups[0].field(1).list() {
+        let meta = chunk.field(3);
+        assert_eq!(meta.field(5).int(), edges.len() as i64);
+        let mut reader = Reader {
+            bytes: &table,
+            position: meta.field(9).int() as usize,
+        };
+        let page = reader.structure();
+        let data = &table[reader.position..reader.position + page.field(3).int() as usize];
+        columns.push(data);
+    }
+
+    let name = |node: usize| snapshot.nodes()[node].name.clone();
+    let texts = |mut data: &[u8]| {
+        std::iter::from_fn(move || {
+            let (len, rest) = data.split_at(4);
+            let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+            let (text, rest) = rest.split_at(len);
+            data = rest;
+            Some(String::from_utf8(text.to_vec()).unwrap())
+        })
+        .take(edges.len())
+        .collect::<Vec<_>>()
+    };
+    let ints = |data: &[u8]| -> Vec<i64> {
+        data.chunks(8)
+            .map(|value| i64::from_le_bytes(value.try_into().unwrap()))
+            .collect()
+    };
+    let callers: Vec<_> = edges.iter().map(|edge| name(edge.caller)).collect();
+    let callees: Vec<_> = edges.iter().map(|edge| name(edge.callee)).collect();
+    assert_eq!(texts(columns[0]), callers);
+    assert_eq!(texts(columns[1]), callees);
+    let counts: Vec<_> = edges.iter().map(|edge| edge.count as i64).collect();
+    let durations: Vec<_> = edges
+        .iter()
+        .map(|edge| edge.duration.as_nanos() as i64)
+        .collect();
+    let errors: Vec<_> = edges.iter().map(|edge| edge.errors as i64).collect();
+    assert_eq!(ints(columns[2]), counts);
+    assert_eq!(ints(columns[3]), durations);
+    assert_eq!(ints(columns[4]), errors);
+    assert!(errors.iter().any(|&errors| errors > 0));
+}
@@ -0,0 +1,130 @@
+use std::{thread, time::Duration};
+use tracing_callgraph::{CallGraphSnapshot, GraphLayer};
+
+fn names(snapshot: &CallGraphSnapshot, path: &[usize]) -> Vec<String> {
+    path.iter()
+        .map(|&node| snapshot.nodes()[node].name.clone())
+        .collect()
+}
+
+#[tracing::instrument]
+fn outer() {
+    slow();
+    nested();
+}
+
+/// Slower than `nested` itself, but not than the calls below it.
+#[tracing::instrument]
+fn slow() {
+    thread::sleep(Duration::from_millis(30));
+}
+
+#[tracing::instrument]
+fn nested() {
+    inner();
+}
+
+#[tracing::instrument]
+fn inner() {
+    thread::sleep(Duration::from_millis(25));
+}
+
+#[test]
+fn critical_path_is_longest() {
+    let snapshot = GraphLayer::builder().record(outer);
+    // Following the slowest callee would go from `outer` to `slow`.
+    assert_eq!(
+        names(&snapshot, &snapshot.critical_path()),
+        ["outer", "nested", "inner"]
+    );
+}
+
+#[tracing::instrument]
+fn recursive(depth: u32) {
+    if depth > 0 {
+        recursive(depth - 1);
+    }
+    inner();
+}
+
+#[test]
+fn critical_path_skips_recursion() {
+    let snapshot = GraphLayer::builder().record(|| recursive(2));
+    assert_eq!(
+        names(&snapshot, &snapshot.critical_path()),
+        ["recursive", "inner"]
+    );
+}
+
+#[test]
+fn critical_path_of_empty_graph() {
+    assert!(CallGraphSnapshot::default().critical_path().is_empty());
+}
+
+/// A chain of `diamonds`, each splitting into two branches called `left`
+/// and `right` times, so that the number of paths doubles with every one,
+/// ending in a call from `end` to `tail` made `tail` times.
+fn diamonds(diamonds: usize, left: u64, right: u64, tail: u64) -> CallGraphSnapshot {
+    let mut dot = String::from("digraph {\n");
+    for diamond in 0..diamonds {
+        for (offset, name) in ["joint", "left", "right"].iter().enumerate() {
+            dot += &format!(
+                "    {} [ label = \"{}{}\" ]\n",
+                3 * diamond + offset,
+                name,
+                diamond
+            );
+        }
+    }
+    dot += &format!("    {} [ label = \"end\" ]\n", 3 * diamonds);
+    dot += &format!("    {} [ label = \"tail\" ]\n", 3 * diamonds + 1);
+    dot += &format!(
+        "    {} -> {} [ label = \"{}\" ]\n",
+        3 * diamonds,
+        3 * diamonds + 1,
+        tail
+    );
+    for diamond in 0..diamonds {
+        for (offset, count) in [(1, left), (2, right)] {
+            let (joint, side) = (3 * diamond, 3 * diamond + offset);
+            dot += &format!("    {} -> {} [ label = \"{}\" ]\n", joint, side, count);
+            dot += &format!("    {} -> {} [ label = \"{}\" ]\n", side, joint + 3, count);
+        }
+    }
+    dot += "}\n";
+    CallGraphSnapshot::from_dot(&dot).unwrap()
+}
+
+#[test]
+fn top_paths_of_exponentially_many() {
+    let snapshot = diamonds(40, 3, 2, 3);
+    let paths = snapshot.top_paths(2);
+    assert_eq!(paths.len(), 2);
+    // Only taking the left branches keeps a count of 3.
+    assert_eq!(paths[0].count, 3);
+    assert_eq!(paths[0].nodes.len(), 82);
+    assert!(names(&snapshot, &paths[0].nodes)
+        .iter()
+        .all(|name| !name.starts_with("right")));
+    assert_eq!(paths[1].count, 2);
+
+    // Every path is as frequent as the others, but only once complete.
+    let snapshot = diamonds(40, 5, 5, 1);
+    let paths = snapshot.top_paths(2);
+    assert_eq!(paths.len(), 2);
+    assert!(paths.iter().all(|path| path.count == 1));
+}
+
+#[test]
+fn call_paths_of_exponentially_many() {
+    let snapshot = diamonds(40, 3, 2, 3);
+    let paths = snapshot.call_paths(9, 3);
+    assert_eq!(paths.len(), 3);
+    assert_eq!(paths[0].count, 3);
+    assert_eq!(paths[0].nodes.len(), 9);
+    assert!(paths.iter().all(|path| path.nodes.len() == 9));
+
+    let paths = snapshot.call_paths(usize::MAX, 2);
+    assert_eq!(paths.len(), 2);
+    assert_eq!(paths[0].nodes.len(), 82);
+}
@@ -0,0 +1,181 @@
+#![cfg(feature = "pprof")]
+
+mod common;
+
+use flate2::read::GzDecoder;
+use std::io::Read;
+use tracing_callgraph::{CallGraphSnapshot, GraphLayer};
+
+#[tracing::instrument]
+fn outer() {
+    inner();
+    inner();
+}
+
+#[tracing::instrument]
+fn inner() {}
+
+/// A field of a protobuf message, by its number.
+#[derive(Debug)]
+enum Field {
+    Varint(u64),
+    Bytes(Vec<u8>),
+}
+
+fn varint(bytes: &mut &[u8]) -> u64 {
+    let mut value = 0;
+    for shift in (0..).step_by(7) {
+        let byte = bytes[0];
+        *bytes = &bytes[1..];
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            break;
+        }
+    }
+    value
+}
+
+/// Decodes the fields of a protobuf message, in order.
+fn message(mut bytes: &[u8]) -> Vec<(u64, Field)> {
+    let mut fields = Vec::new();
+    while !bytes.is_empty() {
+        let key = varint(&mut bytes);
+        let field = match key & 7 {
+            0 => Field::Varint(varint(&mut bytes)),
+            2 => {
+                let len = varint(&mut bytes) as usize;
+                let (field, rest) = bytes.split_at(len);
+                bytes = rest;
+                Field::Bytes(field.to_vec())
+            }
+            wire => panic!("unexpected wire type {}", wire),
+        };
+        fields.push((key >> 3, field));
+    }
+    fields
+}
+
+/// Returns the messages, strings or packed values in field `number`.
+fn repeated(fields: &[(u64, Field)], number: u64) -> Vec<&[u8]> {
+    fields
+        .iter()
+        .filter(|(field, _)| *field == number)
+        .map(|(_, field)| match field {
+            Field::Bytes(bytes) => bytes.as_slice(),
+            field => panic!("not length-delimited: {:?}", field),
+        })
+        .collect()
+}
+
+fn varint_field(fields: &[(u64, Field)], number: u64) -> u64 {
+    match fields.iter().find(|(field, _)| *field == number) {
+        Some((_, Field::Varint(value))) => *value,
+        field => panic!("not a varint: {:?}", field),
+    }
+}
+
+fn packed(mut bytes: &[u8]) -> Vec<u64> {
+    let mut values = Vec::new();
+    while !bytes.is_empty() {
+        values.push(varint(&mut bytes));
+    }
+    values
+}
+
+/// Decodes the gzipped profile of `snapshot`.
+fn profile(snapshot: &CallGraphSnapshot) -> Vec<(u64, Field)> {
+    let mut profile = Vec::new();
+    GzDecoder::new(&snapshot.to_pprof()[..])
+        .read_to_end(&mut profile)
+        .unwrap();
+    message(&profile)
+}
+
+/// Decodes the locations and values of every sample of `profile`.
+fn samples(profile: &[(u64, Field)]) -> Vec<(Vec<u64>, Vec<u64>)> {
+    repeated(profile, 2)
+        .into_iter()
+        .map(|sample| {
+            let sample = message(sample);
+            (
+                packed(repeated(&sample, 1)[0]),
+                packed(repeated(&sample, 2)[0]),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn profile_decodes() {
+    let snapshot = GraphLayer::builder().record(outer);
+    let profile = profile(&snapshot);
+
+    let strings: Vec<_> = repeated(&profile, 6)
+        .into_iter()
+        .map(|string| std::str::from_utf8(string).unwrap())
+        .collect();
+    assert_eq!(
+        strings,
+        [
+            "",
+            "calls",
+            "count",
+            "time",
+            "nanoseconds",
+            "inner",
+            "outer"
+        ]
+    );
+    let sample_types: Vec<_> = repeated(&profile, 1)
+        .into_iter()
+        .map(|value_type| {
+            let value_type = message(value_type);
+            (varint_field(&value_type, 1), varint_field(&value_type, 2))
+        })
+        .collect();
+    assert_eq!(sample_types, [(1, 2), (3, 4)]);
+
+    // Locations and functions are numbered after the nodes, from 1.
+    let locations = repeated(&profile, 4);
+    assert_eq!(locations.len(), 2);
+    for (index, location) in locations.into_iter().enumerate() {
+        let location = message(location);
+        assert_eq!(varint_field(&location, 1), index as u64 + 1);
+        let line = message(repeated(&location, 4)[0]);
+        assert_eq!(varint_field(&line, 1), index as u64 + 1);
+    }
+    let functions: Vec<_> = repeated(&profile, 5)
+        .into_iter()
+        .map(|function| {
+            let function = message(function);
+            (varint_field(&function, 1), varint_field(&function, 2))
+        })
+        .collect();
+    assert_eq!(functions, [(1, 5), (2, 6)]);
+
+    // `inner` called from `outer` twice, and `outer` called once.
+    let samples = samples(&profile);
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[0].0, [1, 2]);
+    assert_eq!(samples[0].1[0], 2);
+    assert_eq!(samples[1].0, [2]);
+    assert_eq!(samples[1].1[0], 1);
+    let time: u64 = samples.iter().map(|(_, values)| values[1]).sum();
+    let outer = &snapshot.nodes()[snapshot.node_index("outer").unwrap()];
+    assert_eq!(u128::from(time), outer.duration.as_nanos());
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn saturated_profile_decodes() {
+    let snapshot = common::saturated();
+    let samples = samples(&profile(&snapshot));
+    // Locations are numbered after the sorted nodes: `first`, `outer` and
+    // `second`.
+    let stacks: Vec<_> = samples.iter().map(|(stack, _)| stack.clone()).collect();
+    assert_eq!(stacks, [vec![1, 2], vec![2], vec![3, 1], vec![3, 2]]);
+    assert!(samples
+        .iter()
+        .filter(|(stack, _)| stack.len() == 2)
+        .all(|(_, values)| values[0] == u64::MAX));
+}
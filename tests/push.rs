@@ -0,0 +1,81 @@
+#![cfg(feature = "http")]
+
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    thread::{self, JoinHandle},
+};
+use tracing_callgraph::{GraphLayer, HttpPush};
+use tracing_subscriber::{prelude::*, Registry};
+
+#[tracing::instrument]
+fn handle() {}
+
+/// Accepts one request on `listener`, responding with `204 No Content`.
+fn serve(listener: TcpListener) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let (mut connection, _) = listener.accept().unwrap();
+        let mut request = String::new();
+        connection.read_to_string(&mut request).unwrap();
+        connection
+            .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+            .unwrap();
+        request
+    })
+}
+
+fn push(url: &str) {
+    let (layer, guard) = GraphLayer::builder().with_http_push(HttpPush::new(url).unwrap());
+    tracing::subscriber::with_default(Registry::default().with(layer), handle);
+    drop(guard);
+}
+
+#[test]
+fn host_includes_port() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = serve(listener);
+    push(&format!("http://{}/graphs", address));
+    let request = server.join().unwrap();
+    assert!(request.contains(&format!("\r\nHost: 127.0.0.1:{}\r\n", address.port())));
+}
+
+#[test]
+fn pushes_to_ipv6() {
+    // Without IPv6 on the loopback interface, there is nothing to push to.
+    let listener = match TcpListener::bind("[::1]:0") {
+        Ok(listener) => listener,
+        Err(_) => return,
+    };
+    let port = listener.local_addr().unwrap().port();
+    let server = serve(listener);
+    push(&format!("http://[::1]:{}/graphs", port));
+    let request = server.join().unwrap();
+    assert!(request.contains(&format!("\r\nHost: [::1]:{}\r\n", port)));
+}
+
+#[test]
+fn rejects_invalid_urls() {
+    for url in [
+        "https://collector/",
+        "http://[::1/",
+        "http://::1/",
+        "http://:80/",
+    ] {
+        assert!(HttpPush::new(url).is_err(), "{}", url);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn pushes_json() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = serve(listener);
+    push(&format!("http://{}/graphs", address));
+    let request = server.join().unwrap();
+    assert!(request.contains("\r\nContent-Type: application/json\r\n"));
+    let (_, body) = request.split_once("\r\n\r\n").unwrap();
+    let snapshot: tracing_callgraph::CallGraphSnapshot = serde_json::from_str(body).unwrap();
+    assert_eq!(snapshot.nodes()[0].name, "handle");
+}
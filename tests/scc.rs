@@ -0,0 +1,95 @@
+use tracing_callgraph::{CallGraphSnapshot, GraphLayer};
+
+#[tracing::instrument]
+fn main_loop() {
+    ping(3);
+    countdown(2);
+}
+
+#[tracing::instrument]
+fn ping(remaining: u32) {
+    if remaining > 0 {
+        pong(remaining);
+    }
+}
+
+#[tracing::instrument]
+fn pong(remaining: u32) {
+    ping(remaining - 1);
+}
+
+#[tracing::instrument]
+fn countdown(remaining: u32) {
+    if remaining > 0 {
+        countdown(remaining - 1);
+    }
+}
+
+#[tracing::instrument]
+fn worker() {
+    task();
+}
+
+#[tracing::instrument]
+fn task() {}
+
+/// Records a cycle and a self-loop under one root, and a separate tree
+/// without recursion.
+fn record() -> CallGraphSnapshot {
+    GraphLayer::builder().record(|| {
+        main_loop();
+        worker();
+    })
+}
+
+fn names(snapshot: &CallGraphSnapshot, group: &[usize]) -> Vec<String> {
+    group
+        .iter()
+        .map(|&node| snapshot.nodes()[node].name.clone())
+        .collect()
+}
+
+#[test]
+fn groups_cycles_and_self_loops() {
+    let snapshot = record();
+    let groups: Vec<_> = snapshot
+        .recursive_groups()
+        .iter()
+        .map(|group| names(&snapshot, group))
+        .collect();
+    assert_eq!(groups, [vec!["countdown"], vec!["ping", "pong"]]);
+}
+
+#[test]
+fn condenses_groups_only() {
+    let snapshot = record();
+    let condensed = snapshot.condensed();
+    let nodes = names(
+        &condensed,
+        &(0..condensed.nodes().len()).collect::<Vec<_>>(),
+    );
+    assert_eq!(
+        nodes,
+        ["countdown", "main_loop", "ping | pong", "task", "worker"]
+    );
+
+    let cycle = condensed.edge("ping | pong", "ping | pong").unwrap();
+    let within =
+        snapshot.edge("ping", "pong").unwrap().count + snapshot.edge("pong", "ping").unwrap().count;
+    assert_eq!(cycle.count, within);
+    assert_eq!(
+        condensed.edge("countdown", "countdown").unwrap().count,
+        snapshot.edge("countdown", "countdown").unwrap().count
+    );
+    assert_eq!(condensed.edge("main_loop", "ping | pong").unwrap().count, 1);
+    // The separate tree is left as it is.
+    assert_eq!(condensed.edge("worker", "task").unwrap().count, 1);
+    assert_eq!(condensed.edges().len(), 5);
+}
+
+#[test]
+fn no_groups_without_recursion() {
+    let snapshot = GraphLayer::builder().record(worker);
+    assert!(snapshot.recursive_groups().is_empty());
+    assert_eq!(snapshot.condensed(), snapshot);
+}
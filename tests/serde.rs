@@ -0,0 +1,31 @@
+#![cfg(feature = "serde_json")]
+
+use tracing_callgraph::CallGraphSnapshot;
+
+const SNAPSHOT: &str = r#"{
+    "nodes": [
+        {"name": "query", "calls": 1, "duration": {"secs": 0, "nanos": 0}},
+        {"name": "handle", "calls": 1, "duration": {"secs": 0, "nanos": 0}},
+        {"name": "cache", "calls": 1, "duration": {"secs": 0, "nanos": 0}}
+    ],
+    "edges": [
+        {"caller": 1, "callee": 0, "count": 2, "duration": {"secs": 0, "nanos": 0}},
+        {"caller": 1, "callee": 2, "count": 1, "duration": {"secs": 0, "nanos": 0}}
+    ]
+}"#;
+
+#[test]
+fn deserializing_sorts_nodes_and_edges() {
+    let snapshot: CallGraphSnapshot = serde_json::from_str(SNAPSHOT).unwrap();
+    let names: Vec<_> = snapshot.nodes().iter().map(|node| &node.name).collect();
+    assert_eq!(names, ["cache", "handle", "query"]);
+    assert_eq!(snapshot.edge("handle", "query").unwrap().count, 2);
+    assert_eq!(snapshot.edge("handle", "cache").unwrap().count, 1);
+}
+
+#[test]
+fn deserializing_rejects_missing_nodes() {
+    let invalid = SNAPSHOT.replace(r#""callee": 2"#, r#""callee": 3"#);
+    let error = serde_json::from_str::<CallGraphSnapshot>(&invalid).unwrap_err();
+    assert!(error.to_string().contains("refers to node 3"));
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use tracing_callgraph::{CallGraphSnapshot, GraphLayer};
+use tracing_subscriber::{prelude::*, Registry};
+
+#[tracing::instrument]
+fn outer() {
+    inner()
+}
+
+#[tracing::instrument]
+fn inner() {}
+
+fn saturated() -> String {
+    format!(
+        "digraph {{\n    0 [ label = \"outer\" ]\n    1 [ label = \"inner\" ]\n    0 -> 1 [ label = \"{}\" ]\n}}\n",
+        u64::MAX
+    )
+}
+
+#[test]
+fn merged_counts_saturate() {
+    let dot = saturated();
+    let mut snapshot = CallGraphSnapshot::from_dot(&dot).unwrap();
+    let once = CallGraphSnapshot::from_dot(&dot.replace(&u64::MAX.to_string(), "1")).unwrap();
+    snapshot.merge(&once);
+    assert_eq!(snapshot.edge("outer", "inner").unwrap().count, u64::MAX);
+    snapshot.merge(&snapshot.clone());
+    assert_eq!(snapshot.edge("outer", "inner").unwrap().count, u64::MAX);
+}
+
+#[test]
+fn accumulated_counts_saturate() {
+    let path = std::env::temp_dir().join(format!("saturated-{}.dot", std::process::id()));
+    std::fs::write(&path, saturated()).unwrap();
+    let (layer, guard) = GraphLayer::builder()
+        .accumulate(true)
+        .with_file(&path)
+        .unwrap();
+    tracing::subscriber::with_default(Registry::default().with(layer), outer);
+    drop(guard);
+    let dot = std::fs::read_to_string(&path).unwrap();
+    let snapshot = CallGraphSnapshot::from_dot(&dot).unwrap();
+    assert_eq!(snapshot.edge("outer", "inner").unwrap().count, u64::MAX);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn merged_durations_saturate() {
+    use std::time::Duration;
+
+    let mut snapshot = common::saturated();
+    snapshot.merge(&common::saturated());
+    assert!(snapshot
+        .edges()
+        .iter()
+        .all(|edge| edge.duration == Duration::MAX && edge.count == u64::MAX));
+    assert!(snapshot
+        .nodes()
+        .iter()
+        .all(|node| node.duration == Duration::MAX && node.calls == u64::MAX));
+}
+
+/// Every output summing the durations of several edges saturates rather
+/// than panicking.
+#[cfg(feature = "serde_json")]
+#[test]
+fn exported_durations_saturate() {
+    use tracing_callgraph::DotOptions;
+
+    let snapshot = common::saturated();
+    // `outer` spends all of its time in its callees.
+    assert!(snapshot.to_callgrind().contains("fn=(1) outer\n0 0\n"));
+    let options = DotOptions::new()
+        .with_stats(true)
+        .with_critical_path(true)
+        .with_node_tables(true);
+    assert!(snapshot.to_dot_with(&options).contains("outer"));
+    assert!(snapshot.to_diff_dot(&snapshot, 0.1).contains("outer"));
+    assert!(snapshot.to_text().contains("outer"));
+    assert_eq!(snapshot.critical_path().len(), 3);
+    assert_eq!(snapshot.condensed().nodes().len(), 3);
+    assert!(snapshot.heaviest_call_tree("outer").is_some());
+    #[cfg(feature = "pprof")]
+    assert!(!snapshot.to_pprof().is_empty());
+    #[cfg(feature = "parquet")]
+    assert!(!snapshot.to_parquet().is_empty());
+    #[cfg(feature = "svg")]
+    assert!(snapshot.to_svg().contains("outer"));
+    #[cfg(feature = "drawio")]
+    assert!(snapshot.to_drawio().contains("outer"));
+}